@@ -0,0 +1,23 @@
+//! `HolderState` is read from account data of any length: only data of
+//! exactly `LEN` bytes unpacks, the copy is the state the program borrows in
+//! place, and it packs back to the same bytes.
+#![no_main]
+
+use codox::{pod, HolderState};
+use libfuzzer_sys::fuzz_target;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(holder_state) = HolderState::unpack_unchecked(data) else {
+        assert_ne!(data.len(), HolderState::LEN);
+        assert!(pod::load::<HolderState>(data).is_err());
+        return;
+    };
+    assert_eq!(data.len(), HolderState::LEN);
+    assert_eq!(*pod::load::<HolderState>(data).unwrap(), holder_state);
+
+    let mut packed = [0; HolderState::LEN];
+    holder_state.pack_into_slice(&mut packed);
+    assert_eq!(packed[..], data[..]);
+    assert_eq!(HolderState::unpack(data).is_ok(), holder_state.holder != Pubkey::default());
+});
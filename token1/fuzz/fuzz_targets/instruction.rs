@@ -0,0 +1,13 @@
+//! Instruction data is untrusted: decoding must never panic, and whatever
+//! decodes must encode back to the same bytes.
+#![no_main]
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use codox::CodoxTokenInstruction;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(instruction) = CodoxTokenInstruction::try_from_slice(data) {
+        assert_eq!(instruction.try_to_vec().unwrap(), data);
+    }
+});
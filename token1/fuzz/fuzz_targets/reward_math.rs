@@ -0,0 +1,48 @@
+//! Reflection reward math: the multiplier stays in range, rewards grow with
+//! the balance, the index and the multiplier, holders splitting the supply
+//! can't claim more than was accrued, and a claim stays within the pool and
+//! its bonus allocation.
+#![no_main]
+
+use codox::{reflection_reward, time_multiplier, CodoxTokenState, HolderState, REFLECTION_INDEX_SCALE};
+use libfuzzer_sys::fuzz_target;
+use solana_program::program_pack::Pack;
+
+fuzz_target!(|input: (u64, u64, u64, u128, u64, i64, i64, u64)| {
+    let (amount, supply, balance, index, pool_balance, holding_time, later, extra) = input;
+    // An index can't pass every token there is reflected onto a supply of one
+    let index = index % (u64::MAX as u128 * REFLECTION_INDEX_SCALE);
+
+    let multiplier = time_multiplier(holding_time);
+    assert!((100..=500).contains(&multiplier));
+    assert!(time_multiplier(holding_time.saturating_add(later.max(0))) >= multiplier);
+
+    let reward = reflection_reward(balance, index, multiplier);
+    assert!(reward >= reflection_reward(balance, index, 100));
+    assert!(reflection_reward(balance.saturating_add(extra), index, multiplier) >= reward);
+    assert!(reflection_reward(balance, index.saturating_add(extra as u128), multiplier) >= reward);
+
+    // Every token of the supply claiming at the base multiplier, split between two holders
+    let mut state = CodoxTokenState::unpack_unchecked(&[0; CodoxTokenState::LEN]).unwrap();
+    state.reflection_index = index.into();
+    state.accrue_reflection(amount, supply);
+    let accrued = state.reflection_index.get() - index;
+    let first = balance.min(supply);
+    let claimed = reflection_reward(first, accrued, 100) as u128 + reflection_reward(supply - first, accrued, 100) as u128;
+    assert!(claimed <= amount as u128);
+
+    let holder_state = HolderState {
+        reflection_index_checkpoint: index.into(),
+        last_reflection_claim: 0.into(),
+        reflection_balance: balance.into(),
+        ..HolderState::unpack_unchecked(&[0; HolderState::LEN]).unwrap()
+    };
+    let (reward, bonus, pending_multiplier, settled) =
+        holder_state.claimable_reflection(&state, balance, pool_balance, holding_time);
+    assert!(reward as u128 + bonus as u128 <= pool_balance as u128);
+    assert!(bonus <= state.reflection_bonus.get());
+    assert_eq!(pending_multiplier, multiplier);
+    // Whatever isn't paid stays owed
+    let owed = (balance as u128).saturating_mul(accrued);
+    assert_eq!(settled.unpaid_reflection.get() + reward as u128 * REFLECTION_INDEX_SCALE, owed);
+});
@@ -0,0 +1,23 @@
+//! `CodoxTokenState` is read from account data of any length: only data of
+//! exactly `LEN` bytes unpacks, the copy is the state the program borrows in
+//! place, and it packs back to the same bytes.
+#![no_main]
+
+use codox::{pod, CodoxTokenState};
+use libfuzzer_sys::fuzz_target;
+use solana_program::program_pack::Pack;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(state) = CodoxTokenState::unpack_unchecked(data) else {
+        assert_ne!(data.len(), CodoxTokenState::LEN);
+        assert!(pod::load::<CodoxTokenState>(data).is_err());
+        return;
+    };
+    assert_eq!(data.len(), CodoxTokenState::LEN);
+    assert_eq!(*pod::load::<CodoxTokenState>(data).unwrap(), state);
+
+    let mut packed = [0; CodoxTokenState::LEN];
+    state.pack_into_slice(&mut packed);
+    assert_eq!(packed[..], data[..]);
+    assert_eq!(CodoxTokenState::unpack(data).is_ok(), state.is_initialized.get());
+});
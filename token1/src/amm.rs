@@ -0,0 +1,151 @@
+//! Constant-product AMM interface used to add liquidity from the tax.
+//!
+//! The Codox program talks to the configured AMM through the SPL Token Swap
+//! instruction layout: a one-byte tag followed by little-endian `u64` arguments,
+//! with the accounts in the order listed on each builder. Any constant-product
+//! AMM exposing this interface, such as SPL Token Swap itself or a local mock in
+//! tests, can back swap-and-liquify.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+/// Tag of the `Swap` instruction
+pub const SWAP_TAG: u8 = 1;
+/// Tag of the `DepositAllTokenTypes` instruction
+pub const DEPOSIT_ALL_TOKEN_TYPES_TAG: u8 = 2;
+
+/// Accounts of a constant-product pool
+pub struct AmmPool<'a> {
+    /// AMM program
+    pub program_id: &'a Pubkey,
+    /// Pool state account
+    pub pool: &'a Pubkey,
+    /// Pool authority, owner of the reserves and the LP mint
+    pub authority: &'a Pubkey,
+    /// Reserve of token A
+    pub reserve_a: &'a Pubkey,
+    /// Reserve of token B
+    pub reserve_b: &'a Pubkey,
+    /// LP token mint
+    pub pool_mint: &'a Pubkey,
+    /// Account receiving the LP trading fee
+    pub fee_account: &'a Pubkey,
+    /// Token program of both reserves
+    pub token_program: &'a Pubkey,
+}
+
+/// Swap `amount_in` from `source` into `destination`
+/// Accounts expected by the AMM:
+/// 0. [] Pool state account
+/// 1. [] Pool authority
+/// 2. [signer] User transfer authority
+/// 3. [writable] Source token account
+/// 4. [writable] Pool reserve of the source token
+/// 5. [writable] Pool reserve of the destination token
+/// 6. [writable] Destination token account
+/// 7. [writable] LP token mint
+/// 8. [writable] LP fee account
+/// 9. [] Token program
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    pool: &AmmPool,
+    user_transfer_authority: &Pubkey,
+    source: &Pubkey,
+    reserve_source: &Pubkey,
+    reserve_destination: &Pubkey,
+    destination: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(17);
+    data.push(SWAP_TAG);
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    Instruction {
+        program_id: *pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pool.pool, false),
+            AccountMeta::new_readonly(*pool.authority, false),
+            AccountMeta::new_readonly(*user_transfer_authority, true),
+            AccountMeta::new(*source, false),
+            AccountMeta::new(*reserve_source, false),
+            AccountMeta::new(*reserve_destination, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new(*pool.pool_mint, false),
+            AccountMeta::new(*pool.fee_account, false),
+            AccountMeta::new_readonly(*pool.token_program, false),
+        ],
+        data,
+    }
+}
+
+/// Deposit both tokens for exactly `pool_token_amount` LP tokens
+/// Accounts expected by the AMM:
+/// 0. [] Pool state account
+/// 1. [] Pool authority
+/// 2. [signer] User transfer authority
+/// 3. [writable] Token A source account
+/// 4. [writable] Token B source account
+/// 5. [writable] Pool reserve of token A
+/// 6. [writable] Pool reserve of token B
+/// 7. [writable] LP token mint
+/// 8. [writable] LP token destination account
+/// 9. [] Token program
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_all_token_types(
+    pool: &AmmPool,
+    user_transfer_authority: &Pubkey,
+    source_a: &Pubkey,
+    source_b: &Pubkey,
+    pool_token_destination: &Pubkey,
+    pool_token_amount: u64,
+    maximum_token_a_amount: u64,
+    maximum_token_b_amount: u64,
+) -> Instruction {
+    let mut data = Vec::with_capacity(25);
+    data.push(DEPOSIT_ALL_TOKEN_TYPES_TAG);
+    data.extend_from_slice(&pool_token_amount.to_le_bytes());
+    data.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+    data.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+
+    Instruction {
+        program_id: *pool.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*pool.pool, false),
+            AccountMeta::new_readonly(*pool.authority, false),
+            AccountMeta::new_readonly(*user_transfer_authority, true),
+            AccountMeta::new(*source_a, false),
+            AccountMeta::new(*source_b, false),
+            AccountMeta::new(*pool.reserve_a, false),
+            AccountMeta::new(*pool.reserve_b, false),
+            AccountMeta::new(*pool.pool_mint, false),
+            AccountMeta::new(*pool_token_destination, false),
+            AccountMeta::new_readonly(*pool.token_program, false),
+        ],
+        data,
+    }
+}
+
+/// Output of swapping `amount_in` into reserves of `reserve_in` and `reserve_out`,
+/// before the AMM's trading fee
+pub fn swap_amount_out(amount_in: u64, reserve_in: u64, reserve_out: u64) -> u64 {
+    let denominator = reserve_in as u128 + amount_in as u128;
+    if denominator == 0 {
+        return 0;
+    }
+    (reserve_out as u128 * amount_in as u128 / denominator) as u64
+}
+
+/// LP tokens minted for depositing `amount_a` and `amount_b` into reserves of
+/// `reserve_a` and `reserve_b` with `pool_supply` LP tokens outstanding
+pub fn pool_tokens_for_deposit(amount_a: u64, amount_b: u64, reserve_a: u64, reserve_b: u64, pool_supply: u64) -> u64 {
+    if reserve_a == 0 || reserve_b == 0 {
+        return 0;
+    }
+    let from_a = amount_a as u128 * pool_supply as u128 / reserve_a as u128;
+    let from_b = amount_b as u128 * pool_supply as u128 / reserve_b as u128;
+    from_a.min(from_b).min(u64::MAX as u128) as u64
+}
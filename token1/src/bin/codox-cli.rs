@@ -0,0 +1,454 @@
+//! `codox-cli`: operate a Codox deployment from the command line.
+//!
+//! Transactions are paid for and signed by `--keypair`, then either submitted to
+//! `--url` or, with `--print`, written to stdout base64-encoded so they can be
+//! reviewed or submitted elsewhere. Amounts are in base units of the token.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use clap::{Args, Parser, Subcommand};
+use codox::{
+    instruction::{self, CodoxAccounts},
+    lottery::LotteryState,
+    token, CodoxTokenState, ConfigUpdate, HolderState, MAX_LIMIT_EXEMPT,
+};
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+#[cfg(any(feature = "staking", feature = "reflection"))]
+use solana_sdk::instruction::AccountMeta;
+
+type CliResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Pool token accounts created per transaction by `init`, to stay under the size limit
+const VAULTS_PER_TRANSACTION: usize = 3;
+
+#[derive(Parser)]
+#[command(name = "codox-cli", about = "Operate a Codox token deployment")]
+struct Cli {
+    /// RPC endpoint
+    #[arg(long, short = 'u', default_value = "http://localhost:8899")]
+    url: String,
+    /// Fee payer and signer keypair [default: ~/.config/solana/id.json]
+    #[arg(long, short = 'k')]
+    keypair: Option<String>,
+    /// Codox program
+    #[arg(long)]
+    program_id: Pubkey,
+    /// Program state account, required by every command but `init`
+    #[arg(long)]
+    state: Option<Pubkey>,
+    /// Transfer hook program, required to move tokens
+    #[cfg(feature = "token-2022")]
+    #[arg(long)]
+    hook_program: Option<Pubkey>,
+    /// Print the signed transactions instead of submitting them
+    #[arg(long)]
+    print: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the pool token accounts, then create the state account and initialize the program
+    Init(InitArgs),
+    /// Change settings (authority only)
+    UpdateConfig(Box<UpdateConfigArgs>),
+    /// Stop transfers and stakes; claims, unstakes and prize payouts keep working
+    Pause,
+    /// Resume transfers and stakes
+    Unpause,
+    /// Stake tokens
+    #[cfg(feature = "staking")]
+    Stake {
+        amount: u64,
+        /// Token account to stake from [default: the keypair's associated token account]
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Unstake tokens
+    #[cfg(feature = "staking")]
+    Unstake {
+        amount: u64,
+        /// Token account to unstake into [default: the keypair's associated token account]
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Claim reflection rewards
+    #[cfg(feature = "reflection")]
+    Claim {
+        /// Token account holding the balance [default: the keypair's associated token account]
+        #[arg(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Close the current lottery round, fixing the slot its winners are drawn from
+    #[cfg(feature = "lottery")]
+    CloseLotteryRound,
+    /// Draw the closed lottery round over all of its ticket pages
+    #[cfg(feature = "lottery")]
+    DrawLottery,
+    /// Print the program state and lottery state
+    InspectState {
+        /// Also print this wallet's holder state
+        #[arg(long)]
+        holder: Option<Pubkey>,
+    },
+}
+
+#[derive(Args)]
+struct InitArgs {
+    /// Token mint
+    #[arg(long)]
+    mint: Pubkey,
+    /// Total tax, in basis points
+    #[arg(long)]
+    tax_rate: u16,
+    #[arg(long)]
+    reflection_rate: u16,
+    #[arg(long)]
+    staking_rate: u16,
+    #[arg(long)]
+    lottery_rate: u16,
+    #[arg(long, default_value_t = 0)]
+    liquidity_rate: u16,
+    #[arg(long, default_value_t = 0)]
+    treasury_rate: u16,
+    /// Max transfer, in basis points of supply, 0 = no limit
+    #[arg(long, default_value_t = 0)]
+    max_transfer_bps: u16,
+    /// Max wallet balance, in basis points of supply, 0 = no limit
+    #[arg(long, default_value_t = 0)]
+    max_wallet_bps: u16,
+}
+
+#[derive(Args)]
+struct UpdateConfigArgs {
+    #[arg(long)]
+    max_transfer_bps: Option<u16>,
+    #[arg(long)]
+    max_wallet_bps: Option<u16>,
+    /// Wallets exempt from the launch limits, replacing the current list
+    #[arg(long, num_args = 0..=MAX_LIMIT_EXEMPT)]
+    limit_exempt: Option<Vec<Pubkey>>,
+    /// Unix timestamp trading opens at
+    #[arg(long, allow_negative_numbers = true)]
+    trading_enabled_at: Option<i64>,
+    #[arg(long)]
+    launch_window: Option<i64>,
+    #[arg(long)]
+    buy_cooldown: Option<i64>,
+    #[arg(long)]
+    sniper_tax_rate: Option<u16>,
+    #[arg(long)]
+    amm_program: Option<Pubkey>,
+    #[arg(long)]
+    amm_pool: Option<Pubkey>,
+    #[arg(long)]
+    liquify_threshold: Option<u64>,
+    #[arg(long)]
+    liquify_max_slippage_bps: Option<u16>,
+    #[arg(long)]
+    treasury_quote_vault: Option<Pubkey>,
+    #[arg(long)]
+    keeper: Option<Pubkey>,
+    #[arg(long)]
+    buyback_max_per_call: Option<u64>,
+}
+
+impl UpdateConfigArgs {
+    fn into_update(self) -> ConfigUpdate {
+        let limit_exempt = match self.limit_exempt {
+            Some(wallets) => {
+                let mut limit_exempt = [Pubkey::default(); MAX_LIMIT_EXEMPT];
+                limit_exempt[..wallets.len()].copy_from_slice(&wallets);
+                Some(limit_exempt)
+            }
+            None => None,
+        };
+        ConfigUpdate {
+            max_transfer_bps: self.max_transfer_bps,
+            max_wallet_bps: self.max_wallet_bps,
+            limit_exempt,
+            trading_enabled_at: self.trading_enabled_at,
+            launch_window: self.launch_window,
+            buy_cooldown: self.buy_cooldown,
+            sniper_tax_rate: self.sniper_tax_rate,
+            amm_program: self.amm_program,
+            amm_pool: self.amm_pool,
+            liquify_threshold: self.liquify_threshold,
+            liquify_max_slippage_bps: self.liquify_max_slippage_bps,
+            treasury_quote_vault: self.treasury_quote_vault,
+            keeper: self.keeper,
+            buyback_max_per_call: self.buyback_max_per_call,
+            paused: None,
+        }
+    }
+}
+
+struct Client {
+    rpc: RpcClient,
+    payer: Keypair,
+    program_id: Pubkey,
+    state: Option<Pubkey>,
+    // Only commands that move tokens need the hook program, and without staking and reflection there are none
+    #[cfg(feature = "token-2022")]
+    #[cfg_attr(not(any(feature = "staking", feature = "reflection")), allow(dead_code))]
+    hook_program: Option<Pubkey>,
+    print: bool,
+}
+
+impl Client {
+    /// Sign `instructions` with the payer and `signers`, then submit or print them
+    fn process(&self, instructions: &[Instruction], signers: &[&Keypair]) -> CliResult<()> {
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            self.rpc.get_latest_blockhash()?,
+        );
+        if self.print {
+            println!("{}", STANDARD.encode(bincode::serialize(&transaction)?));
+        } else {
+            println!("Signature: {}", self.rpc.send_and_confirm_transaction(&transaction)?);
+        }
+        Ok(())
+    }
+
+    /// Load the program state and the deployment's accounts
+    fn codox(&self) -> CliResult<(CodoxAccounts, CodoxTokenState)> {
+        let state = self.state.ok_or("--state is required")?;
+        let state_data = CodoxTokenState::unpack(&self.rpc.get_account_data(&state)?)?;
+        Ok((CodoxAccounts::from_state(self.program_id, state, &state_data, token::id()), state_data))
+    }
+
+    /// Token account of the payer, by default its associated token account
+    #[cfg(any(feature = "staking", feature = "reflection"))]
+    fn token_account(&self, codox: &CodoxAccounts, token_account: Option<Pubkey>) -> Pubkey {
+        token_account.unwrap_or_else(|| {
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &self.payer.pubkey(),
+                &codox.mint,
+                &codox.token_program,
+            )
+        })
+    }
+
+    /// Accounts the token program needs for a transfer from `source_owner` to `destination_owner`
+    #[cfg(any(feature = "staking", feature = "reflection"))]
+    #[cfg(not(feature = "token-2022"))]
+    fn hook_accounts(
+        &self,
+        _codox: &CodoxAccounts,
+        _source_owner: &Pubkey,
+        _destination_owner: &Pubkey,
+    ) -> CliResult<Vec<AccountMeta>> {
+        Ok(Vec::new())
+    }
+
+    /// Accounts the token program needs for a transfer from `source_owner` to `destination_owner`
+    #[cfg(any(feature = "staking", feature = "reflection"))]
+    #[cfg(feature = "token-2022")]
+    fn hook_accounts(
+        &self,
+        codox: &CodoxAccounts,
+        source_owner: &Pubkey,
+        destination_owner: &Pubkey,
+    ) -> CliResult<Vec<AccountMeta>> {
+        let hook_program = self.hook_program.ok_or("--hook-program is required")?;
+        Ok(instruction::transfer_hook_accounts(codox, &hook_program, source_owner, destination_owner))
+    }
+}
+
+/// Size of a pool token account, with room for the extensions Token-2022 mode requires
+fn token_account_len() -> usize {
+    #[cfg(not(feature = "token-2022"))]
+    return token::Account::LEN;
+    #[cfg(feature = "token-2022")]
+    {
+        use spl_token_2022::extension::ExtensionType;
+        ExtensionType::try_calculate_account_len::<token::Account>(&[
+            ExtensionType::TransferFeeAmount,
+            ExtensionType::TransferHookAccount,
+        ])
+        .expect("fixed extension list")
+    }
+}
+
+fn initialize_token_account(account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> CliResult<Instruction> {
+    #[cfg(not(feature = "token-2022"))]
+    return Ok(spl_token::instruction::initialize_account3(&token::id(), account, mint, owner)?);
+    #[cfg(feature = "token-2022")]
+    return Ok(spl_token_2022::instruction::initialize_account3(&token::id(), account, mint, owner)?);
+}
+
+fn init(client: &Client, args: InitArgs) -> CliResult<()> {
+    let payer = client.payer.pubkey();
+    let state = Keypair::new();
+    let vaults: Vec<Keypair> = (0..6).map(|_| Keypair::new()).collect();
+    let codox = CodoxAccounts {
+        program_id: client.program_id,
+        state: state.pubkey(),
+        mint: args.mint,
+        tax_vault: vaults[0].pubkey(),
+        reflection_pool: vaults[1].pubkey(),
+        staking_pool: vaults[2].pubkey(),
+        lottery_pool: vaults[3].pubkey(),
+        liquidity_vault: vaults[4].pubkey(),
+        treasury_vault: vaults[5].pubkey(),
+        token_program: token::id(),
+    };
+
+    // Pool token accounts, owned by the pool authority
+    let pool_authority = codox.pool_authority();
+    let account_len = token_account_len();
+    let lamports = client.rpc.get_minimum_balance_for_rent_exemption(account_len)?;
+    for chunk in vaults.chunks(VAULTS_PER_TRANSACTION) {
+        let mut instructions = Vec::new();
+        for vault in chunk {
+            instructions.push(system_instruction::create_account(
+                &payer,
+                &vault.pubkey(),
+                lamports,
+                account_len as u64,
+                &codox.token_program,
+            ));
+            instructions.push(initialize_token_account(&vault.pubkey(), &codox.mint, &pool_authority)?);
+        }
+        client.process(&instructions, &chunk.iter().collect::<Vec<_>>())?;
+    }
+
+    // The state account is created and initialized in one transaction, so nobody
+    // can initialize it in between
+    client.process(
+        &[
+            system_instruction::create_account(
+                &payer,
+                &codox.state,
+                client.rpc.get_minimum_balance_for_rent_exemption(CodoxTokenState::LEN)?,
+                CodoxTokenState::LEN as u64,
+                &codox.program_id,
+            ),
+            instruction::initialize_codox_token(
+                &codox,
+                &payer,
+                args.tax_rate,
+                args.reflection_rate,
+                args.staking_rate,
+                args.lottery_rate,
+                args.liquidity_rate,
+                args.treasury_rate,
+                args.max_transfer_bps,
+                args.max_wallet_bps,
+            ),
+        ],
+        &[&state],
+    )?;
+    println!("State: {}", codox.state);
+    println!("Pool authority: {}", pool_authority);
+    Ok(())
+}
+
+fn set_paused(client: &Client, paused: bool) -> CliResult<()> {
+    let (codox, _) = client.codox()?;
+    let update = ConfigUpdate {
+        paused: Some(paused),
+        ..ConfigUpdate::default()
+    };
+    client.process(&[instruction::update_config(&codox, &client.payer.pubkey(), update)], &[])
+}
+
+fn inspect_state(client: &Client, holder: Option<Pubkey>) -> CliResult<()> {
+    let (codox, state) = client.codox()?;
+    println!("Pool authority: {}", codox.pool_authority());
+    println!("{:#?}", state);
+
+    let lottery_address = codox.lottery();
+    match client.rpc.get_account_with_commitment(&lottery_address, client.rpc.commitment())?.value {
+        Some(account) => println!("Lottery {}: {:#?}", lottery_address, LotteryState::deserialize(&mut &account.data[..])?),
+        None => println!("Lottery {}: not initialized", lottery_address),
+    }
+
+    if let Some(holder) = holder {
+        let holder_state_address = codox.holder_state(&holder);
+        let holder_state = HolderState::unpack(&client.rpc.get_account_data(&holder_state_address)?)?;
+        println!("Holder state {}: {:#?}", holder_state_address, holder_state);
+    }
+    Ok(())
+}
+
+fn run(cli: Cli) -> CliResult<()> {
+    let keypair_path = match cli.keypair {
+        Some(path) => path,
+        None => format!("{}/.config/solana/id.json", std::env::var("HOME")?),
+    };
+    let client = Client {
+        rpc: RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed()),
+        payer: read_keypair_file(&keypair_path).map_err(|err| format!("reading {}: {}", keypair_path, err))?,
+        program_id: cli.program_id,
+        state: cli.state,
+        #[cfg(feature = "token-2022")]
+        hook_program: cli.hook_program,
+        print: cli.print,
+    };
+    let payer = client.payer.pubkey();
+
+    match cli.command {
+        Command::Init(args) => init(&client, args),
+        Command::UpdateConfig(args) => {
+            let (codox, _) = client.codox()?;
+            client.process(&[instruction::update_config(&codox, &payer, args.into_update())], &[])
+        }
+        Command::Pause => set_paused(&client, true),
+        Command::Unpause => set_paused(&client, false),
+        #[cfg(feature = "staking")]
+        Command::Stake { amount, token_account } => {
+            let (codox, _) = client.codox()?;
+            let token_account = client.token_account(&codox, token_account);
+            let hook_accounts = client.hook_accounts(&codox, &payer, &codox.pool_authority())?;
+            client.process(&[instruction::stake(&codox, &payer, &token_account, amount, &hook_accounts)], &[])
+        }
+        #[cfg(feature = "staking")]
+        Command::Unstake { amount, token_account } => {
+            let (codox, _) = client.codox()?;
+            let token_account = client.token_account(&codox, token_account);
+            let hook_accounts = client.hook_accounts(&codox, &codox.pool_authority(), &payer)?;
+            client.process(&[instruction::unstake(&codox, &payer, &token_account, amount, &hook_accounts)], &[])
+        }
+        #[cfg(feature = "reflection")]
+        Command::Claim { token_account } => {
+            let (codox, _) = client.codox()?;
+            let token_account = client.token_account(&codox, token_account);
+            let hook_accounts = client.hook_accounts(&codox, &codox.pool_authority(), &payer)?;
+            client.process(&[instruction::claim_reflection(&codox, &payer, &token_account, &hook_accounts)], &[])
+        }
+        #[cfg(feature = "lottery")]
+        Command::CloseLotteryRound => {
+            let (codox, _) = client.codox()?;
+            client.process(&[instruction::close_lottery_round(&codox, &payer)], &[])
+        }
+        #[cfg(feature = "lottery")]
+        Command::DrawLottery => {
+            let (codox, _) = client.codox()?;
+            let lottery = LotteryState::deserialize(&mut &client.rpc.get_account_data(&codox.lottery())?[..])?;
+            client.process(&[instruction::draw_lottery(&codox, &payer, lottery.round, lottery.ticket_pages)], &[])
+        }
+        Command::InspectState { holder } => inspect_state(&client, holder),
+    }
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,534 @@
+//! `codox-sim`: replay a transfer stream against the program's reward math.
+//!
+//! Holders start with a Zipf-distributed share of the supply, part of it staked.
+//! Each simulated day replays that day's transfers, either synthetic or from
+//! `--csv`, distributes the tax vault, lets holders claim reflection on their own
+//! schedule and draws the lottery. Tax, reflection and lottery numbers come from
+//! the program's own functions, so a change of rates can be judged before it is
+//! sent with `codox-cli update-config`. Amounts are in base units of the token.
+
+use clap::Parser;
+use codox::{
+    lottery::{draw_winners, MAX_PRIZE_TIERS},
+    reflection_accrual, reflection_bonus_share, CodoxTokenState, HolderState, TaxRates, TaxSplit, REFLECTION_INDEX_SCALE,
+};
+use bytemuck::Zeroable;
+use solana_program::pubkey::Pubkey;
+use std::{fs, process};
+
+type SimResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const DAY: i64 = 86400;
+
+#[derive(Parser)]
+#[command(name = "codox-sim", about = "Simulate Codox tokenomics over a transfer stream")]
+struct Cli {
+    /// Total tax, in basis points
+    #[arg(long, default_value_t = 500)]
+    tax_rate: u16,
+    #[arg(long, default_value_t = 200)]
+    reflection_rate: u16,
+    #[arg(long, default_value_t = 100)]
+    staking_rate: u16,
+    #[arg(long, default_value_t = 100)]
+    lottery_rate: u16,
+    #[arg(long, default_value_t = 50)]
+    liquidity_rate: u16,
+    #[arg(long, default_value_t = 50)]
+    treasury_rate: u16,
+    /// Initial supply
+    #[arg(long, default_value_t = 1_000_000_000_000_000)]
+    supply: u64,
+    #[arg(long, default_value_t = 5000)]
+    holders: usize,
+    /// Exponent of the initial Zipf distribution of balances; 0 = equal balances
+    #[arg(long, default_value_t = 1.0)]
+    zipf: f64,
+    /// Share of each holder's tokens staked, in basis points
+    #[arg(long, default_value_t = 2000)]
+    staked_bps: u16,
+    #[arg(long, default_value_t = 365)]
+    days: u32,
+    /// Synthetic transfers per day, between random holders
+    #[arg(long, default_value_t = 2000)]
+    transfers_per_day: u32,
+    /// Largest synthetic transfer, in basis points of the sender's balance
+    #[arg(long, default_value_t = 1000)]
+    max_transfer_bps: u16,
+    /// Replay transfers from a CSV of `day,from,to,amount` rows instead, with holders by index
+    #[arg(long)]
+    csv: Option<String>,
+    /// Days between each holder's reflection claims
+    #[arg(long, default_value_t = 7)]
+    claim_interval: u32,
+    /// Days between lottery draws
+    #[arg(long, default_value_t = 7)]
+    lottery_interval: u32,
+    /// Basis points of each round's prize pool per tier
+    #[arg(long, value_delimiter = ',', default_value = "5000,3000,1000")]
+    prize_tiers: Vec<u16>,
+    /// Basis points chance of paying the jackpot each draw
+    #[arg(long, default_value_t = 100)]
+    jackpot_chance: u16,
+    /// Share of the treasury's income burned by buybacks, in basis points; each `BuybackAndBurn` burns all of it
+    #[arg(long, default_value_t = 10000)]
+    buyback_bps: u16,
+    /// Seed of the synthetic transfers and the lottery draws
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+}
+
+/// A simulated transfer, between holders by index
+struct Transfer {
+    day: u32,
+    from: usize,
+    to: usize,
+    amount: u64,
+}
+
+#[derive(Clone)]
+struct Holder {
+    balance: u64,
+    staked: u64,
+    /// The program's reflection tracking for the holder
+    reflection: HolderState,
+    reflection_claimed: u64,
+    lottery_won: u64,
+}
+
+/// Program state and pool balances, as the program would hold them
+#[derive(Default)]
+struct Pools {
+    supply: u64,
+    reflection_index: u128,
+    reflection: u64,
+    /// Part of the reflection pool set aside for the holding time multiplier
+    reflection_bonus: u64,
+    staking: u64,
+    lottery: u64,
+    jackpot: u64,
+    liquidity: u64,
+    treasury: u64,
+    burned: u64,
+    /// Tax in the tax vault until the day's `DistributeTaxVault`
+    undistributed: TaxSplit,
+}
+
+/// Running totals for the report
+#[derive(Default)]
+struct Totals {
+    transfers: u64,
+    skipped_transfers: u64,
+    volume: u64,
+    tax: u64,
+    reflection_accrued: u64,
+    reflection_claimed: u64,
+    reflection_shortfall: u64,
+    short_claims: u64,
+    staking_income: u64,
+    lottery_paid: u64,
+    jackpots: u32,
+    draws: u32,
+    circulating_days: u128,
+}
+
+/// splitmix64, so runs are reproducible from `--seed`
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+struct Simulation<'a> {
+    cli: &'a Cli,
+    rates: TaxRates,
+    prize_tiers: [u16; MAX_PRIZE_TIERS],
+    holders: Vec<Holder>,
+    pools: Pools,
+    totals: Totals,
+    rng: Rng,
+    days: u32,
+}
+
+impl<'a> Simulation<'a> {
+    fn new(cli: &'a Cli, rates: TaxRates, prize_tiers: [u16; MAX_PRIZE_TIERS]) -> Self {
+        let weights: Vec<f64> = (0..cli.holders).map(|i| 1.0 / ((i + 1) as f64).powf(cli.zipf)).collect();
+        let total_weight: f64 = weights.iter().sum();
+        let mut holders: Vec<Holder> = weights
+            .iter()
+            .map(|weight| {
+                let tokens = (cli.supply as f64 * weight / total_weight) as u64;
+                let staked = (tokens as u128 * cli.staked_bps as u128 / 10000) as u64;
+                // Every holder registered with their unstaked tokens
+                Holder {
+                    balance: tokens - staked,
+                    staked,
+                    reflection: HolderState {
+                        reflection_balance: (tokens - staked).into(),
+                        ..HolderState::zeroed()
+                    },
+                    reflection_claimed: 0,
+                    lottery_won: 0,
+                }
+            })
+            .collect();
+        // Rounding leftovers go to the largest holder
+        let allotted: u64 = holders.iter().map(|holder| holder.balance + holder.staked).sum();
+        holders[0].balance += cli.supply - allotted;
+
+        let staked = holders.iter().map(|holder| holder.staked).sum();
+        Simulation {
+            cli,
+            rates,
+            prize_tiers,
+            holders,
+            pools: Pools {
+                supply: cli.supply,
+                staking: staked,
+                ..Pools::default()
+            },
+            totals: Totals::default(),
+            rng: Rng(cli.seed),
+            days: 0,
+        }
+    }
+
+    fn run(&mut self, csv: &[Transfer]) {
+        self.days = csv.last().map_or(self.cli.days, |transfer| transfer.day + 1).max(self.cli.days);
+        let mut csv = csv.iter().peekable();
+        for day in 0..self.days {
+            let now = day as i64 * DAY;
+            if self.cli.csv.is_some() {
+                while let Some(transfer) = csv.next_if(|transfer| transfer.day == day) {
+                    self.transfer(transfer.from, transfer.to, transfer.amount);
+                }
+            } else {
+                for _ in 0..self.cli.transfers_per_day {
+                    self.synthetic_transfer();
+                }
+            }
+            self.distribute_tax_vault();
+            self.buyback();
+
+            for index in 0..self.holders.len() {
+                if (index as u32 + day).is_multiple_of(self.cli.claim_interval.max(1)) {
+                    self.claim_reflection(index, now);
+                }
+            }
+            if (day + 1).is_multiple_of(self.cli.lottery_interval.max(1)) {
+                self.draw_lottery(day);
+            }
+            self.totals.circulating_days += self.holders.iter().map(|holder| holder.balance as u128).sum::<u128>();
+        }
+    }
+
+    fn synthetic_transfer(&mut self) {
+        let from = self.rng.below(self.holders.len() as u64) as usize;
+        let to = self.rng.below(self.holders.len() as u64) as usize;
+        let max = (self.holders[from].balance as u128 * self.cli.max_transfer_bps as u128 / 10000) as u64;
+        if max == 0 || from == to {
+            self.totals.skipped_transfers += 1;
+            return;
+        }
+        let amount = 1 + self.rng.below(max);
+        self.transfer(from, to, amount);
+    }
+
+    /// A `Transfer`: the net amount to the recipient and the tax into the tax vault
+    fn transfer(&mut self, from: usize, to: usize, amount: u64) {
+        if from >= self.holders.len() || to >= self.holders.len() || amount > self.holders[from].balance {
+            self.totals.skipped_transfers += 1;
+            return;
+        }
+        let (tax_amount, split) = self.rates.transfer_tax(amount, false);
+        let reflection_index = self.pools.reflection_index;
+        let sender = &mut self.holders[from];
+        sender.reflection.settle_reflection(reflection_index, sender.balance);
+        sender.reflection.reflection_balance = sender.reflection.reflection_balance.get().saturating_sub(amount).into();
+        sender.balance -= amount;
+        let recipient = &mut self.holders[to];
+        recipient.reflection.settle_reflection(reflection_index, u64::MAX);
+        recipient.reflection.reflection_balance =
+            recipient.reflection.reflection_balance.get().saturating_add(amount - tax_amount).into();
+        recipient.balance += amount - tax_amount;
+
+        self.pools.undistributed.add(&split);
+
+        self.totals.transfers += 1;
+        self.totals.volume += amount;
+        self.totals.tax += tax_amount;
+        self.totals.reflection_accrued += split.reflection;
+        self.totals.staking_income += split.staking;
+    }
+
+    /// A keeper's daily `DistributeTaxVault`: the day's tax into the pools and the reflection index
+    fn distribute_tax_vault(&mut self) {
+        let split = std::mem::take(&mut self.pools.undistributed);
+        self.pools.reflection += split.reflection;
+        self.pools.staking += split.staking;
+        self.pools.lottery += split.lottery;
+        self.pools.liquidity += split.liquidity;
+        self.pools.treasury += split.treasury;
+        let bonus = reflection_bonus_share(split.reflection);
+        self.pools.reflection_bonus += bonus;
+        self.pools.reflection_index += reflection_accrual(split.reflection - bonus, self.pools.supply);
+    }
+
+    /// The parts of the program state the reflection math reads
+    fn program_state(&self) -> CodoxTokenState {
+        CodoxTokenState {
+            reflection_index: self.pools.reflection_index.into(),
+            reflection_bonus: self.pools.reflection_bonus.into(),
+            ..CodoxTokenState::zeroed()
+        }
+    }
+
+    /// The treasury's tax share, burned by `BuybackAndBurn` when it empties the treasury vault
+    fn buyback(&mut self) {
+        let burned = (self.pools.treasury as u128 * self.cli.buyback_bps as u128 / 10000) as u64;
+        self.pools.treasury -= burned;
+        self.pools.supply -= burned;
+        self.pools.burned += burned;
+    }
+
+    /// A `ClaimReflection`, capped by the reflection pool's balance, with what it can't pay kept owed
+    fn claim_reflection(&mut self, index: usize, now: i64) {
+        let state = self.program_state();
+        let holder = &mut self.holders[index];
+        let (reward, bonus, _, settled) =
+            holder.reflection.claimable_reflection(&state, holder.balance, self.pools.reflection, now);
+        let owed = settled.unpaid_reflection.get() / REFLECTION_INDEX_SCALE;
+        if owed > 0 {
+            self.totals.reflection_shortfall += owed as u64;
+            self.totals.short_claims += 1;
+        }
+        holder.reflection = settled;
+        let payout = reward + bonus;
+        if payout > 0 {
+            self.pools.reflection -= payout;
+            self.pools.reflection_bonus -= bonus;
+            holder.balance += payout;
+            holder.reflection_claimed += payout;
+            holder.reflection.last_reflection_claim = now.into();
+            holder.reflection.reflection_balance = holder.reflection.reflection_balance.get().saturating_add(payout).into();
+            self.totals.reflection_claimed += payout;
+        }
+    }
+
+    /// A balance-mode `DrawLottery` with every holder's balance as their tickets; winners claim at once
+    fn draw_lottery(&mut self, day: u32) {
+        let mut ends = Vec::with_capacity(self.holders.len());
+        let mut total_tickets = 0u64;
+        for holder in &self.holders {
+            total_tickets += holder.balance;
+            ends.push(total_tickets);
+        }
+        let prize_pool = self.pools.lottery.saturating_sub(self.pools.jackpot);
+        let mut seed = [0u8; 32];
+        seed[..8].copy_from_slice(&self.rng.next().to_le_bytes());
+        seed[8..12].copy_from_slice(&day.to_le_bytes());
+
+        // Holders are identified by their index
+        let holder_of = |ticket: u64| Ok(holder_key(ends.partition_point(|end| *end <= ticket)));
+        let (winners, prizes) = draw_winners(
+            &seed,
+            total_tickets,
+            prize_pool,
+            &self.prize_tiers,
+            &mut self.pools.jackpot,
+            self.cli.jackpot_chance,
+            holder_of,
+        )
+        .expect("tickets are always found");
+
+        self.totals.draws += 1;
+        if prizes[MAX_PRIZE_TIERS] > 0 {
+            self.totals.jackpots += 1;
+        }
+        for (winner, prize) in winners.iter().zip(prizes) {
+            if prize > 0 {
+                let holder = &mut self.holders[holder_index(winner)];
+                holder.balance += prize;
+                holder.lottery_won += prize;
+                self.pools.lottery -= prize;
+                self.totals.lottery_paid += prize;
+            }
+        }
+    }
+
+    /// Reflection every holder could claim now at the base multiplier, against what the pool holds
+    fn reflection_liability(&self) -> u64 {
+        self.holders
+            .iter()
+            .map(|holder| {
+                let mut settled = holder.reflection;
+                settled.settle_reflection(self.pools.reflection_index, holder.balance);
+                (settled.unpaid_reflection.get() / REFLECTION_INDEX_SCALE) as u64
+            })
+            .sum()
+    }
+
+    fn report(&self) {
+        let days = self.days.max(1) as f64;
+        let years = days / 365.0;
+        let average_circulating = self.totals.circulating_days as f64 / days;
+        let staked = self.holders.iter().map(|holder| holder.staked).sum::<u64>();
+        let pct = |part: f64, whole: f64| if whole > 0.0 { 100.0 * part / whole } else { 0.0 };
+        let apy = |income: f64, principal: f64| pct(income / years, principal);
+
+        println!("Rates: {:?}", self.rates);
+        println!("Days: {}, holders: {}", self.days, self.holders.len());
+        println!();
+        println!("Transfers");
+        println!("  replayed            {}", self.totals.transfers);
+        println!("  skipped             {}", self.totals.skipped_transfers);
+        println!("  volume              {}", self.totals.volume);
+        println!("  tax collected       {}", self.totals.tax);
+        println!();
+        println!("Pool solvency");
+        let liability = self.reflection_liability();
+        println!("  reflection pool     {}", self.pools.reflection);
+        println!("  claimable now       {} at the base multiplier", liability);
+        if liability > 0 {
+            println!("  coverage            {:.1}%", pct(self.pools.reflection as f64, liability as f64));
+        }
+        println!(
+            "  short claims        {} ({} owed but capped by the pool, kept for later claims)",
+            self.totals.short_claims, self.totals.reflection_shortfall
+        );
+        println!("  bonus allocation    {} for the holding time multiplier", self.pools.reflection_bonus);
+        println!(
+            "  surplus             {} beyond both, accrued on pool and staked balances",
+            self.pools.reflection.saturating_sub(liability).saturating_sub(self.pools.reflection_bonus)
+        );
+        println!("  staking pool        {} ({} staked, {} income)", self.pools.staking, staked, self.totals.staking_income);
+        println!("  lottery pool        {} ({} jackpot)", self.pools.lottery, self.pools.jackpot);
+        println!("  liquidity vault     {}", self.pools.liquidity);
+        println!();
+        println!("Realized APY");
+        println!(
+            "  reflection          {:.2}% on the average circulating balance",
+            apy(self.totals.reflection_claimed as f64, average_circulating)
+        );
+        println!(
+            "  lottery             {:.2}% ({} draws, {} jackpots)",
+            apy(self.totals.lottery_paid as f64, average_circulating),
+            self.totals.draws,
+            self.totals.jackpots
+        );
+        println!(
+            "  staking             {:.2}% of the staked balance taxed into the pool; unstaking returns principal only",
+            apy(self.totals.staking_income as f64, staked as f64)
+        );
+        println!();
+        println!("Concentration of rewards");
+        let mut rewards: Vec<u64> = self.holders.iter().map(|holder| holder.reflection_claimed + holder.lottery_won).collect();
+        rewards.sort_unstable_by(|a, b| b.cmp(a));
+        let total_rewards = rewards.iter().sum::<u64>() as f64;
+        let top = |share: usize| rewards[..(rewards.len() * share / 100).max(1)].iter().sum::<u64>() as f64;
+        println!("  top 1% of holders   {:.1}%", pct(top(1), total_rewards));
+        println!("  top 10% of holders  {:.1}%", pct(top(10), total_rewards));
+        println!("  gini                {:.3}", gini(&rewards));
+        println!();
+        println!("Supply");
+        println!("  burned              {} ({:.3}% of the initial supply)", self.pools.burned, pct(self.pools.burned as f64, self.cli.supply as f64));
+        println!("  supply              {}", self.pools.supply);
+    }
+}
+
+fn holder_key(index: usize) -> Pubkey {
+    let mut key = [0u8; 32];
+    key[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    Pubkey::new_from_array(key)
+}
+
+fn holder_index(key: &Pubkey) -> usize {
+    u64::from_le_bytes(key.to_bytes()[..8].try_into().unwrap()) as usize
+}
+
+/// Gini coefficient of `values` sorted largest first
+fn gini(values: &[u64]) -> f64 {
+    let total: f64 = values.iter().map(|value| *value as f64).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+    let n = values.len() as f64;
+    // Ranked smallest first: G = 2 * sum(rank * value) / (n * total) - (n + 1) / n
+    let weighted: f64 = values.iter().rev().enumerate().map(|(i, value)| (i + 1) as f64 * *value as f64).sum();
+    2.0 * weighted / (n * total) - (n + 1.0) / n
+}
+
+/// Read `day,from,to,amount` rows, skipping a header and `#` comments, sorted by day
+fn read_csv(path: &str) -> SimResult<Vec<Transfer>> {
+    let mut transfers = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("day") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [day, from, to, amount] = fields[..] else {
+            return Err(format!("{path}:{}: expected day,from,to,amount", number + 1).into());
+        };
+        let parse_error = |error: std::num::ParseIntError| format!("{path}:{}: {error}", number + 1);
+        transfers.push(Transfer {
+            day: day.parse().map_err(parse_error)?,
+            from: from.parse().map_err(parse_error)?,
+            to: to.parse().map_err(parse_error)?,
+            amount: amount.parse().map_err(parse_error)?,
+        });
+    }
+    transfers.sort_by_key(|transfer| transfer.day);
+    Ok(transfers)
+}
+
+fn run(cli: &Cli) -> SimResult<()> {
+    let rates = TaxRates {
+        tax_rate: cli.tax_rate,
+        reflection_rate: cli.reflection_rate,
+        staking_rate: cli.staking_rate,
+        lottery_rate: cli.lottery_rate,
+        liquidity_rate: cli.liquidity_rate,
+        treasury_rate: cli.treasury_rate,
+        sniper_tax_rate: 0,
+    };
+    if !rates.is_valid() {
+        return Err("the split rates must add up to the tax rate, at most 10%".into());
+    }
+    if cli.holders == 0 {
+        return Err("--holders must be at least 1".into());
+    }
+    let prize_tiers: [u16; MAX_PRIZE_TIERS] = cli.prize_tiers[..]
+        .try_into()
+        .map_err(|_| format!("--prize-tiers takes {MAX_PRIZE_TIERS} tiers"))?;
+    if prize_tiers.iter().map(|tier| *tier as u32).sum::<u32>() > 10000 || cli.jackpot_chance > 10000 {
+        return Err("prize tiers and the jackpot chance are at most 10000 basis points".into());
+    }
+    let csv = match &cli.csv {
+        Some(path) => read_csv(path)?,
+        None => Vec::new(),
+    };
+
+    let mut simulation = Simulation::new(cli, rates, prize_tiers);
+    simulation.run(&csv);
+    simulation.report();
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(error) = run(&cli) {
+        eprintln!("Error: {error}");
+        process::exit(1);
+    }
+}
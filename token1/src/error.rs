@@ -0,0 +1,128 @@
+use num_derive::FromPrimitive;
+use solana_program::{decode_error::DecodeError, program_error::ProgramError};
+use thiserror::Error;
+
+/// Errors returned by the Codox program
+#[derive(Clone, Debug, Eq, Error, FromPrimitive, PartialEq)]
+pub enum CodoxError {
+    /// Program state account is not owned by this program
+    #[error("Program state account has the wrong owner")]
+    InvalidStateOwner,
+    /// Program state account has not been initialized
+    #[error("Program state account is not initialized")]
+    UninitializedState,
+    /// Program state account was already initialized
+    #[error("Program state account is already initialized")]
+    AlreadyInitialized,
+    /// A pool or vault account does not match the one stored in the state
+    #[error("Pool account does not match program state")]
+    InvalidPoolAccount,
+    /// The mint does not match the one stored in the state
+    #[error("Mint does not match program state")]
+    InvalidMint,
+    /// The mint's transfer fee does not match the configured tax rate
+    #[error("Mint transfer fee does not match the configured tax rate")]
+    TransferFeeMismatch,
+    /// The mint's withheld fees can't be withdrawn by the pool authority
+    #[error("Mint withdraw withheld authority is not the pool authority")]
+    InvalidWithdrawAuthority,
+    /// The mint does not point its transfer hook at this program
+    #[error("Mint transfer hook is not set to this program")]
+    InvalidTransferHook,
+    /// The transfer hook was invoked outside of a token transfer
+    #[error("Transfer hook invoked outside of a transfer")]
+    NotTransferring,
+    /// Taxed transfers go through the token program in Token-2022 mode
+    #[error("Use the token program to transfer in Token-2022 mode")]
+    UseTokenProgramTransfer,
+    /// The transfer amount is above the max-transaction limit
+    #[error("Transfer exceeds the max-transaction limit")]
+    TransferLimitExceeded,
+    /// The recipient's balance would be above the max-wallet limit
+    #[error("Transfer exceeds the max-wallet limit")]
+    WalletLimitExceeded,
+    /// Launch limits can only be raised or removed
+    #[error("Launch limits can't be tightened")]
+    LimitTightened,
+    /// Trading has not opened and the transfer isn't between exempt accounts
+    #[error("Trading is not enabled yet")]
+    TradingNotEnabled,
+    /// The launch settings can't change once trading has opened
+    #[error("Trading is already enabled")]
+    TradingAlreadyEnabled,
+    /// The wallet bought too recently during the launch window
+    #[error("Buy cooldown has not elapsed")]
+    BuyCooldown,
+    /// The AMM accounts don't match the configured AMM pool
+    #[error("AMM does not match program state")]
+    InvalidAmm,
+    /// The liquidity vault is below the swap-and-liquify threshold
+    #[error("Liquidity vault is below the swap-and-liquify threshold")]
+    LiquifyThresholdNotReached,
+    /// The buyback amount is zero or above the per-call maximum
+    #[error("Buyback amount exceeds the per-call maximum")]
+    BuybackLimitExceeded,
+    /// The swap returned less than the minimum output
+    #[error("Swap output is below the minimum")]
+    SlippageExceeded,
+    /// Balance snapshots only read a holder's associated token account
+    #[error("Snapshot token account isn't its owner's associated token account")]
+    InvalidSnapshotAccount,
+    /// The ticket page has no room for more entries
+    #[error("Ticket page is full")]
+    TicketPageFull,
+    /// The lottery interval has not passed since the last draw, or the round's draw slot hasn't been reached
+    #[error("Lottery draw is too early")]
+    LotteryDrawTooEarly,
+    /// The prize doesn't exist, was already claimed or has expired
+    #[error("Lottery prize is unavailable")]
+    PrizeUnavailable,
+    /// The draw wasn't given every ticket page of the round
+    #[error("Lottery ticket pages are missing")]
+    TicketPagesMissing,
+    /// The instruction doesn't apply to the lottery's mode
+    #[error("Instruction doesn't match the lottery mode")]
+    LotteryModeMismatch,
+    /// The deposit is entered in a round that hasn't been drawn yet
+    #[error("Lottery deposit is locked until the round is drawn")]
+    DepositLocked,
+    /// A new ticket page can only be opened once the last one is full
+    #[error("Last ticket page is not full")]
+    TicketPageNotFull,
+    /// Ticket pages can only be closed once their round is drawn
+    #[error("Ticket page round has not been drawn")]
+    TicketPageInUse,
+    /// The prize's claim deadline has passed
+    #[error("Lottery prize claim deadline has passed")]
+    ClaimDeadlinePassed,
+    /// The round's prizes can still be claimed
+    #[error("Lottery prize claim period has not ended")]
+    ClaimPeriodActive,
+    /// Transfers are paused by the authority
+    #[error("Program is paused")]
+    ProgramPaused,
+    /// The round has been closed for its draw and takes no more tickets
+    #[error("Lottery round is closed")]
+    LotteryRoundClosed,
+    /// The round must be closed before it can be drawn
+    #[error("Lottery round is not closed")]
+    LotteryRoundOpen,
+    /// The draw slot's hash has left the slot hashes sysvar; the round must be closed again
+    #[error("Lottery draw slot has expired")]
+    DrawSlotExpired,
+    /// The instruction's tag belongs to a retired instruction
+    #[error("Instruction has been retired")]
+    InstructionRetired,
+}
+
+impl From<CodoxError> for ProgramError {
+    fn from(e: CodoxError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+impl<T> DecodeError<T> for CodoxError {
+    fn type_of() -> &'static str {
+        "CodoxError"
+    }
+}
@@ -0,0 +1,132 @@
+//! Typed program events for indexers.
+//!
+//! Events are Borsh-serialized `CodoxEvent`s logged with `sol_log_data` as two
+//! fields, `EVENT_DISCRIMINATOR` followed by the event. They show up in the
+//! transaction logs as `Program data: <base64> <base64>` lines, which
+//! `parse_logs` turns back into events. Variants are only ever appended, so the
+//! Borsh tag of an event is stable.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+use crate::{
+    lottery::{MAX_PRIZE_TIERS, PRIZE_SLOTS},
+    ConfigUpdate, TaxSplit,
+};
+
+/// First field of every event logged by the program
+pub const EVENT_DISCRIMINATOR: [u8; 8] = *b"codoxevt";
+
+/// Prefix of the log lines written by `sol_log_data`
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Events emitted by the Codox program
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum CodoxEvent {
+    /// A taxed transfer, through `Transfer` or the Token-2022 transfer hook
+    Transfer {
+        source: Pubkey,
+        destination: Pubkey,
+        amount: u64,
+        net_amount: u64,       // Received by the destination
+        tax_amount: u64,
+        split: TaxSplit,       // Shares of the tax by pool
+        launch_buy: bool,      // Charged the sniper tax
+    },
+    Stake {
+        staker: Pubkey,
+        amount: u64,
+        staked_amount: u64,    // Staker's stake after the change
+        total_staked: u64,
+    },
+    Unstake {
+        staker: Pubkey,
+        amount: u64,
+        staked_amount: u64,    // Staker's stake after the change
+        total_staked: u64,
+    },
+    /// Reflection claimed with `ClaimReflection`
+    Claim {
+        holder: Pubkey,
+        reward: u64,
+        multiplier: u16,       // Holding time multiplier, in percent
+        total_claimed: u64,
+    },
+    /// Tickets recorded for a holder or deposit
+    LotteryEntered {
+        round: u64,
+        holder: Pubkey,
+        page: u32,
+        first_ticket: u64,
+        tickets: u64,
+    },
+    LotteryDrawn {
+        round: u64,
+        total_tickets: u64,
+        prize_pool: u64,
+        prize_tiers: [u16; MAX_PRIZE_TIERS],
+        winners: [Pubkey; PRIZE_SLOTS],
+        prizes: [u64; PRIZE_SLOTS],
+        jackpot: u64,          // Carried over after the draw
+        claim_deadline: i64,
+    },
+    /// Settings changed with `UpdateConfig`, as submitted
+    ConfigUpdated {
+        authority: Pubkey,
+        update: ConfigUpdate,
+    },
+}
+
+impl CodoxEvent {
+    /// Log the event with `sol_log_data`
+    pub fn emit(&self) {
+        // Serializing into a Vec can't fail
+        let data = borsh::to_vec(self).unwrap_or_default();
+        sol_log_data(&[&EVENT_DISCRIMINATOR, &data]);
+    }
+
+    /// Decode the fields of one `sol_log_data` call, if they are a Codox event
+    pub fn decode(fields: &[&[u8]]) -> Option<Self> {
+        match fields {
+            [discriminator, data] if *discriminator == EVENT_DISCRIMINATOR => Self::try_from_slice(data).ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Decode the events `program_id` emitted in a transaction's log messages,
+/// ignoring data logged by any other program, including programs it invokes
+pub fn parse_logs<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<CodoxEvent> {
+    let program = program_id.to_string();
+    let mut invocations: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs {
+        let line = line.as_ref();
+        if let Some(data) = line.strip_prefix(PROGRAM_DATA_PREFIX) {
+            if invocations.last() != Some(&program.as_str()) {
+                continue;
+            }
+            let fields: Option<Vec<Vec<u8>>> = data.split(' ').map(|field| STANDARD.decode(field).ok()).collect();
+            if let Some(fields) = fields {
+                let fields: Vec<&[u8]> = fields.iter().map(Vec::as_slice).collect();
+                events.extend(CodoxEvent::decode(&fields));
+            }
+            continue;
+        }
+
+        // "Program <id> invoke [depth]", then "Program <id> success" or "... failed: ..."
+        let mut words = line.split(' ');
+        if let (Some("Program"), Some(id), Some(status)) = (words.next(), words.next(), words.next()) {
+            match status {
+                "invoke" => invocations.push(id),
+                "success" | "failed:" if invocations.last() == Some(&id) => {
+                    invocations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
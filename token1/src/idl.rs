@@ -0,0 +1,1092 @@
+//! Interface description of the program for clients, rendered as JSON.
+//!
+//! Every instruction is described here with its Borsh tag, arguments and accounts
+//! in handler order, alongside the byte layout of each account the program owns
+//! and the types they refer to; the error codes are taken from `CodoxError`
+//! itself. `to_json` renders the description committed as `idl/codox.json`, and
+//! `tests/idl.rs` checks it against `CodoxTokenInstruction` and its account docs,
+//! the instruction builders and the account structs, so the IDL can't drift from
+//! the code.
+
+use std::fmt::Write;
+
+use num_traits::FromPrimitive;
+use solana_program::program_pack::Pack;
+
+use crate::{
+    error::CodoxError,
+    lottery::{LotteryDeposit, LotteryRound, LotteryState, TicketPage, MAX_PRIZE_TIERS, PRIZE_SLOTS},
+    CodoxTokenState, HolderState, MAX_LIMIT_EXEMPT,
+};
+
+/// Type of an argument or field, encoded as Borsh
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdlType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    I64,
+    U128,
+    PublicKey,
+    /// Fixed-length array, without a length prefix
+    Array(&'static IdlType, usize),
+    /// A tag byte, followed by the value when the tag is 1
+    Option(&'static IdlType),
+    /// A struct from `TYPES`
+    Defined(&'static str),
+}
+
+impl IdlType {
+    /// Encoded size, or `None` if it depends on the value
+    pub fn size(&self) -> Option<usize> {
+        Some(match self {
+            IdlType::Bool | IdlType::U8 => 1,
+            IdlType::U16 => 2,
+            IdlType::U32 => 4,
+            IdlType::U64 | IdlType::I64 => 8,
+            IdlType::U128 => 16,
+            IdlType::PublicKey => 32,
+            IdlType::Array(ty, len) => ty.size()? * len,
+            IdlType::Option(_) => return None,
+            IdlType::Defined(name) => fields_size(TYPES.iter().find(|ty| ty.name == *name)?.fields)?,
+        })
+    }
+
+    fn to_json(self) -> Json {
+        let name = match self {
+            IdlType::Bool => "bool",
+            IdlType::U8 => "u8",
+            IdlType::U16 => "u16",
+            IdlType::U32 => "u32",
+            IdlType::U64 => "u64",
+            IdlType::I64 => "i64",
+            IdlType::U128 => "u128",
+            IdlType::PublicKey => "publicKey",
+            IdlType::Array(ty, len) => return Json::Object(vec![("array", Json::Array(vec![ty.to_json(), len.into()]))]),
+            IdlType::Option(ty) => return Json::Object(vec![("option", ty.to_json())]),
+            IdlType::Defined(name) => return Json::Object(vec![("defined", name.into())]),
+        };
+        name.into()
+    }
+}
+
+/// An instruction argument or a field of an account or type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlField {
+    pub name: &'static str,
+    pub ty: IdlType,
+}
+
+const fn field(name: &'static str, ty: IdlType) -> IdlField {
+    IdlField { name, ty }
+}
+
+fn fields_size(fields: &[IdlField]) -> Option<usize> {
+    fields.iter().map(|field| field.ty.size()).sum()
+}
+
+/// An account passed to an instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlAccount {
+    pub name: &'static str,
+    pub writable: bool,
+    pub signer: bool,
+    /// May be left out, only as the last account
+    pub optional: bool,
+    /// Any number of accounts, only as the last account
+    pub variadic: bool,
+    pub docs: &'static str,
+}
+
+const fn account(name: &'static str, docs: &'static str) -> IdlAccount {
+    IdlAccount {
+        name,
+        writable: false,
+        signer: false,
+        optional: false,
+        variadic: false,
+        docs,
+    }
+}
+
+impl IdlAccount {
+    const fn writable(self) -> Self {
+        IdlAccount { writable: true, ..self }
+    }
+
+    const fn signer(self) -> Self {
+        IdlAccount { signer: true, ..self }
+    }
+
+    const fn optional(self) -> Self {
+        IdlAccount { optional: true, ..self }
+    }
+
+    const fn variadic(self) -> Self {
+        IdlAccount { variadic: true, ..self }
+    }
+}
+
+/// A `CodoxTokenInstruction` variant; its Borsh tag is its index in `INSTRUCTIONS`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlInstruction {
+    pub name: &'static str,
+    pub docs: &'static [&'static str],
+    pub accounts: &'static [IdlAccount],
+    pub args: &'static [IdlField],
+    /// Type returned through the return data
+    pub returns: Option<&'static str>,
+    /// Cargo feature the instruction is built with
+    pub feature: Option<&'static str>,
+}
+
+const fn instruction(
+    name: &'static str,
+    docs: &'static [&'static str],
+    accounts: &'static [IdlAccount],
+    args: &'static [IdlField],
+) -> IdlInstruction {
+    IdlInstruction {
+        name,
+        docs,
+        accounts,
+        args,
+        returns: None,
+        feature: None,
+    }
+}
+
+impl IdlInstruction {
+    const fn returns(self, ty: &'static str) -> Self {
+        IdlInstruction { returns: Some(ty), ..self }
+    }
+
+    const fn feature(self, feature: &'static str) -> Self {
+        IdlInstruction { feature: Some(feature), ..self }
+    }
+}
+
+/// A Borsh-encoded struct used by arguments, accounts or return data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlTypeDef {
+    pub name: &'static str,
+    pub docs: &'static str,
+    pub fields: &'static [IdlField],
+}
+
+/// An account owned by the program, its fields laid out back to back from offset 0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdlAccountType {
+    pub name: &'static str,
+    pub docs: &'static str,
+    pub size: usize,
+    pub fields: &'static [IdlField],
+}
+
+const AUTHORITY: IdlAccount = account("authority", "Token authority");
+const MINT: IdlAccount = account("mint", "Token mint");
+const STATE: IdlAccount = account("state", "Program state account");
+const TAX_VAULT: IdlAccount = account("tax_vault", "Tax vault account");
+const REFLECTION_POOL: IdlAccount = account("reflection_pool", "Reflection pool account");
+const STAKING_POOL: IdlAccount = account("staking_pool", "Staking pool account");
+const LOTTERY_POOL: IdlAccount = account("lottery_pool", "Lottery pool account");
+const LIQUIDITY_VAULT: IdlAccount = account("liquidity_vault", "Liquidity vault account");
+const TREASURY_VAULT: IdlAccount = account("treasury_vault", "Treasury vault account");
+const POOL_AUTHORITY: IdlAccount = account("pool_authority", "Pool authority");
+const LOTTERY: IdlAccount = account("lottery", "Lottery state account");
+const LOTTERY_ROUND: IdlAccount = account("lottery_round", "Lottery round account");
+const TOKEN_PROGRAM: IdlAccount = account("token_program", "Token program");
+const SYSTEM_PROGRAM: IdlAccount = account("system_program", "System program");
+const RENT: IdlAccount = account("rent", "Rent sysvar");
+const HOOK_ACCOUNTS: IdlAccount =
+    account("transfer_hook_accounts", "Transfer hook accounts, any number (Token-2022 mode)").variadic();
+const AMM_ACCOUNTS: [IdlAccount; 8] = [
+    account("amm_program", "AMM program"),
+    account("amm_pool", "AMM pool state account"),
+    account("amm_authority", "AMM pool authority"),
+    account("amm_reserve_a", "AMM reserve of token A").writable(),
+    account("amm_reserve_b", "AMM reserve of token B").writable(),
+    account("amm_pool_mint", "AMM LP token mint").writable(),
+    account("amm_fee_account", "AMM LP fee account").writable(),
+    TOKEN_PROGRAM,
+];
+
+const AMOUNT: IdlField = field("amount", IdlType::U64);
+
+/// Every `CodoxTokenInstruction`, in variant order
+pub static INSTRUCTIONS: &[IdlInstruction] = &[
+    instruction(
+        "InitializeCodoxToken",
+        &["Initialize the Codox token", "The tax vault and pools must be owned by the pool authority PDA"],
+        &[
+            AUTHORITY.signer(),
+            account("mint", "Tax token mint").writable(),
+            TAX_VAULT.writable(),
+            REFLECTION_POOL.writable(),
+            STAKING_POOL.writable(),
+            LOTTERY_POOL.writable(),
+            STATE.signer().writable(),
+            TOKEN_PROGRAM,
+            SYSTEM_PROGRAM,
+            RENT,
+            LIQUIDITY_VAULT.writable(),
+            TREASURY_VAULT.writable(),
+        ],
+        &[
+            field("tax_rate", IdlType::U16),
+            field("reflection_rate", IdlType::U16),
+            field("staking_rate", IdlType::U16),
+            field("lottery_rate", IdlType::U16),
+            field("liquidity_rate", IdlType::U16),
+            field("treasury_rate", IdlType::U16),
+            field("max_transfer_bps", IdlType::U16),
+            field("max_wallet_bps", IdlType::U16),
+        ],
+    ),
+    instruction(
+        "Transfer",
+        &[
+            "Transfer tokens with tax",
+            "The tax goes to the tax vault, and `DistributeTaxVault` moves it to the pools;",
+            "the reflection of registered senders and recipients is settled on their balances",
+            "before the transfer",
+        ],
+        &[
+            account("owner", "Source token account owner").signer(),
+            account("source", "Source token account").writable(),
+            account("destination", "Destination token account").writable(),
+            TAX_VAULT.writable(),
+            STATE.writable(),
+            TOKEN_PROGRAM,
+            MINT,
+            account("source_holder_state", "Source owner's holder state account (holder state PDA, created or not)")
+                .writable(),
+            account(
+                "recipient_holder_state",
+                "Recipient's holder state account (holder state PDA, created for buys during the launch window)",
+            )
+            .writable(),
+        ],
+        &[AMOUNT],
+    ),
+    instruction(
+        "Stake",
+        &["Stake tokens for bonus rewards"],
+        &[
+            account("staker", "Staker").signer().writable(),
+            account("staker_token", "Staker token account").writable(),
+            STAKING_POOL.writable(),
+            account("holder_state", "Staker state account (holder state PDA)").writable(),
+            TOKEN_PROGRAM,
+            SYSTEM_PROGRAM,
+            RENT,
+            STATE.writable(),
+            MINT,
+            HOOK_ACCOUNTS,
+        ],
+        &[AMOUNT],
+    )
+    .feature("staking"),
+    instruction(
+        "ClaimReflection",
+        &[
+            "Claim reflection rewards: what the tracked balance earned, up to the holder",
+            "token account's balance, boosted by the holding time multiplier",
+        ],
+        &[
+            account("holder", "Holder").signer(),
+            account("holder_token", "Holder token account").writable(),
+            REFLECTION_POOL.writable(),
+            account("holder_state", "Holder state account").writable(),
+            TOKEN_PROGRAM,
+            STATE.writable(),
+            POOL_AUTHORITY,
+            MINT,
+            HOOK_ACCOUNTS,
+        ],
+        &[],
+    )
+    .feature("reflection"),
+    instruction(
+        "ParticipateInLottery",
+        &[
+            "Retired: holders now enter the lottery through `SnapshotBalances`. The tag",
+            "stays reserved and always fails with `InstructionRetired`.",
+        ],
+        &[],
+        &[],
+    ),
+    instruction(
+        "DrawLottery",
+        &[
+            "Draw the closed round's winners by ticket into its history account once its",
+            "draw slot has passed (can be called by anyone)",
+        ],
+        &[
+            account("caller", "Caller, pays for the round account").signer().writable(),
+            LOTTERY.writable(),
+            account("lottery_round", "Lottery round account for the current round").writable(),
+            LOTTERY_POOL,
+            STATE.writable(),
+            account("slot_hashes", "Slot hashes sysvar"),
+            SYSTEM_PROGRAM,
+            account("ticket_pages", "Every ticket page of the current round, in page order").variadic(),
+        ],
+        &[],
+    )
+    .feature("lottery"),
+    instruction(
+        "DistributeTaxVault",
+        &[
+            "Move the tax vault into the reward pools (can be called by anyone): each pool's",
+            "undistributed share of the `Transfer` tax, plus anything else in the vault,",
+            "such as harvested Token-2022 fees, split by the configured rates",
+        ],
+        &[
+            TAX_VAULT.writable(),
+            REFLECTION_POOL.writable(),
+            STAKING_POOL.writable(),
+            LOTTERY_POOL.writable(),
+            STATE.writable(),
+            POOL_AUTHORITY,
+            MINT,
+            TOKEN_PROGRAM,
+            LIQUIDITY_VAULT.writable(),
+            TREASURY_VAULT.writable(),
+            HOOK_ACCOUNTS,
+        ],
+        &[],
+    ),
+    instruction(
+        "UpdateConfig",
+        &[
+            "Update the configuration (authority only); `None` leaves a setting unchanged",
+            "Launch limits can be raised or removed but never lowered or reinstated;",
+            "the trading start, launch window, buy cooldown and sniper tax can only",
+            "change before trading opens; pausing stops transfers and stakes, while",
+            "claims, unstakes and prize payouts from the pools keep working",
+        ],
+        &[AUTHORITY.signer(), STATE.writable()],
+        &[field("update", IdlType::Defined("ConfigUpdate"))],
+    ),
+    instruction(
+        "RegisterHolder",
+        &[
+            "Create a holder's state account, needed to buy during the launch window, to",
+            "earn reflection, which accrues on the token account's balance from now on, and",
+            "to take part in the lottery from the next round",
+        ],
+        &[
+            account("holder", "Holder").signer().writable(),
+            account("holder_state", "Holder state account (holder state PDA)").writable(),
+            STATE,
+            SYSTEM_PROGRAM,
+            account("holder_token", "Holder token account"),
+        ],
+        &[],
+    ),
+    instruction(
+        "SwapAndLiquify",
+        &[
+            "Swap half of the liquidity vault for the quote token through the configured",
+            "AMM and add both halves as liquidity (authority or keeper only, once the vault",
+            "reaches the threshold). LP tokens stay with the pool authority.",
+        ],
+        &[
+            account("caller", "Token authority or keeper").signer(),
+            LIQUIDITY_VAULT.writable(),
+            account("quote_vault", "Quote token account owned by the pool authority").writable(),
+            account("lp_vault", "LP token account owned by the pool authority").writable(),
+            STATE.writable(),
+            POOL_AUTHORITY,
+            AMM_ACCOUNTS[0],
+            AMM_ACCOUNTS[1],
+            AMM_ACCOUNTS[2],
+            AMM_ACCOUNTS[3],
+            AMM_ACCOUNTS[4],
+            AMM_ACCOUNTS[5],
+            AMM_ACCOUNTS[6],
+            AMM_ACCOUNTS[7],
+        ],
+        &[field("minimum_quote_out", IdlType::U64)],
+    ),
+    instruction(
+        "BuybackAndBurn",
+        &[
+            "Buy CODOX with the treasury's quote tokens through the configured AMM and",
+            "burn it along with the treasury's tax share (authority or keeper only)",
+        ],
+        &[
+            account("caller", "Token authority or keeper").signer(),
+            account("treasury_quote_vault", "Treasury quote token account").writable(),
+            account("treasury_vault", "Treasury vault account, emptied by the burn").writable(),
+            MINT.writable(),
+            STATE.writable(),
+            POOL_AUTHORITY,
+            AMM_ACCOUNTS[0],
+            AMM_ACCOUNTS[1],
+            AMM_ACCOUNTS[2],
+            AMM_ACCOUNTS[3],
+            AMM_ACCOUNTS[4],
+            AMM_ACCOUNTS[5],
+            AMM_ACCOUNTS[6],
+            AMM_ACCOUNTS[7],
+        ],
+        &[field("amount_in", IdlType::U64), field("minimum_amount_out", IdlType::U64)],
+    )
+    .feature("burn"),
+    instruction(
+        "InitializeLottery",
+        &[
+            "Create the lottery state account (authority only), starting round 0. In deposit",
+            "mode tickets come from deposits instead of wallet balances.",
+        ],
+        &[AUTHORITY.signer().writable(), LOTTERY.writable(), STATE.writable(), SYSTEM_PROGRAM],
+        &[
+            field("prize_tiers", IdlType::Array(&IdlType::U16, MAX_PRIZE_TIERS)),
+            field("jackpot_chance", IdlType::U16),
+            field("deposit_mode", IdlType::Bool),
+            field("claim_period", IdlType::I64),
+        ],
+    )
+    .feature("lottery"),
+    instruction(
+        "ClaimLotteryPrize",
+        &["Claim an escrowed prize before the round's claim deadline (winner only)"],
+        &[
+            account("winner", "Winner").signer(),
+            account("destination", "Token account receiving the prize").writable(),
+            LOTTERY_POOL.writable(),
+            LOTTERY.writable(),
+            LOTTERY_ROUND.writable(),
+            STATE,
+            POOL_AUTHORITY,
+            MINT,
+            TOKEN_PROGRAM,
+            HOOK_ACCOUNTS,
+        ],
+        &[field("round", IdlType::U64), field("slot", IdlType::U8)],
+    )
+    .feature("lottery"),
+    instruction(
+        "DepositToLottery",
+        &["Lock tokens in the lottery pool for tickets in a deposit-mode lottery"],
+        &[
+            account("depositor", "Depositor").signer().writable(),
+            account("depositor_token", "Depositor token account").writable(),
+            LOTTERY_POOL.writable(),
+            LOTTERY.writable(),
+            account("lottery_deposit", "Depositor's lottery deposit account (PDA, created on first deposit)").writable(),
+            account("holder_state", "Depositor's holder state account (holder state PDA, created or not)").writable(),
+            STATE,
+            MINT,
+            TOKEN_PROGRAM,
+            SYSTEM_PROGRAM,
+            HOOK_ACCOUNTS,
+        ],
+        &[AMOUNT],
+    )
+    .feature("lottery"),
+    instruction(
+        "WithdrawFromLottery",
+        &["Withdraw deposited principal once the round it's entered in has been drawn"],
+        &[
+            account("depositor", "Depositor").signer(),
+            account("depositor_token", "Depositor token account").writable(),
+            LOTTERY_POOL.writable(),
+            LOTTERY.writable(),
+            account("lottery_deposit", "Depositor's lottery deposit account").writable(),
+            account("holder_state", "Depositor's holder state account (holder state PDA, created or not)").writable(),
+            STATE,
+            POOL_AUTHORITY,
+            MINT,
+            TOKEN_PROGRAM,
+            HOOK_ACCOUNTS,
+        ],
+        &[AMOUNT],
+    )
+    .feature("lottery"),
+    instruction(
+        "SnapshotDeposits",
+        &[
+            "Record deposits as tickets for the current round of a deposit-mode lottery",
+            "(can be called by anyone). Each deposit is entered once a round and locked",
+            "until the round is drawn.",
+        ],
+        &[
+            account("caller", "Caller").signer(),
+            LOTTERY.writable(),
+            account("ticket_page", "The round's last ticket page").writable(),
+            STATE,
+            account("lottery_deposits", "Lottery deposit accounts, any number")
+                .writable()
+                .variadic(),
+        ],
+        &[],
+    )
+    .feature("lottery"),
+    instruction(
+        "ExpireLotteryPrizes",
+        &[
+            "Return a round's unclaimed prizes to the jackpot once its claim deadline",
+            "has passed (can be called by anyone)",
+        ],
+        &[LOTTERY.writable(), LOTTERY_ROUND.writable(), STATE],
+        &[field("round", IdlType::U64)],
+    )
+    .feature("lottery"),
+    instruction(
+        "OpenTicketPage",
+        &["Open the current round's next ticket page once the last one is full (can be called by anyone)"],
+        &[
+            account("payer", "Payer, refunded when the page is closed").signer().writable(),
+            LOTTERY.writable(),
+            account("ticket_page", "New ticket page account").writable(),
+            STATE,
+            SYSTEM_PROGRAM,
+            account("previous_ticket_page", "The round's last ticket page, if any").optional(),
+        ],
+        &[],
+    )
+    .feature("lottery"),
+    instruction(
+        "CloseTicketPage",
+        &[
+            "Close a ticket page of a drawn round, refunding its rent to the payer that",
+            "opened it (can be called by anyone)",
+        ],
+        &[
+            account("ticket_page", "Ticket page account").writable(),
+            account("payer", "Payer that opened the page").writable(),
+            LOTTERY,
+            STATE,
+        ],
+        &[],
+    )
+    .feature("lottery"),
+    instruction(
+        "PreviewClaim",
+        &[
+            "Preview the reflection `ClaimReflection` would pay now, returned as a",
+            "`preview::ClaimPreview` through the return data (read-only, for simulation)",
+        ],
+        &[
+            account("holder_token", "Holder token account"),
+            REFLECTION_POOL,
+            account("holder_state", "Holder state account"),
+            STATE,
+        ],
+        &[],
+    )
+    .returns("ClaimPreview")
+    .feature("reflection"),
+    instruction(
+        "PreviewTransferTax",
+        &[
+            "Preview the tax on a transfer of `amount` between two token accounts,",
+            "returned as a `preview::TransferTaxPreview` through the return data",
+            "(read-only, for simulation)",
+        ],
+        &[
+            account("source", "Source token account"),
+            account("destination", "Destination token account"),
+            STATE,
+            MINT,
+        ],
+        &[AMOUNT],
+    )
+    .returns("TransferTaxPreview"),
+    instruction(
+        "PreviewLotteryOdds",
+        &[
+            "Preview a holder's tickets and prizes in the current lottery round,",
+            "returned as a `preview::LotteryOddsPreview` through the return data",
+            "(read-only, for simulation)",
+        ],
+        &[
+            LOTTERY,
+            LOTTERY_POOL,
+            STATE,
+            account("ticket_pages", "Ticket pages of the current round to count the holder's tickets in, any number")
+                .variadic(),
+        ],
+        &[field("holder", IdlType::PublicKey)],
+    )
+    .returns("LotteryOddsPreview")
+    .feature("lottery"),
+    instruction(
+        "Unstake",
+        &["Withdraw staked tokens from the staking pool"],
+        &[
+            account("staker", "Staker").signer(),
+            account("staker_token", "Staker token account").writable(),
+            STAKING_POOL.writable(),
+            account("holder_state", "Staker state account (holder state PDA)").writable(),
+            STATE.writable(),
+            POOL_AUTHORITY,
+            MINT,
+            TOKEN_PROGRAM,
+            HOOK_ACCOUNTS,
+        ],
+        &[AMOUNT],
+    )
+    .feature("staking"),
+    instruction(
+        "InitializeTransferHook",
+        &["Register the program state with the transfer hook (sent to the hook program)"],
+        &[
+            account("authority", "Token authority, also the mint authority").signer().writable(),
+            account("extra_account_metas", "Extra account metas account").writable(),
+            MINT,
+            account("codox_program", "Codox program"),
+            STATE,
+            SYSTEM_PROGRAM,
+        ],
+        &[],
+    )
+    .feature("token-2022"),
+    instruction(
+        "HarvestFees",
+        &["Withdraw withheld transfer fees into the tax vault (can be called by anyone)"],
+        &[
+            MINT.writable(),
+            TAX_VAULT.writable(),
+            STATE,
+            POOL_AUTHORITY,
+            TOKEN_PROGRAM,
+            account("sources", "Token accounts holding withheld fees, any number").writable().variadic(),
+        ],
+        &[],
+    )
+    .feature("token-2022"),
+    instruction(
+        "CloseLotteryRound",
+        &[
+            "Stop the current lottery round taking tickets and fix the slot whose hash",
+            "draws it, `lottery::DRAW_SLOT_DELAY` slots ahead (authority or keeper only,",
+            "once the lottery interval has passed since the last draw)",
+        ],
+        &[account("caller", "Token authority or keeper").signer(), LOTTERY.writable(), STATE],
+        &[],
+    )
+    .feature("lottery"),
+    instruction(
+        "SnapshotBalances",
+        &[
+            "Record registered holders' balances, less what they received during the round,",
+            "as tickets for the current lottery round until it's closed (can be called by",
+            "anyone). Each holder is entered once a round from their associated token account;",
+            "empty, limit-exempt, unregistered and already entered holders are skipped.",
+        ],
+        &[
+            account("caller", "Caller").signer(),
+            LOTTERY.writable(),
+            account("ticket_page", "The round's last ticket page").writable(),
+            STATE,
+            account(
+                "holder_tokens",
+                "Holder associated token accounts, each followed by its owner's holder state",
+            )
+            .writable()
+            .variadic(),
+        ],
+        &[],
+    )
+    .feature("lottery"),
+];
+
+/// Every account owned by the program
+pub static ACCOUNTS: &[IdlAccountType] = &[
+    IdlAccountType {
+        name: "CodoxTokenState",
+        docs: "Program state",
+        size: CodoxTokenState::LEN,
+        fields: &[
+            field("is_initialized", IdlType::Bool),
+            field("authority", IdlType::PublicKey),
+            field("token_mint", IdlType::PublicKey),
+            field("tax_vault", IdlType::PublicKey),
+            field("reflection_pool", IdlType::PublicKey),
+            field("staking_pool", IdlType::PublicKey),
+            field("lottery_pool", IdlType::PublicKey),
+            field("tax_rate", IdlType::U16),
+            field("reflection_rate", IdlType::U16),
+            field("staking_rate", IdlType::U16),
+            field("lottery_rate", IdlType::U16),
+            field("total_staked", IdlType::U64),
+            field("total_reflection_distributed", IdlType::U64),
+            field("last_lottery_draw", IdlType::I64),
+            field("lottery_interval", IdlType::I64),
+            field("total_tax_collected", IdlType::U64),
+            field("reflection_index", IdlType::U128),
+            field("max_transfer_bps", IdlType::U16),
+            field("max_wallet_bps", IdlType::U16),
+            field("limit_exempt", IdlType::Array(&IdlType::PublicKey, MAX_LIMIT_EXEMPT)),
+            field("trading_enabled_at", IdlType::I64),
+            field("launch_window", IdlType::I64),
+            field("buy_cooldown", IdlType::I64),
+            field("sniper_tax_rate", IdlType::U16),
+            field("liquidity_rate", IdlType::U16),
+            field("liquidity_vault", IdlType::PublicKey),
+            field("amm_program", IdlType::PublicKey),
+            field("amm_pool", IdlType::PublicKey),
+            field("liquify_threshold", IdlType::U64),
+            field("total_liquified", IdlType::U64),
+            field("treasury_rate", IdlType::U16),
+            field("treasury_vault", IdlType::PublicKey),
+            field("treasury_quote_vault", IdlType::PublicKey),
+            field("keeper", IdlType::PublicKey),
+            field("buyback_max_per_call", IdlType::U64),
+            field("total_burned", IdlType::U64),
+            field("paused", IdlType::Bool),
+            field("undistributed", IdlType::Defined("TaxSplit")),
+            field("reflection_bonus", IdlType::U64),
+            field("liquify_max_slippage_bps", IdlType::U16),
+        ],
+    },
+    IdlAccountType {
+        name: "HolderState",
+        docs: "A holder's reward tracking, at the holder state PDA",
+        size: HolderState::LEN,
+        fields: &[
+            field("holder", IdlType::PublicKey),
+            field("last_reflection_claim", IdlType::I64),
+            field("staked_amount", IdlType::U64),
+            field("stake_time", IdlType::I64),
+            field("total_claimed", IdlType::U64),
+            field("holding_multiplier", IdlType::U16),
+            field("reflection_index_checkpoint", IdlType::U128),
+            field("last_buy_time", IdlType::I64),
+            field("reflection_balance", IdlType::U64),
+            field("unpaid_reflection", IdlType::U128),
+            field("lottery_round_start", IdlType::I64),
+            field("lottery_received", IdlType::U64),
+        ],
+    },
+    IdlAccountType {
+        name: "LotteryState",
+        docs: "Lottery state",
+        size: LotteryState::LEN,
+        fields: &[
+            field("is_initialized", IdlType::Bool),
+            field("round", IdlType::U64),
+            field("jackpot", IdlType::U64),
+            field("prizes_owed", IdlType::U64),
+            field("prize_tiers", IdlType::Array(&IdlType::U16, MAX_PRIZE_TIERS)),
+            field("jackpot_chance", IdlType::U16),
+            field("ticket_pages", IdlType::U32),
+            field("total_tickets", IdlType::U64),
+            field("deposit_mode", IdlType::Bool),
+            field("total_deposits", IdlType::U64),
+            field("claim_period", IdlType::I64),
+            field("draw_slot", IdlType::U64),
+        ],
+    },
+    IdlAccountType {
+        name: "LotteryRound",
+        docs: "Winners and prizes of a drawn round",
+        size: LotteryRound::LEN,
+        fields: &[
+            field("round", IdlType::U64),
+            field("drawn_at", IdlType::I64),
+            field("participants", IdlType::U64),
+            field("prize_pool", IdlType::U64),
+            field("jackpot", IdlType::U64),
+            field("winners", IdlType::Array(&IdlType::PublicKey, PRIZE_SLOTS)),
+            field("prizes", IdlType::Array(&IdlType::U64, PRIZE_SLOTS)),
+            field("paid", IdlType::U8),
+            field("seed", IdlType::Array(&IdlType::U8, 32)),
+            field("claim_deadline", IdlType::I64),
+            field("expired", IdlType::Bool),
+        ],
+    },
+    IdlAccountType {
+        name: "LotteryDeposit",
+        docs: "A holder's principal in a deposit-mode lottery",
+        size: LotteryDeposit::LEN,
+        fields: &[
+            field("owner", IdlType::PublicKey),
+            field("amount", IdlType::U64),
+            field("locked_round", IdlType::U64),
+            field("entered_round_start", IdlType::I64),
+        ],
+    },
+    IdlAccountType {
+        name: "TicketPage",
+        docs: "Header of a ticket page, followed in the account by `count` `TicketEntry`s in ticket order",
+        size: TicketPage::LEN,
+        fields: &[
+            field("round", IdlType::U64),
+            field("page", IdlType::U32),
+            field("count", IdlType::U32),
+            field("first_ticket", IdlType::U64),
+            field("tickets", IdlType::U64),
+            field("payer", IdlType::PublicKey),
+            field("is_initialized", IdlType::Bool),
+        ],
+    },
+];
+
+/// Structs used by instruction arguments, accounts and return data
+pub static TYPES: &[IdlTypeDef] = &[
+    IdlTypeDef {
+        name: "ConfigUpdate",
+        docs: "Settings changed by `UpdateConfig`; `None` leaves a setting unchanged",
+        fields: &[
+            field("max_transfer_bps", IdlType::Option(&IdlType::U16)),
+            field("max_wallet_bps", IdlType::Option(&IdlType::U16)),
+            field("limit_exempt", IdlType::Option(&IdlType::Array(&IdlType::PublicKey, MAX_LIMIT_EXEMPT))),
+            field("trading_enabled_at", IdlType::Option(&IdlType::I64)),
+            field("launch_window", IdlType::Option(&IdlType::I64)),
+            field("buy_cooldown", IdlType::Option(&IdlType::I64)),
+            field("sniper_tax_rate", IdlType::Option(&IdlType::U16)),
+            field("amm_program", IdlType::Option(&IdlType::PublicKey)),
+            field("amm_pool", IdlType::Option(&IdlType::PublicKey)),
+            field("liquify_threshold", IdlType::Option(&IdlType::U64)),
+            field("liquify_max_slippage_bps", IdlType::Option(&IdlType::U16)),
+            field("treasury_quote_vault", IdlType::Option(&IdlType::PublicKey)),
+            field("keeper", IdlType::Option(&IdlType::PublicKey)),
+            field("buyback_max_per_call", IdlType::Option(&IdlType::U64)),
+            field("paused", IdlType::Option(&IdlType::Bool)),
+        ],
+    },
+    IdlTypeDef {
+        name: "TaxSplit",
+        docs: "A tax amount split into its shares by the configured rates",
+        fields: &[
+            field("reflection", IdlType::U64),
+            field("staking", IdlType::U64),
+            field("lottery", IdlType::U64),
+            field("liquidity", IdlType::U64),
+            field("treasury", IdlType::U64),
+        ],
+    },
+    IdlTypeDef {
+        name: "TicketEntry",
+        docs: "A holder's range of tickets on a ticket page",
+        fields: &[
+            field("holder", IdlType::PublicKey),
+            field("first_ticket", IdlType::U64),
+            field("tickets", IdlType::U64),
+        ],
+    },
+    IdlTypeDef {
+        name: "ClaimPreview",
+        docs: "Returned by `PreviewClaim`",
+        fields: &[field("reward", IdlType::U64), field("multiplier", IdlType::U16)],
+    },
+    IdlTypeDef {
+        name: "TransferTaxPreview",
+        docs: "Returned by `PreviewTransferTax`",
+        fields: &[
+            field("net_amount", IdlType::U64),
+            field("tax_amount", IdlType::U64),
+            field("split", IdlType::Defined("TaxSplit")),
+            field("launch_buy", IdlType::Bool),
+        ],
+    },
+    IdlTypeDef {
+        name: "LotteryOddsPreview",
+        docs: "Returned by `PreviewLotteryOdds`",
+        fields: &[
+            field("round", IdlType::U64),
+            field("tickets", IdlType::U64),
+            field("total_tickets", IdlType::U64),
+            field("tier_prizes", IdlType::Array(&IdlType::U64, MAX_PRIZE_TIERS)),
+            field("jackpot", IdlType::U64),
+            field("jackpot_chance", IdlType::U16),
+        ],
+    },
+];
+
+/// Every `CodoxError`, in code order
+pub fn errors() -> impl Iterator<Item = CodoxError> {
+    (0..).map_while(CodoxError::from_u32)
+}
+
+/// The IDL as pretty-printed JSON
+pub fn to_json() -> String {
+    let instructions = INSTRUCTIONS.iter().enumerate().map(|(discriminant, instruction)| {
+        let mut object = vec![
+            ("name", instruction.name.into()),
+            ("discriminant", discriminant.into()),
+            ("docs", Json::Array(instruction.docs.iter().map(|&line| line.into()).collect())),
+            ("accounts", Json::Array(instruction.accounts.iter().map(account_json).collect())),
+            ("args", fields_json(instruction.args, false)),
+        ];
+        object.extend(instruction.returns.map(|ty| ("returns", Json::Object(vec![("defined", ty.into())]))));
+        object.extend(instruction.feature.map(|feature| ("feature", feature.into())));
+        Json::Object(object)
+    });
+    let accounts = ACCOUNTS.iter().map(|account| {
+        Json::Object(vec![
+            ("name", account.name.into()),
+            ("docs", account.docs.into()),
+            ("size", account.size.into()),
+            ("fields", fields_json(account.fields, true)),
+        ])
+    });
+    let types = TYPES.iter().map(|ty| {
+        Json::Object(vec![
+            ("name", ty.name.into()),
+            ("docs", ty.docs.into()),
+            ("fields", fields_json(ty.fields, false)),
+        ])
+    });
+    let errors = errors().map(|error| {
+        Json::Object(vec![
+            ("code", (error.clone() as usize).into()),
+            ("name", format!("{error:?}").into()),
+            ("msg", error.to_string().into()),
+        ])
+    });
+
+    let idl = Json::Object(vec![
+        ("name", "codox".into()),
+        ("version", env!("CARGO_PKG_VERSION").into()),
+        ("instructions", Json::Array(instructions.collect())),
+        ("accounts", Json::Array(accounts.collect())),
+        ("types", Json::Array(types.collect())),
+        ("errors", Json::Array(errors.collect())),
+    ]);
+    let mut json = String::new();
+    idl.write(&mut json, 0);
+    json.push('\n');
+    json
+}
+
+fn account_json(account: &IdlAccount) -> Json {
+    let mut object = vec![
+        ("name", account.name.into()),
+        ("writable", Json::Bool(account.writable)),
+        ("signer", Json::Bool(account.signer)),
+    ];
+    if account.optional {
+        object.push(("optional", Json::Bool(true)));
+    }
+    if account.variadic {
+        object.push(("variadic", Json::Bool(true)));
+    }
+    object.push(("docs", account.docs.into()));
+    Json::Object(object)
+}
+
+/// Fields in order, with their byte offsets for fixed layouts
+fn fields_json(fields: &[IdlField], offsets: bool) -> Json {
+    let mut offset = 0;
+    Json::Array(
+        fields
+            .iter()
+            .map(|field| {
+                let mut object = vec![("name", field.name.into()), ("type", field.ty.to_json())];
+                if offsets {
+                    object.push(("offset", offset.into()));
+                    offset += field.ty.size().expect("account fields have a fixed size");
+                }
+                Json::Object(object)
+            })
+            .collect(),
+    )
+}
+
+/// Just enough JSON to render the IDL
+enum Json {
+    Bool(bool),
+    Number(usize),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+/// Longest value kept on one line
+const INLINE_WIDTH: usize = 100;
+
+impl Json {
+    fn write(&self, out: &mut String, indent: usize) {
+        let inline = self.inline();
+        if inline.len() <= INLINE_WIDTH {
+            out.push_str(&inline);
+            return;
+        }
+        let (open, close, items): (char, char, Vec<(Option<&str>, &Json)>) = match self {
+            Json::Array(items) => ('[', ']', items.iter().map(|item| (None, item)).collect()),
+            Json::Object(entries) => ('{', '}', entries.iter().map(|(key, value)| (Some(*key), value)).collect()),
+            _ => unreachable!("scalars are always inline"),
+        };
+        out.push(open);
+        for (i, (key, value)) in items.iter().enumerate() {
+            out.push('\n');
+            out.push_str(&"  ".repeat(indent + 1));
+            if let Some(key) = key {
+                write_string(out, key);
+                out.push_str(": ");
+            }
+            value.write(out, indent + 1);
+            if i + 1 < items.len() {
+                out.push(',');
+            }
+        }
+        out.push('\n');
+        out.push_str(&"  ".repeat(indent));
+        out.push(close);
+    }
+
+    fn inline(&self) -> String {
+        let mut out = String::new();
+        match self {
+            Json::Bool(value) => write!(out, "{value}").unwrap(),
+            Json::Number(value) => write!(out, "{value}").unwrap(),
+            Json::String(value) => write_string(&mut out, value),
+            Json::Array(items) => {
+                let items: Vec<String> = items.iter().map(Json::inline).collect();
+                write!(out, "[{}]", items.join(", ")).unwrap();
+            }
+            Json::Object(entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let mut entry = String::new();
+                        write_string(&mut entry, key);
+                        entry + ": " + &value.inline()
+                    })
+                    .collect();
+                write!(out, "{{ {} }}", entries.join(", ")).unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value)
+    }
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,641 @@
+//! Instruction builders for clients.
+//!
+//! Each builder returns a ready-to-sign `Instruction` for one
+//! `CodoxTokenInstruction` variant, with the accounts in the order its handler
+//! reads them. A deployment's fixed accounts are gathered in `CodoxAccounts`,
+//! which also derives the program's PDAs. Builders for instructions that move
+//! tokens take `additional_accounts`, the transfer hook's accounts in Token-2022
+//! mode, which are appended as given.
+
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+    sysvar,
+};
+
+pub use crate::{
+    find_holder_state, find_pool_authority,
+    lottery::{find_lottery_deposit, find_lottery_round, find_lottery_state, find_ticket_page},
+};
+#[cfg(feature = "lottery")]
+use crate::lottery::MAX_PRIZE_TIERS;
+use crate::{amm::AmmPool, CodoxTokenInstruction, CodoxTokenState, ConfigUpdate};
+
+/// Fixed accounts of a Codox deployment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodoxAccounts {
+    /// Codox program
+    pub program_id: Pubkey,
+    /// Program state account
+    pub state: Pubkey,
+    /// Token mint
+    pub mint: Pubkey,
+    pub tax_vault: Pubkey,
+    pub reflection_pool: Pubkey,
+    pub staking_pool: Pubkey,
+    pub lottery_pool: Pubkey,
+    pub liquidity_vault: Pubkey,
+    pub treasury_vault: Pubkey,
+    /// Token program of the mint, SPL Token or Token-2022
+    pub token_program: Pubkey,
+}
+
+impl CodoxAccounts {
+    /// Accounts of the deployment whose state account `state` holds `state_data`
+    pub fn from_state(program_id: Pubkey, state: Pubkey, state_data: &CodoxTokenState, token_program: Pubkey) -> Self {
+        CodoxAccounts {
+            program_id,
+            state,
+            mint: state_data.token_mint,
+            tax_vault: state_data.tax_vault,
+            reflection_pool: state_data.reflection_pool,
+            staking_pool: state_data.staking_pool,
+            lottery_pool: state_data.lottery_pool,
+            liquidity_vault: state_data.liquidity_vault,
+            treasury_vault: state_data.treasury_vault,
+            token_program,
+        }
+    }
+
+    /// PDA that owns the tax vault and reward pools
+    pub fn pool_authority(&self) -> Pubkey {
+        find_pool_authority(&self.program_id, &self.state).0
+    }
+
+    /// A holder's state account
+    pub fn holder_state(&self, holder: &Pubkey) -> Pubkey {
+        find_holder_state(&self.program_id, &self.state, holder).0
+    }
+
+    /// Lottery state account
+    pub fn lottery(&self) -> Pubkey {
+        find_lottery_state(&self.program_id, &self.state).0
+    }
+
+    /// History account of a lottery round
+    pub fn lottery_round(&self, round: u64) -> Pubkey {
+        find_lottery_round(&self.program_id, &self.state, round).0
+    }
+
+    /// A page of a lottery round's tickets
+    pub fn ticket_page(&self, round: u64, page: u32) -> Pubkey {
+        find_ticket_page(&self.program_id, &self.state, round, page).0
+    }
+
+    /// A holder's lottery deposit account
+    pub fn lottery_deposit(&self, owner: &Pubkey) -> Pubkey {
+        find_lottery_deposit(&self.program_id, &self.state, owner).0
+    }
+
+    fn instruction(&self, accounts: Vec<AccountMeta>, instruction: CodoxTokenInstruction) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: borsh::to_vec(&instruction).expect("serializing into a Vec can't fail"),
+        }
+    }
+}
+
+/// `InitializeCodoxToken`
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_codox_token(
+    codox: &CodoxAccounts,
+    authority: &Pubkey,
+    tax_rate: u16,
+    reflection_rate: u16,
+    staking_rate: u16,
+    lottery_rate: u16,
+    liquidity_rate: u16,
+    treasury_rate: u16,
+    max_transfer_bps: u16,
+    max_wallet_bps: u16,
+) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(codox.mint, false),
+            AccountMeta::new(codox.tax_vault, false),
+            AccountMeta::new(codox.reflection_pool, false),
+            AccountMeta::new(codox.staking_pool, false),
+            AccountMeta::new(codox.lottery_pool, false),
+            AccountMeta::new(codox.state, true),
+            AccountMeta::new_readonly(codox.token_program, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(codox.liquidity_vault, false),
+            AccountMeta::new(codox.treasury_vault, false),
+        ],
+        CodoxTokenInstruction::InitializeCodoxToken {
+            tax_rate,
+            reflection_rate,
+            staking_rate,
+            lottery_rate,
+            liquidity_rate,
+            treasury_rate,
+            max_transfer_bps,
+            max_wallet_bps,
+        },
+    )
+}
+
+/// `Transfer` from `owner`'s `source` to `recipient`'s `destination`
+pub fn transfer(
+    codox: &CodoxAccounts,
+    owner: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    recipient: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new_readonly(*owner, true),
+        AccountMeta::new(*source, false),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new(codox.tax_vault, false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new(codox.holder_state(owner), false),
+        AccountMeta::new(codox.holder_state(recipient), false),
+    ];
+    codox.instruction(accounts, CodoxTokenInstruction::Transfer { amount })
+}
+
+/// `Stake`
+#[cfg(feature = "staking")]
+pub fn stake(
+    codox: &CodoxAccounts,
+    staker: &Pubkey,
+    staker_token: &Pubkey,
+    amount: u64,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*staker, true),
+        AccountMeta::new(*staker_token, false),
+        AccountMeta::new(codox.staking_pool, false),
+        AccountMeta::new(codox.holder_state(staker), false),
+        AccountMeta::new_readonly(codox.token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(codox.mint, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::Stake { amount })
+}
+
+/// `Unstake`
+#[cfg(feature = "staking")]
+pub fn unstake(
+    codox: &CodoxAccounts,
+    staker: &Pubkey,
+    staker_token: &Pubkey,
+    amount: u64,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*staker, true),
+        AccountMeta::new(*staker_token, false),
+        AccountMeta::new(codox.staking_pool, false),
+        AccountMeta::new(codox.holder_state(staker), false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::Unstake { amount })
+}
+
+/// `ClaimReflection`
+#[cfg(feature = "reflection")]
+pub fn claim_reflection(
+    codox: &CodoxAccounts,
+    holder: &Pubkey,
+    holder_token: &Pubkey,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*holder, true),
+        AccountMeta::new(*holder_token, false),
+        AccountMeta::new(codox.reflection_pool, false),
+        AccountMeta::new(codox.holder_state(holder), false),
+        AccountMeta::new_readonly(codox.token_program, false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.mint, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::ClaimReflection)
+}
+
+/// `SnapshotBalances` onto page `page` of `round`, the round's last page, of
+/// `holder_tokens`, pairs of an associated token account and its owner
+#[cfg(feature = "lottery")]
+pub fn snapshot_balances(
+    codox: &CodoxAccounts,
+    caller: &Pubkey,
+    round: u64,
+    page: u32,
+    holder_tokens: &[(Pubkey, Pubkey)],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*caller, true),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.ticket_page(round, page), false),
+        AccountMeta::new_readonly(codox.state, false),
+    ];
+    for (holder_token, owner) in holder_tokens {
+        accounts.push(AccountMeta::new(*holder_token, false));
+        accounts.push(AccountMeta::new(codox.holder_state(owner), false));
+    }
+    codox.instruction(accounts, CodoxTokenInstruction::SnapshotBalances)
+}
+
+/// `SnapshotDeposits` onto page `page` of `round`, the round's last page, of the
+/// deposits of `owners`
+#[cfg(feature = "lottery")]
+pub fn snapshot_deposits(codox: &CodoxAccounts, caller: &Pubkey, round: u64, page: u32, owners: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*caller, true),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.ticket_page(round, page), false),
+        AccountMeta::new_readonly(codox.state, false),
+    ];
+    accounts.extend(owners.iter().map(|owner| AccountMeta::new(codox.lottery_deposit(owner), false)));
+    codox.instruction(accounts, CodoxTokenInstruction::SnapshotDeposits)
+}
+
+/// `DrawLottery` for `round`, which has `ticket_pages` pages
+#[cfg(feature = "lottery")]
+pub fn draw_lottery(codox: &CodoxAccounts, caller: &Pubkey, round: u64, ticket_pages: u32) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*caller, true),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.lottery_round(round), false),
+        AccountMeta::new_readonly(codox.lottery_pool, false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(sysvar::slot_hashes::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend((0..ticket_pages).map(|page| AccountMeta::new_readonly(codox.ticket_page(round, page), false)));
+    codox.instruction(accounts, CodoxTokenInstruction::DrawLottery)
+}
+
+/// `CloseLotteryRound`, fixing the current round's draw slot
+#[cfg(feature = "lottery")]
+pub fn close_lottery_round(codox: &CodoxAccounts, caller: &Pubkey) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*caller, true),
+            AccountMeta::new(codox.lottery(), false),
+            AccountMeta::new_readonly(codox.state, false),
+        ],
+        CodoxTokenInstruction::CloseLotteryRound,
+    )
+}
+
+/// `DistributeTaxVault`
+pub fn distribute_tax_vault(codox: &CodoxAccounts, additional_accounts: &[AccountMeta]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(codox.tax_vault, false),
+        AccountMeta::new(codox.reflection_pool, false),
+        AccountMeta::new(codox.staking_pool, false),
+        AccountMeta::new(codox.lottery_pool, false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+        AccountMeta::new(codox.liquidity_vault, false),
+        AccountMeta::new(codox.treasury_vault, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::DistributeTaxVault)
+}
+
+/// `UpdateConfig`
+pub fn update_config(codox: &CodoxAccounts, authority: &Pubkey, update: ConfigUpdate) -> Instruction {
+    codox.instruction(
+        vec![AccountMeta::new_readonly(*authority, true), AccountMeta::new(codox.state, false)],
+        CodoxTokenInstruction::UpdateConfig { update: Box::new(update) },
+    )
+}
+
+/// `RegisterHolder`, with reflection accruing on `holder_token`'s balance
+pub fn register_holder(codox: &CodoxAccounts, holder: &Pubkey, holder_token: &Pubkey) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new(*holder, true),
+            AccountMeta::new(codox.holder_state(holder), false),
+            AccountMeta::new_readonly(codox.state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*holder_token, false),
+        ],
+        CodoxTokenInstruction::RegisterHolder,
+    )
+}
+
+/// `SwapAndLiquify` through `amm`, with the pool authority's quote and LP token accounts
+pub fn swap_and_liquify(
+    codox: &CodoxAccounts,
+    caller: &Pubkey,
+    amm: &AmmPool,
+    quote_vault: &Pubkey,
+    lp_vault: &Pubkey,
+    minimum_quote_out: u64,
+) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*caller, true),
+            AccountMeta::new(codox.liquidity_vault, false),
+            AccountMeta::new(*quote_vault, false),
+            AccountMeta::new(*lp_vault, false),
+            AccountMeta::new(codox.state, false),
+            AccountMeta::new_readonly(codox.pool_authority(), false),
+            AccountMeta::new_readonly(*amm.program_id, false),
+            AccountMeta::new_readonly(*amm.pool, false),
+            AccountMeta::new_readonly(*amm.authority, false),
+            AccountMeta::new(*amm.reserve_a, false),
+            AccountMeta::new(*amm.reserve_b, false),
+            AccountMeta::new(*amm.pool_mint, false),
+            AccountMeta::new(*amm.fee_account, false),
+            AccountMeta::new_readonly(*amm.token_program, false),
+        ],
+        CodoxTokenInstruction::SwapAndLiquify { minimum_quote_out },
+    )
+}
+
+/// `BuybackAndBurn` through `amm`, spending the treasury's quote tokens
+#[cfg(feature = "burn")]
+pub fn buyback_and_burn(
+    codox: &CodoxAccounts,
+    caller: &Pubkey,
+    amm: &AmmPool,
+    treasury_quote_vault: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*caller, true),
+            AccountMeta::new(*treasury_quote_vault, false),
+            AccountMeta::new(codox.treasury_vault, false),
+            AccountMeta::new(codox.mint, false),
+            AccountMeta::new(codox.state, false),
+            AccountMeta::new_readonly(codox.pool_authority(), false),
+            AccountMeta::new_readonly(*amm.program_id, false),
+            AccountMeta::new_readonly(*amm.pool, false),
+            AccountMeta::new_readonly(*amm.authority, false),
+            AccountMeta::new(*amm.reserve_a, false),
+            AccountMeta::new(*amm.reserve_b, false),
+            AccountMeta::new(*amm.pool_mint, false),
+            AccountMeta::new(*amm.fee_account, false),
+            AccountMeta::new_readonly(*amm.token_program, false),
+        ],
+        CodoxTokenInstruction::BuybackAndBurn {
+            amount_in,
+            minimum_amount_out,
+        },
+    )
+}
+
+/// `InitializeLottery`
+#[cfg(feature = "lottery")]
+pub fn initialize_lottery(
+    codox: &CodoxAccounts,
+    authority: &Pubkey,
+    prize_tiers: [u16; MAX_PRIZE_TIERS],
+    jackpot_chance: u16,
+    deposit_mode: bool,
+    claim_period: i64,
+) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(codox.lottery(), false),
+            AccountMeta::new(codox.state, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        CodoxTokenInstruction::InitializeLottery {
+            prize_tiers,
+            jackpot_chance,
+            deposit_mode,
+            claim_period,
+        },
+    )
+}
+
+/// `ClaimLotteryPrize`, paying into `destination`
+#[cfg(feature = "lottery")]
+pub fn claim_lottery_prize(
+    codox: &CodoxAccounts,
+    winner: &Pubkey,
+    destination: &Pubkey,
+    round: u64,
+    slot: u8,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*winner, true),
+        AccountMeta::new(*destination, false),
+        AccountMeta::new(codox.lottery_pool, false),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.lottery_round(round), false),
+        AccountMeta::new_readonly(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::ClaimLotteryPrize { round, slot })
+}
+
+/// `ExpireLotteryPrizes`
+#[cfg(feature = "lottery")]
+pub fn expire_lottery_prizes(codox: &CodoxAccounts, round: u64) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new(codox.lottery(), false),
+            AccountMeta::new(codox.lottery_round(round), false),
+            AccountMeta::new_readonly(codox.state, false),
+        ],
+        CodoxTokenInstruction::ExpireLotteryPrizes { round },
+    )
+}
+
+/// `DepositToLottery`
+#[cfg(feature = "lottery")]
+pub fn deposit_to_lottery(
+    codox: &CodoxAccounts,
+    depositor: &Pubkey,
+    depositor_token: &Pubkey,
+    amount: u64,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*depositor, true),
+        AccountMeta::new(*depositor_token, false),
+        AccountMeta::new(codox.lottery_pool, false),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.lottery_deposit(depositor), false),
+        AccountMeta::new(codox.holder_state(depositor), false),
+        AccountMeta::new_readonly(codox.state, false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::DepositToLottery { amount })
+}
+
+/// `WithdrawFromLottery`
+#[cfg(feature = "lottery")]
+pub fn withdraw_from_lottery(
+    codox: &CodoxAccounts,
+    depositor: &Pubkey,
+    depositor_token: &Pubkey,
+    amount: u64,
+    additional_accounts: &[AccountMeta],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*depositor, true),
+        AccountMeta::new(*depositor_token, false),
+        AccountMeta::new(codox.lottery_pool, false),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.lottery_deposit(depositor), false),
+        AccountMeta::new(codox.holder_state(depositor), false),
+        AccountMeta::new_readonly(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.mint, false),
+        AccountMeta::new_readonly(codox.token_program, false),
+    ];
+    accounts.extend_from_slice(additional_accounts);
+    codox.instruction(accounts, CodoxTokenInstruction::WithdrawFromLottery { amount })
+}
+
+/// `OpenTicketPage` for page `page` of `round`
+#[cfg(feature = "lottery")]
+pub fn open_ticket_page(codox: &CodoxAccounts, payer: &Pubkey, round: u64, page: u32) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*payer, true),
+        AccountMeta::new(codox.lottery(), false),
+        AccountMeta::new(codox.ticket_page(round, page), false),
+        AccountMeta::new_readonly(codox.state, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some(previous_page) = page.checked_sub(1) {
+        accounts.push(AccountMeta::new_readonly(codox.ticket_page(round, previous_page), false));
+    }
+    codox.instruction(accounts, CodoxTokenInstruction::OpenTicketPage)
+}
+
+/// `CloseTicketPage` for page `page` of `round`, refunding `payer`
+#[cfg(feature = "lottery")]
+pub fn close_ticket_page(codox: &CodoxAccounts, payer: &Pubkey, round: u64, page: u32) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new(codox.ticket_page(round, page), false),
+            AccountMeta::new(*payer, false),
+            AccountMeta::new_readonly(codox.lottery(), false),
+            AccountMeta::new_readonly(codox.state, false),
+        ],
+        CodoxTokenInstruction::CloseTicketPage,
+    )
+}
+
+/// `PreviewClaim` for the holder owning `holder_token`
+#[cfg(feature = "reflection")]
+pub fn preview_claim(codox: &CodoxAccounts, holder: &Pubkey, holder_token: &Pubkey) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*holder_token, false),
+            AccountMeta::new_readonly(codox.reflection_pool, false),
+            AccountMeta::new_readonly(codox.holder_state(holder), false),
+            AccountMeta::new_readonly(codox.state, false),
+        ],
+        CodoxTokenInstruction::PreviewClaim,
+    )
+}
+
+/// `PreviewTransferTax`
+pub fn preview_transfer_tax(codox: &CodoxAccounts, source: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+    codox.instruction(
+        vec![
+            AccountMeta::new_readonly(*source, false),
+            AccountMeta::new_readonly(*destination, false),
+            AccountMeta::new_readonly(codox.state, false),
+            AccountMeta::new_readonly(codox.mint, false),
+        ],
+        CodoxTokenInstruction::PreviewTransferTax { amount },
+    )
+}
+
+/// `PreviewLotteryOdds` over all `ticket_pages` pages of `round`
+#[cfg(feature = "lottery")]
+pub fn preview_lottery_odds(codox: &CodoxAccounts, holder: &Pubkey, round: u64, ticket_pages: u32) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new_readonly(codox.lottery(), false),
+        AccountMeta::new_readonly(codox.lottery_pool, false),
+        AccountMeta::new_readonly(codox.state, false),
+    ];
+    accounts.extend((0..ticket_pages).map(|page| AccountMeta::new_readonly(codox.ticket_page(round, page), false)));
+    codox.instruction(accounts, CodoxTokenInstruction::PreviewLotteryOdds { holder: *holder })
+}
+
+/// Transfer hook accounts for a Token-2022 transfer from a token account owned by
+/// `source_owner` to one owned by `destination_owner`, passed as `additional_accounts`
+#[cfg(feature = "token-2022")]
+pub fn transfer_hook_accounts(
+    codox: &CodoxAccounts,
+    hook_program_id: &Pubkey,
+    source_owner: &Pubkey,
+    destination_owner: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(*hook_program_id, false),
+        AccountMeta::new_readonly(
+            spl_transfer_hook_interface::get_extra_account_metas_address(&codox.mint, hook_program_id),
+            false,
+        ),
+        AccountMeta::new_readonly(codox.program_id, false),
+        AccountMeta::new(codox.state, false),
+        AccountMeta::new(codox.holder_state(destination_owner), false),
+        AccountMeta::new(codox.holder_state(source_owner), false),
+    ]
+}
+
+/// `InitializeTransferHook`, sent to the transfer hook program `hook_program_id`
+#[cfg(feature = "token-2022")]
+pub fn initialize_transfer_hook(codox: &CodoxAccounts, hook_program_id: &Pubkey, authority: &Pubkey) -> Instruction {
+    let extra_metas = spl_transfer_hook_interface::get_extra_account_metas_address(&codox.mint, hook_program_id);
+    Instruction {
+        program_id: *hook_program_id,
+        ..codox.instruction(
+            vec![
+                AccountMeta::new(*authority, true),
+                AccountMeta::new(extra_metas, false),
+                AccountMeta::new_readonly(codox.mint, false),
+                AccountMeta::new_readonly(codox.program_id, false),
+                AccountMeta::new_readonly(codox.state, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            CodoxTokenInstruction::InitializeTransferHook,
+        )
+    }
+}
+
+/// `HarvestFees` from `sources`
+#[cfg(feature = "token-2022")]
+pub fn harvest_fees(codox: &CodoxAccounts, sources: &[Pubkey]) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(codox.mint, false),
+        AccountMeta::new(codox.tax_vault, false),
+        AccountMeta::new_readonly(codox.state, false),
+        AccountMeta::new_readonly(codox.pool_authority(), false),
+        AccountMeta::new_readonly(codox.token_program, false),
+    ];
+    accounts.extend(sources.iter().map(|source| AccountMeta::new(*source, false)));
+    codox.instruction(accounts, CodoxTokenInstruction::HarvestFees)
+}
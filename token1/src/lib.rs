@@ -1,6 +1,8 @@
+use std::cell::{Ref, RefMut};
+
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    entrypoint,
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
     program::{invoke, invoke_signed},
@@ -11,17 +13,67 @@ use solana_program::{
     system_instruction,
     sysvar::Sysvar,
 };
-use spl_token::state::{Account, Mint};
 use borsh::{BorshDeserialize, BorshSerialize};
-use std::convert::TryInto;
+use bytemuck::{Pod, Zeroable};
+
+pub mod amm;
+pub mod error;
+pub mod events;
+pub mod idl;
+pub mod instruction;
+pub mod lottery;
+pub mod pod;
+pub mod preview;
+pub mod tax;
+pub mod token;
+#[cfg(feature = "token-2022")]
+pub mod transfer_hook;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use error::CodoxError;
+use events::CodoxEvent;
+use pod::{PodBool, PodI64, PodU128, PodU16, PodU64};
+pub use tax::{PodTaxSplit, TaxRates, TaxSplit};
+#[cfg(all(not(feature = "no-entrypoint"), feature = "transfer-hook"))]
+use transfer_hook::process_hook_instruction;
 
 #[cfg(not(feature = "no-entrypoint"))]
+use solana_program::entrypoint;
+
+#[cfg(all(not(feature = "no-entrypoint"), not(feature = "transfer-hook")))]
 entrypoint!(process_instruction);
+#[cfg(all(not(feature = "no-entrypoint"), feature = "transfer-hook"))]
+entrypoint!(process_hook_instruction);
+
+/// Seed for the PDA that owns the tax vault and reward pools
+pub const POOL_AUTHORITY_SEED: &[u8] = b"pool_authority";
+/// Seed for per-holder state accounts
+pub const HOLDER_SEED: &[u8] = b"holder";
+/// Fixed-point scale of `CodoxTokenState::reflection_index`
+pub const REFLECTION_INDEX_SCALE: u128 = 1_000_000_000_000;
+/// Basis points of the reflection rewards set aside to pay the holding time multiplier
+pub const REFLECTION_BONUS_BPS: u16 = 2000;
+/// Slippage swap-and-liquify accepts below the pool's quote until the authority changes it
+pub const DEFAULT_LIQUIFY_MAX_SLIPPAGE_BPS: u16 = 100;
+/// Number of wallets that can be exempted from the launch limits
+pub const MAX_LIMIT_EXEMPT: usize = 4;
+
+/// Find the PDA that owns the tax vault and reward pools
+pub fn find_pool_authority(program_id: &Pubkey, state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POOL_AUTHORITY_SEED, state.as_ref()], program_id)
+}
+
+/// Find a holder's state account
+pub fn find_holder_state(program_id: &Pubkey, state: &Pubkey, holder: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[HOLDER_SEED, state.as_ref(), holder.as_ref()], program_id)
+}
 
 /// Codox Token Program Instructions
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+#[derive(Debug, Clone)]
 pub enum CodoxTokenInstruction {
     /// Initialize the Codox token
+    /// The tax vault and pools must be owned by the pool authority PDA
     /// Accounts expected:
     /// 0. [signer] Token authority
     /// 1. [writable] Tax token mint
@@ -29,192 +81,754 @@ pub enum CodoxTokenInstruction {
     /// 3. [writable] Reflection pool account
     /// 4. [writable] Staking pool account
     /// 5. [writable] Lottery pool account
-    /// 6. [writable] Program state account
+    /// 6. [signer, writable] Program state account
     /// 7. [] Token program
     /// 8. [] System program
     /// 9. [] Rent sysvar
+    /// 10. [writable] Liquidity vault account
+    /// 11. [writable] Treasury vault account
     InitializeCodoxToken {
         tax_rate: u16,        // Basis points (e.g., 300 = 3%)
         reflection_rate: u16, // Basis points for reflection rewards
         staking_rate: u16,    // Basis points for staking rewards
         lottery_rate: u16,    // Basis points for lottery pool
+        liquidity_rate: u16,  // Basis points for the liquidity vault
+        treasury_rate: u16,   // Basis points for the treasury vault
+        max_transfer_bps: u16, // Max tokens per transfer, basis points of supply (0 = no limit)
+        max_wallet_bps: u16,   // Max tokens per wallet, basis points of supply (0 = no limit)
     },
 
     /// Transfer tokens with tax
+    /// The tax goes to the tax vault, and `DistributeTaxVault` moves it to the pools;
+    /// the reflection of registered senders and recipients is settled on their balances
+    /// before the transfer
     /// Accounts expected:
     /// 0. [signer] Source token account owner
     /// 1. [writable] Source token account
     /// 2. [writable] Destination token account
     /// 3. [writable] Tax vault account
-    /// 4. [writable] Reflection pool account
-    /// 5. [writable] Staking pool account
-    /// 6. [writable] Lottery pool account
-    /// 7. [writable] Program state account
-    /// 8. [] Token program
+    /// 4. [writable] Program state account
+    /// 5. [] Token program
+    /// 6. [] Token mint
+    /// 7. [writable] Source owner's holder state account (holder state PDA, created or not)
+    /// 8. [writable] Recipient's holder state account (holder state PDA, created for buys during the launch window)
     Transfer {
         amount: u64,
     },
 
     /// Stake tokens for bonus rewards
     /// Accounts expected:
-    /// 0. [signer] Staker
+    /// 0. [signer, writable] Staker
     /// 1. [writable] Staker token account
     /// 2. [writable] Staking pool account
-    /// 3. [writable] Staker state account
+    /// 3. [writable] Staker state account (holder state PDA)
     /// 4. [] Token program
     /// 5. [] System program
     /// 6. [] Rent sysvar
+    /// 7. [writable] Program state account
+    /// 8. [] Token mint
+    /// 9. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "staking")]
     Stake {
         amount: u64,
     },
 
-    /// Claim reflection rewards
+    /// Claim reflection rewards: what the tracked balance earned, up to the holder
+    /// token account's balance, boosted by the holding time multiplier
     /// Accounts expected:
     /// 0. [signer] Holder
     /// 1. [writable] Holder token account
     /// 2. [writable] Reflection pool account
     /// 3. [writable] Holder state account
     /// 4. [] Token program
+    /// 5. [writable] Program state account
+    /// 6. [] Pool authority
+    /// 7. [] Token mint
+    /// 8. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "reflection")]
     ClaimReflection,
 
-    /// Participate in lottery
+    /// Retired: holders now enter the lottery through `SnapshotBalances`. The tag
+    /// stays reserved and always fails with `InstructionRetired`.
+    ParticipateInLottery,
+
+    /// Draw the closed round's winners by ticket into its history account once its
+    /// draw slot has passed (can be called by anyone)
+    /// Accounts expected:
+    /// 0. [signer, writable] Caller, pays for the round account
+    /// 1. [writable] Lottery state account
+    /// 2. [writable] Lottery round account for the current round
+    /// 3. [] Lottery pool account
+    /// 4. [writable] Program state account
+    /// 5. [] Slot hashes sysvar
+    /// 6. [] System program
+    /// 7. [] Every ticket page of the current round, in page order
+    #[cfg(feature = "lottery")]
+    DrawLottery,
+
+    /// Move the tax vault into the reward pools (can be called by anyone): each pool's
+    /// undistributed share of the `Transfer` tax, plus anything else in the vault,
+    /// such as harvested Token-2022 fees, split by the configured rates
+    /// Accounts expected:
+    /// 0. [writable] Tax vault account
+    /// 1. [writable] Reflection pool account
+    /// 2. [writable] Staking pool account
+    /// 3. [writable] Lottery pool account
+    /// 4. [writable] Program state account
+    /// 5. [] Pool authority
+    /// 6. [] Token mint
+    /// 7. [] Token program
+    /// 8. [writable] Liquidity vault account
+    /// 9. [writable] Treasury vault account
+    /// 10. [] Transfer hook accounts, any number (Token-2022 mode)
+    DistributeTaxVault,
+
+    /// Update the configuration (authority only); `None` leaves a setting unchanged
+    /// Launch limits can be raised or removed but never lowered or reinstated;
+    /// the trading start, launch window, buy cooldown and sniper tax can only
+    /// change before trading opens; pausing stops transfers and stakes, while
+    /// claims, unstakes and prize payouts from the pools keep working
+    /// Accounts expected:
+    /// 0. [signer] Token authority
+    /// 1. [writable] Program state account
+    UpdateConfig {
+        update: Box<ConfigUpdate>,
+    },
+
+    /// Create a holder's state account, needed to buy during the launch window, to
+    /// earn reflection, which accrues on the token account's balance from now on, and
+    /// to take part in the lottery from the next round
+    /// Accounts expected:
+    /// 0. [signer, writable] Holder
+    /// 1. [writable] Holder state account (holder state PDA)
+    /// 2. [] Program state account
+    /// 3. [] System program
+    /// 4. [] Holder token account
+    RegisterHolder,
+
+    /// Swap half of the liquidity vault for the quote token through the configured
+    /// AMM and add both halves as liquidity (authority or keeper only, once the vault
+    /// reaches the threshold). LP tokens stay with the pool authority.
+    /// Accounts expected:
+    /// 0. [signer] Token authority or keeper
+    /// 1. [writable] Liquidity vault account
+    /// 2. [writable] Quote token account owned by the pool authority
+    /// 3. [writable] LP token account owned by the pool authority
+    /// 4. [writable] Program state account
+    /// 5. [] Pool authority
+    /// 6. [] AMM program
+    /// 7. [] AMM pool state account
+    /// 8. [] AMM pool authority
+    /// 9. [writable] AMM reserve of token A
+    /// 10. [writable] AMM reserve of token B
+    /// 11. [writable] AMM LP token mint
+    /// 12. [writable] AMM LP fee account
+    /// 13. [] Token program
+    SwapAndLiquify {
+        minimum_quote_out: u64, // Slippage bound on the swapped half, at most liquify_max_slippage_bps below the pool's quote
+    },
+
+    /// Buy CODOX with the treasury's quote tokens through the configured AMM and
+    /// burn it along with the treasury's tax share (authority or keeper only)
+    /// Accounts expected:
+    /// 0. [signer] Token authority or keeper
+    /// 1. [writable] Treasury quote token account
+    /// 2. [writable] Treasury vault account, emptied by the burn
+    /// 3. [writable] Token mint
+    /// 4. [writable] Program state account
+    /// 5. [] Pool authority
+    /// 6. [] AMM program
+    /// 7. [] AMM pool state account
+    /// 8. [] AMM pool authority
+    /// 9. [writable] AMM reserve of token A
+    /// 10. [writable] AMM reserve of token B
+    /// 11. [writable] AMM LP token mint
+    /// 12. [writable] AMM LP fee account
+    /// 13. [] Token program
+    #[cfg(feature = "burn")]
+    BuybackAndBurn {
+        amount_in: u64,          // Quote tokens to spend, at most buyback_max_per_call
+        minimum_amount_out: u64, // Slippage bound on the CODOX bought
+    },
+
+    /// Create the lottery state account (authority only), starting round 0. In deposit
+    /// mode tickets come from deposits instead of wallet balances.
+    /// Accounts expected:
+    /// 0. [signer, writable] Token authority
+    /// 1. [writable] Lottery state account
+    /// 2. [writable] Program state account
+    /// 3. [] System program
+    #[cfg(feature = "lottery")]
+    InitializeLottery {
+        prize_tiers: [u16; lottery::MAX_PRIZE_TIERS], // Basis points of each round's prize pool
+        jackpot_chance: u16,                          // Basis points chance of paying the jackpot each draw
+        deposit_mode: bool,                           // No-loss mode: tickets from locked deposits
+        claim_period: i64,                            // Seconds winners have to claim a prize
+    },
+
+    /// Claim an escrowed prize before the round's claim deadline (winner only)
     /// Accounts expected:
-    /// 0. [signer] Participant
-    /// 1. [writable] Participant token account
+    /// 0. [signer] Winner
+    /// 1. [writable] Token account receiving the prize
     /// 2. [writable] Lottery pool account
     /// 3. [writable] Lottery state account
-    /// 4. [] Token program
-    /// 5. [] Clock sysvar
-    ParticipateInLottery,
+    /// 4. [writable] Lottery round account
+    /// 5. [] Program state account
+    /// 6. [] Pool authority
+    /// 7. [] Token mint
+    /// 8. [] Token program
+    /// 9. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "lottery")]
+    ClaimLotteryPrize {
+        round: u64,
+        slot: u8, // Prize tier, or lottery::JACKPOT_SLOT
+    },
 
-    /// Draw lottery winner (can be called by anyone)
+    /// Lock tokens in the lottery pool for tickets in a deposit-mode lottery
     /// Accounts expected:
-    /// 0. [signer] Caller
-    /// 1. [writable] Winner token account
+    /// 0. [signer, writable] Depositor
+    /// 1. [writable] Depositor token account
+    /// 2. [writable] Lottery pool account
+    /// 3. [writable] Lottery state account
+    /// 4. [writable] Depositor's lottery deposit account (PDA, created on first deposit)
+    /// 5. [writable] Depositor's holder state account (holder state PDA, created or not)
+    /// 6. [] Program state account
+    /// 7. [] Token mint
+    /// 8. [] Token program
+    /// 9. [] System program
+    /// 10. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "lottery")]
+    DepositToLottery {
+        amount: u64,
+    },
+
+    /// Withdraw deposited principal once the round it's entered in has been drawn
+    /// Accounts expected:
+    /// 0. [signer] Depositor
+    /// 1. [writable] Depositor token account
     /// 2. [writable] Lottery pool account
     /// 3. [writable] Lottery state account
+    /// 4. [writable] Depositor's lottery deposit account
+    /// 5. [writable] Depositor's holder state account (holder state PDA, created or not)
+    /// 6. [] Program state account
+    /// 7. [] Pool authority
+    /// 8. [] Token mint
+    /// 9. [] Token program
+    /// 10. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "lottery")]
+    WithdrawFromLottery {
+        amount: u64,
+    },
+
+    /// Record deposits as tickets for the current round of a deposit-mode lottery
+    /// (can be called by anyone). Each deposit is entered once a round and locked
+    /// until the round is drawn.
+    /// Accounts expected:
+    /// 0. [signer] Caller
+    /// 1. [writable] Lottery state account
+    /// 2. [writable] The round's last ticket page
+    /// 3. [] Program state account
+    /// 4. [writable] Lottery deposit accounts, any number
+    #[cfg(feature = "lottery")]
+    SnapshotDeposits,
+
+    /// Return a round's unclaimed prizes to the jackpot once its claim deadline
+    /// has passed (can be called by anyone)
+    /// Accounts expected:
+    /// 0. [writable] Lottery state account
+    /// 1. [writable] Lottery round account
+    /// 2. [] Program state account
+    #[cfg(feature = "lottery")]
+    ExpireLotteryPrizes {
+        round: u64,
+    },
+
+    /// Open the current round's next ticket page once the last one is full (can be called by anyone)
+    /// Accounts expected:
+    /// 0. [signer, writable] Payer, refunded when the page is closed
+    /// 1. [writable] Lottery state account
+    /// 2. [writable] New ticket page account
+    /// 3. [] Program state account
+    /// 4. [] System program
+    /// 5. [] The round's last ticket page, if any
+    #[cfg(feature = "lottery")]
+    OpenTicketPage,
+
+    /// Close a ticket page of a drawn round, refunding its rent to the payer that
+    /// opened it (can be called by anyone)
+    /// Accounts expected:
+    /// 0. [writable] Ticket page account
+    /// 1. [writable] Payer that opened the page
+    /// 2. [] Lottery state account
+    /// 3. [] Program state account
+    #[cfg(feature = "lottery")]
+    CloseTicketPage,
+
+    /// Preview the reflection `ClaimReflection` would pay now, returned as a
+    /// `preview::ClaimPreview` through the return data (read-only, for simulation)
+    /// Accounts expected:
+    /// 0. [] Holder token account
+    /// 1. [] Reflection pool account
+    /// 2. [] Holder state account
+    /// 3. [] Program state account
+    #[cfg(feature = "reflection")]
+    PreviewClaim,
+
+    /// Preview the tax on a transfer of `amount` between two token accounts,
+    /// returned as a `preview::TransferTaxPreview` through the return data
+    /// (read-only, for simulation)
+    /// Accounts expected:
+    /// 0. [] Source token account
+    /// 1. [] Destination token account
+    /// 2. [] Program state account
+    /// 3. [] Token mint
+    PreviewTransferTax {
+        amount: u64,
+    },
+
+    /// Preview a holder's tickets and prizes in the current lottery round,
+    /// returned as a `preview::LotteryOddsPreview` through the return data
+    /// (read-only, for simulation)
+    /// Accounts expected:
+    /// 0. [] Lottery state account
+    /// 1. [] Lottery pool account
+    /// 2. [] Program state account
+    /// 3. [] Ticket pages of the current round to count the holder's tickets in, any number
+    #[cfg(feature = "lottery")]
+    PreviewLotteryOdds {
+        holder: Pubkey,
+    },
+
+    /// Withdraw staked tokens from the staking pool
+    /// Accounts expected:
+    /// 0. [signer] Staker
+    /// 1. [writable] Staker token account
+    /// 2. [writable] Staking pool account
+    /// 3. [writable] Staker state account (holder state PDA)
+    /// 4. [writable] Program state account
+    /// 5. [] Pool authority
+    /// 6. [] Token mint
+    /// 7. [] Token program
+    /// 8. [] Transfer hook accounts, any number (Token-2022 mode)
+    #[cfg(feature = "staking")]
+    Unstake {
+        amount: u64,
+    },
+
+    /// Register the program state with the transfer hook (sent to the hook program)
+    /// Accounts expected:
+    /// 0. [signer, writable] Token authority, also the mint authority
+    /// 1. [writable] Extra account metas account
+    /// 2. [] Token mint
+    /// 3. [] Codox program
+    /// 4. [] Program state account
+    /// 5. [] System program
+    #[cfg(feature = "token-2022")]
+    InitializeTransferHook,
+
+    /// Withdraw withheld transfer fees into the tax vault (can be called by anyone)
+    /// Accounts expected:
+    /// 0. [writable] Token mint
+    /// 1. [writable] Tax vault account
+    /// 2. [] Program state account
+    /// 3. [] Pool authority
     /// 4. [] Token program
-    /// 5. [] Clock sysvar
-    DrawLottery,
+    /// 5. [writable] Token accounts holding withheld fees, any number
+    #[cfg(feature = "token-2022")]
+    HarvestFees,
+
+    /// Stop the current lottery round taking tickets and fix the slot whose hash
+    /// draws it, `lottery::DRAW_SLOT_DELAY` slots ahead (authority or keeper only,
+    /// once the lottery interval has passed since the last draw)
+    /// Accounts expected:
+    /// 0. [signer] Token authority or keeper
+    /// 1. [writable] Lottery state account
+    /// 2. [] Program state account
+    #[cfg(feature = "lottery")]
+    CloseLotteryRound,
+
+    /// Record registered holders' balances, less what they received during the round,
+    /// as tickets for the current lottery round until it's closed (can be called by
+    /// anyone). Each holder is entered once a round from their associated token account;
+    /// empty, limit-exempt, unregistered and already entered holders are skipped.
+    /// Accounts expected:
+    /// 0. [signer] Caller
+    /// 1. [writable] Lottery state account
+    /// 2. [writable] The round's last ticket page
+    /// 3. [] Program state account
+    /// 4. [writable] Holder associated token accounts, each followed by its owner's holder state
+    #[cfg(feature = "lottery")]
+    SnapshotBalances,
+}
+
+/// Borsh encoding of `CodoxTokenInstruction` with a fixed tag per variant rather
+/// than its position, so an instruction compiled out by a feature leaves a gap
+/// instead of renumbering the ones after it
+macro_rules! instruction_tags {
+    ($($tag:literal => $(#[$cfg:meta])* $variant:ident $({ $($field:ident),* })?,)*) => {
+        impl BorshSerialize for CodoxTokenInstruction {
+            fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+                match self {
+                    $(
+                        $(#[$cfg])*
+                        CodoxTokenInstruction::$variant $({ $($field),* })? => {
+                            writer.write_all(&[$tag])?;
+                            $($(BorshSerialize::serialize($field, writer)?;)*)?
+                        }
+                    )*
+                }
+                Ok(())
+            }
+        }
+
+        impl BorshDeserialize for CodoxTokenInstruction {
+            fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+                let tag = u8::deserialize_reader(reader)?;
+                match tag {
+                    $(
+                        $(#[$cfg])*
+                        $tag => Ok(CodoxTokenInstruction::$variant $({
+                            $($field: BorshDeserialize::deserialize_reader(reader)?),*
+                        })?),
+                    )*
+                    _ => Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("Unexpected variant index: {tag}"),
+                    )),
+                }
+            }
+        }
+    };
+}
+
+instruction_tags! {
+    0 => InitializeCodoxToken {
+        tax_rate,
+        reflection_rate,
+        staking_rate,
+        lottery_rate,
+        liquidity_rate,
+        treasury_rate,
+        max_transfer_bps,
+        max_wallet_bps
+    },
+    1 => Transfer { amount },
+    2 => #[cfg(feature = "staking")] Stake { amount },
+    3 => #[cfg(feature = "reflection")] ClaimReflection,
+    4 => ParticipateInLottery,
+    5 => #[cfg(feature = "lottery")] DrawLottery,
+    6 => DistributeTaxVault,
+    7 => UpdateConfig { update },
+    8 => RegisterHolder,
+    9 => SwapAndLiquify { minimum_quote_out },
+    10 => #[cfg(feature = "burn")] BuybackAndBurn { amount_in, minimum_amount_out },
+    11 => #[cfg(feature = "lottery")] InitializeLottery { prize_tiers, jackpot_chance, deposit_mode, claim_period },
+    12 => #[cfg(feature = "lottery")] ClaimLotteryPrize { round, slot },
+    13 => #[cfg(feature = "lottery")] DepositToLottery { amount },
+    14 => #[cfg(feature = "lottery")] WithdrawFromLottery { amount },
+    15 => #[cfg(feature = "lottery")] SnapshotDeposits,
+    16 => #[cfg(feature = "lottery")] ExpireLotteryPrizes { round },
+    17 => #[cfg(feature = "lottery")] OpenTicketPage,
+    18 => #[cfg(feature = "lottery")] CloseTicketPage,
+    19 => #[cfg(feature = "reflection")] PreviewClaim,
+    20 => PreviewTransferTax { amount },
+    21 => #[cfg(feature = "lottery")] PreviewLotteryOdds { holder },
+    22 => #[cfg(feature = "staking")] Unstake { amount },
+    23 => #[cfg(feature = "token-2022")] InitializeTransferHook,
+    24 => #[cfg(feature = "token-2022")] HarvestFees,
+    25 => #[cfg(feature = "lottery")] CloseLotteryRound,
+    26 => #[cfg(feature = "lottery")] SnapshotBalances,
+}
+
+/// Settings changed by `UpdateConfig`; `None` leaves a setting unchanged
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigUpdate {
+    pub max_transfer_bps: Option<u16>,
+    pub max_wallet_bps: Option<u16>,
+    pub limit_exempt: Option<[Pubkey; MAX_LIMIT_EXEMPT]>,
+    pub trading_enabled_at: Option<i64>,
+    pub launch_window: Option<i64>,
+    pub buy_cooldown: Option<i64>,
+    pub sniper_tax_rate: Option<u16>,
+    pub amm_program: Option<Pubkey>,
+    pub amm_pool: Option<Pubkey>,
+    pub liquify_threshold: Option<u64>,
+    pub liquify_max_slippage_bps: Option<u16>,
+    pub treasury_quote_vault: Option<Pubkey>,
+    pub keeper: Option<Pubkey>,
+    pub buyback_max_per_call: Option<u64>,
+    pub paused: Option<bool>,
 }
 
-/// Program state
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+/// Program state, read in place from the account data
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
 pub struct CodoxTokenState {
-    pub is_initialized: bool,
+    pub is_initialized: PodBool,
     pub authority: Pubkey,
     pub token_mint: Pubkey,
     pub tax_vault: Pubkey,
     pub reflection_pool: Pubkey,
     pub staking_pool: Pubkey,
     pub lottery_pool: Pubkey,
-    pub tax_rate: u16,
-    pub reflection_rate: u16,
-    pub staking_rate: u16,
-    pub lottery_rate: u16,
-    pub total_staked: u64,
-    pub total_reflection_distributed: u64,
-    pub last_lottery_draw: i64,
-    pub lottery_interval: i64, // seconds
+    pub tax_rate: PodU16,
+    pub reflection_rate: PodU16,
+    pub staking_rate: PodU16,
+    pub lottery_rate: PodU16,
+    pub total_staked: PodU64,
+    pub total_reflection_distributed: PodU64,
+    pub last_lottery_draw: PodI64,
+    pub lottery_interval: PodI64, // seconds
+    pub total_tax_collected: PodU64,
+    pub reflection_index: PodU128, // Reflection per token, scaled by REFLECTION_INDEX_SCALE
+    pub max_transfer_bps: PodU16, // Basis points of supply, 0 = no limit
+    pub max_wallet_bps: PodU16,   // Basis points of supply, 0 = no limit
+    pub limit_exempt: [Pubkey; MAX_LIMIT_EXEMPT], // Wallets exempt from the launch limits, e.g. the treasury
+    pub trading_enabled_at: PodI64, // Unix timestamp, only exempt accounts trade before it
+    pub launch_window: PodI64,      // seconds after trading opens with the buy cooldown and sniper tax
+    pub buy_cooldown: PodI64,       // seconds between buys per wallet during the launch window
+    pub sniper_tax_rate: PodU16,    // Basis points charged on launch window buys
+    pub liquidity_rate: PodU16,
+    pub liquidity_vault: Pubkey,
+    pub amm_program: Pubkey,        // Constant-product AMM used by swap-and-liquify
+    pub amm_pool: Pubkey,           // AMM pool state account
+    pub liquify_threshold: PodU64,  // Liquidity vault balance that triggers swap-and-liquify
+    pub total_liquified: PodU64,    // Tokens added as liquidity
+    pub treasury_rate: PodU16,
+    pub treasury_vault: Pubkey,
+    pub treasury_quote_vault: Pubkey, // Quote tokens spent on buybacks, owned by the pool authority
+    pub keeper: Pubkey,               // May trigger buybacks and swap-and-liquify besides the authority
+    pub buyback_max_per_call: PodU64, // Quote tokens, 0 = buybacks disabled
+    pub total_burned: PodU64,
+    pub paused: PodBool,              // Only payouts from the Codox pools can move tokens
+    pub undistributed: PodTaxSplit,   // Transfer tax in the tax vault by pool, until DistributeTaxVault
+    pub reflection_bonus: PodU64,     // Reflection pool tokens set aside for the holding time multiplier
+    pub liquify_max_slippage_bps: PodU16, // Lowest minimum_quote_out swap-and-liquify accepts, below the pool's quote
+}
+
+// Pins the layout: fields back to back with no padding, in the order and at the
+// offsets the hand-written Pack encoding used, so accounts it wrote read the same.
+// A field added, removed or resized fails here until the size is updated on purpose.
+const _: () = assert!(std::mem::size_of::<CodoxTokenState>() == 694);
+const _: () = assert!(std::mem::align_of::<CodoxTokenState>() == 1);
+
+impl CodoxTokenState {
+    /// Whether a token account is the tax vault, a reward pool, the liquidity vault or the treasury vault
+    pub fn is_pool(&self, token_account: &Pubkey) -> bool {
+        [
+            self.tax_vault,
+            self.reflection_pool,
+            self.staking_pool,
+            self.lottery_pool,
+            self.liquidity_vault,
+            self.treasury_vault,
+        ]
+        .contains(token_account)
+    }
+
+    /// Whether a token account skips the launch limits: the Codox pools and
+    /// accounts owned by an exempt wallet (DEX pools are exempted by their owner)
+    pub fn is_limit_exempt(&self, token_account: &Pubkey, owner: &Pubkey) -> bool {
+        self.is_pool(token_account) || (*owner != Pubkey::default() && self.limit_exempt.contains(owner))
+    }
+
+    /// Check a transfer of `amount` against the launch limits. The max-transaction
+    /// limit applies unless either side is exempt; the max-wallet limit applies to
+    /// the recipient's balance after the transfer unless the recipient is exempt.
+    pub fn check_launch_limits(
+        &self,
+        supply: u64,
+        amount: u64,
+        source_exempt: bool,
+        destination_exempt: bool,
+        destination_balance: u64,
+    ) -> ProgramResult {
+        let limit = |bps: u16| (supply as u128 * bps as u128 / 10000) as u64;
+        let (max_transfer_bps, max_wallet_bps) = (self.max_transfer_bps.get(), self.max_wallet_bps.get());
+        if max_transfer_bps != 0 && !source_exempt && !destination_exempt && amount > limit(max_transfer_bps) {
+            return Err(CodoxError::TransferLimitExceeded.into());
+        }
+        if max_wallet_bps != 0 && !destination_exempt && destination_balance > limit(max_wallet_bps) {
+            return Err(CodoxError::WalletLimitExceeded.into());
+        }
+        Ok(())
+    }
+
+    /// Check that tokens may leave `source`: while paused only the Codox pools pay out,
+    /// so claims and unstakes keep working
+    pub fn check_not_paused(&self, source: &Pubkey) -> ProgramResult {
+        if self.paused.get() && !self.is_pool(source) {
+            return Err(CodoxError::ProgramPaused.into());
+        }
+        Ok(())
+    }
+
+    /// Check a transfer against the trading start and return whether it is a launch
+    /// window buy: a non-exempt wallet receiving from an exempt account such as a DEX pool
+    pub fn check_trading_open(&self, now: i64, source_exempt: bool, destination_exempt: bool) -> Result<bool, ProgramError> {
+        let trading_enabled_at = self.trading_enabled_at.get();
+        if now < trading_enabled_at && !(source_exempt && destination_exempt) {
+            return Err(CodoxError::TradingNotEnabled.into());
+        }
+        Ok(now < trading_enabled_at.saturating_add(self.launch_window.get()) && source_exempt && !destination_exempt)
+    }
+
+    /// The configured tax rates
+    pub fn tax_rates(&self) -> TaxRates {
+        TaxRates {
+            tax_rate: self.tax_rate.get(),
+            reflection_rate: self.reflection_rate.get(),
+            staking_rate: self.staking_rate.get(),
+            lottery_rate: self.lottery_rate.get(),
+            liquidity_rate: self.liquidity_rate.get(),
+            treasury_rate: self.treasury_rate.get(),
+            sniper_tax_rate: self.sniper_tax_rate.get(),
+        }
+    }
+
+    /// Split a tax amount into its shares; see `TaxRates::split`
+    pub fn split_tax(&self, tax_amount: u64) -> TaxSplit {
+        self.tax_rates().split(tax_amount)
+    }
+
+    /// Tax on a `Transfer` of `amount` and its split; see `TaxRates::transfer_tax`
+    pub fn transfer_tax(&self, amount: u64, launch_buy: bool) -> (u64, TaxSplit) {
+        self.tax_rates().transfer_tax(amount, launch_buy)
+    }
+
+    /// Spread `amount` of reflection rewards across every token in `supply`, less
+    /// the share set aside for the holding time multiplier
+    pub fn accrue_reflection(&mut self, amount: u64, supply: u64) {
+        let bonus = reflection_bonus_share(amount);
+        self.reflection_index = (self.reflection_index.get() + reflection_accrual(amount - bonus, supply)).into();
+        self.reflection_bonus = self.reflection_bonus.get().saturating_add(bonus).into();
+        self.total_reflection_distributed = self.total_reflection_distributed.get().saturating_add(amount).into();
+    }
 }
 
 impl Sealed for CodoxTokenState {}
 
 impl IsInitialized for CodoxTokenState {
     fn is_initialized(&self) -> bool {
-        self.is_initialized
+        self.is_initialized.get()
     }
 }
 
+// Kept for clients and tools that read a copy of the state
 impl Pack for CodoxTokenState {
-    const LEN: usize = 1 + 32 + 32 + 32 + 32 + 32 + 32 + 2 + 2 + 2 + 2 + 8 + 8 + 8 + 8;
+    const LEN: usize = std::mem::size_of::<CodoxTokenState>();
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = arrayref::array_ref![src, 0, CodoxTokenState::LEN];
-        let (
-            is_initialized,
-            authority,
-            token_mint,
-            tax_vault,
-            reflection_pool,
-            staking_pool,
-            lottery_pool,
-            tax_rate,
-            reflection_rate,
-            staking_rate,
-            lottery_rate,
-            total_staked,
-            total_reflection_distributed,
-            last_lottery_draw,
-            lottery_interval,
-        ) = arrayref::array_refs![src, 1, 32, 32, 32, 32, 32, 32, 2, 2, 2, 2, 8, 8, 8, 8];
-
-        Ok(CodoxTokenState {
-            is_initialized: is_initialized[0] != 0,
-            authority: Pubkey::new_from_array(*authority),
-            token_mint: Pubkey::new_from_array(*token_mint),
-            tax_vault: Pubkey::new_from_array(*tax_vault),
-            reflection_pool: Pubkey::new_from_array(*reflection_pool),
-            staking_pool: Pubkey::new_from_array(*staking_pool),
-            lottery_pool: Pubkey::new_from_array(*lottery_pool),
-            tax_rate: u16::from_le_bytes(*tax_rate),
-            reflection_rate: u16::from_le_bytes(*reflection_rate),
-            staking_rate: u16::from_le_bytes(*staking_rate),
-            lottery_rate: u16::from_le_bytes(*lottery_rate),
-            total_staked: u64::from_le_bytes(*total_staked),
-            total_reflection_distributed: u64::from_le_bytes(*total_reflection_distributed),
-            last_lottery_draw: i64::from_le_bytes(*last_lottery_draw),
-            lottery_interval: i64::from_le_bytes(*lottery_interval),
-        })
+        pod::load(src).copied()
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = arrayref::array_mut_ref![dst, 0, CodoxTokenState::LEN];
-        let (
-            is_initialized_dst,
-            authority_dst,
-            token_mint_dst,
-            tax_vault_dst,
-            reflection_pool_dst,
-            staking_pool_dst,
-            lottery_pool_dst,
-            tax_rate_dst,
-            reflection_rate_dst,
-            staking_rate_dst,
-            lottery_rate_dst,
-            total_staked_dst,
-            total_reflection_distributed_dst,
-            last_lottery_draw_dst,
-            lottery_interval_dst,
-        ) = arrayref::array_mut_refs![dst, 1, 32, 32, 32, 32, 32, 32, 2, 2, 2, 2, 8, 8, 8, 8];
-
-        is_initialized_dst[0] = self.is_initialized as u8;
-        authority_dst.copy_from_slice(self.authority.as_ref());
-        token_mint_dst.copy_from_slice(self.token_mint.as_ref());
-        tax_vault_dst.copy_from_slice(self.tax_vault.as_ref());
-        reflection_pool_dst.copy_from_slice(self.reflection_pool.as_ref());
-        staking_pool_dst.copy_from_slice(self.staking_pool.as_ref());
-        lottery_pool_dst.copy_from_slice(self.lottery_pool.as_ref());
-        *tax_rate_dst = self.tax_rate.to_le_bytes();
-        *reflection_rate_dst = self.reflection_rate.to_le_bytes();
-        *staking_rate_dst = self.staking_rate.to_le_bytes();
-        *lottery_rate_dst = self.lottery_rate.to_le_bytes();
-        *total_staked_dst = self.total_staked.to_le_bytes();
-        *total_reflection_distributed_dst = self.total_reflection_distributed.to_le_bytes();
-        *last_lottery_draw_dst = self.last_lottery_draw.to_le_bytes();
-        *lottery_interval_dst = self.lottery_interval.to_le_bytes();
-    }
-}
-
-/// Holder state for tracking rewards
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+        dst.copy_from_slice(bytemuck::bytes_of(self));
+    }
+}
+
+/// Holder state for tracking rewards, read in place from the account data
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Pod, Zeroable)]
 pub struct HolderState {
     pub holder: Pubkey,
-    pub last_reflection_claim: i64,
-    pub staked_amount: u64,
-    pub stake_time: i64,
-    pub total_claimed: u64,
-    pub holding_multiplier: u16, // Increases over time
+    pub last_reflection_claim: PodI64,
+    pub staked_amount: PodU64,
+    pub stake_time: PodI64,
+    pub total_claimed: PodU64,
+    pub holding_multiplier: PodU16, // Increases over time
+    pub reflection_index_checkpoint: PodU128, // State reflection index at the last settlement
+    pub last_buy_time: PodI64, // Last buy during the launch window
+    pub reflection_balance: PodU64, // Balance reflection accrues on, tracked through transfers
+    pub unpaid_reflection: PodU128, // Settled and unclaimed reflection, scaled by REFLECTION_INDEX_SCALE
+    pub lottery_round_start: PodI64, // `last_lottery_draw` of the round lottery_received counts for
+    pub lottery_received: PodU64, // Tokens received that round, which earn no tickets in it
+}
+
+// Pins the layout the same way as `CodoxTokenState`'s
+const _: () = assert!(std::mem::size_of::<HolderState>() == 130);
+const _: () = assert!(std::mem::align_of::<HolderState>() == 1);
+
+impl HolderState {
+    /// Record a launch window buy at `now`, at least `cooldown` seconds after the last one
+    pub fn record_buy(&mut self, now: i64, cooldown: i64) -> ProgramResult {
+        let last_buy_time = self.last_buy_time.get();
+        if last_buy_time != 0 && now < last_buy_time.saturating_add(cooldown) {
+            return Err(CodoxError::BuyCooldown.into());
+        }
+        self.last_buy_time = now.into();
+        Ok(())
+    }
+
+    /// Record `amount` received during the lottery round that started at `round_start`
+    pub fn record_lottery_receipt(&mut self, round_start: i64, amount: u64) {
+        if self.lottery_round_start.get() != round_start {
+            self.lottery_round_start = round_start.into();
+            self.lottery_received = 0.into();
+        }
+        self.lottery_received = self.lottery_received.get().saturating_add(amount).into();
+    }
+
+    /// Tokens received during the lottery round that started at `round_start`,
+    /// left out of the holder's tickets in it
+    pub fn lottery_received(&self, round_start: i64) -> u64 {
+        if self.lottery_round_start.get() == round_start {
+            self.lottery_received.get()
+        } else {
+            0
+        }
+    }
+
+    /// Keep a holder registering at `now` out of a lottery round already under way,
+    /// as their balance can't be told apart from tokens received during it
+    pub fn skip_lottery_round(&mut self, round_start: i64, now: i64) {
+        if now > round_start {
+            self.record_lottery_receipt(round_start, u64::MAX);
+        }
+    }
+
+    /// Settle the reflection earned on the tracked balance since the checkpoint,
+    /// counting at most `held` tokens, which lowers the tracked balance too
+    pub fn settle_reflection(&mut self, reflection_index: u128, held: u64) {
+        let balance = self.reflection_balance.get().min(held);
+        let accrued_index = reflection_index.saturating_sub(self.reflection_index_checkpoint.get());
+        let earned = (balance as u128).saturating_mul(accrued_index);
+        self.unpaid_reflection = self.unpaid_reflection.get().saturating_add(earned).into();
+        self.reflection_balance = balance.into();
+        self.reflection_index_checkpoint = reflection_index.into();
+    }
+
+    /// Reflection claimable at `now` from an account holding `balance`, with
+    /// `pool_balance` in the reflection pool: the settled reward, out of the pool
+    /// less the bonus allocation, the holding time bonus on top, out of the bonus
+    /// allocation, and the multiplier. Settles a copy of the holder state, which is
+    /// returned with the reward deducted.
+    pub fn claimable_reflection(
+        &self,
+        state: &CodoxTokenState,
+        balance: u64,
+        pool_balance: u64,
+        now: i64,
+    ) -> (u64, u64, u16, HolderState) {
+        let mut settled = *self;
+        settled.settle_reflection(state.reflection_index.get(), balance);
+        // Time-based multiplier (max 500% after 200 days)
+        let multiplier = time_multiplier(now - self.last_reflection_claim.get());
+        let bonus_allocation = state.reflection_bonus.get().min(pool_balance);
+        let owed = u64::try_from(settled.unpaid_reflection.get() / REFLECTION_INDEX_SCALE).unwrap_or(u64::MAX);
+        let reward = owed.min(pool_balance - bonus_allocation);
+        let bonus = reflection_bonus(reward, multiplier).min(bonus_allocation);
+        // What the pool can't pay yet stays owed, along with the rounding remainder
+        settled.unpaid_reflection = (settled.unpaid_reflection.get() - reward as u128 * REFLECTION_INDEX_SCALE).into();
+        (reward, bonus, multiplier, settled)
+    }
+
+    /// Reflection claimable at `now` from an account holding `balance`, bonus
+    /// included, and the multiplier; see `claimable_reflection`
+    pub fn pending_reflection(&self, state: &CodoxTokenState, balance: u64, pool_balance: u64, now: i64) -> (u64, u16) {
+        let (reward, bonus, multiplier, _) = self.claimable_reflection(state, balance, pool_balance, now);
+        (reward + bonus, multiplier)
+    }
 }
 
 impl Sealed for HolderState {}
@@ -226,52 +840,32 @@ impl IsInitialized for HolderState {
 }
 
 impl Pack for HolderState {
-    const LEN: usize = 32 + 8 + 8 + 8 + 8 + 2;
+    const LEN: usize = std::mem::size_of::<HolderState>();
 
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let src = arrayref::array_ref![src, 0, HolderState::LEN];
-        let (holder, last_reflection_claim, staked_amount, stake_time, total_claimed, holding_multiplier) =
-            arrayref::array_refs![src, 32, 8, 8, 8, 8, 2];
-
-        Ok(HolderState {
-            holder: Pubkey::new_from_array(*holder),
-            last_reflection_claim: i64::from_le_bytes(*last_reflection_claim),
-            staked_amount: u64::from_le_bytes(*staked_amount),
-            stake_time: i64::from_le_bytes(*stake_time),
-            total_claimed: u64::from_le_bytes(*total_claimed),
-            holding_multiplier: u16::from_le_bytes(*holding_multiplier),
-        })
+        pod::load(src).copied()
     }
 
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let dst = arrayref::array_mut_ref![dst, 0, HolderState::LEN];
-        let (holder_dst, last_reflection_claim_dst, staked_amount_dst, stake_time_dst, total_claimed_dst, holding_multiplier_dst) =
-            arrayref::array_mut_refs![dst, 32, 8, 8, 8, 8, 2];
-
-        holder_dst.copy_from_slice(self.holder.as_ref());
-        *last_reflection_claim_dst = self.last_reflection_claim.to_le_bytes();
-        *staked_amount_dst = self.staked_amount.to_le_bytes();
-        *stake_time_dst = self.stake_time.to_le_bytes();
-        *total_claimed_dst = self.total_claimed.to_le_bytes();
-        *holding_multiplier_dst = self.holding_multiplier.to_le_bytes();
+        dst.copy_from_slice(bytemuck::bytes_of(self));
     }
 }
 
-/// Lottery state
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct LotteryState {
-    pub participants: Vec<Pubkey>,
-    pub current_prize: u64,
-    pub last_winner: Pubkey,
-    pub total_draws: u64,
-}
-
 // Main program entry point
 pub fn process_instruction(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    // Token-2022 calls back into the program with the transfer hook interface's own encoding
+    #[cfg(feature = "token-2022")]
+    if let Ok(spl_transfer_hook_interface::instruction::TransferHookInstruction::Execute { amount }) =
+        spl_transfer_hook_interface::instruction::TransferHookInstruction::unpack(instruction_data)
+    {
+        msg!("Instruction: TransferHookExecute");
+        return transfer_hook::process_execute(program_id, accounts, amount);
+    }
+
     let instruction = CodoxTokenInstruction::try_from_slice(instruction_data)?;
 
     match instruction {
@@ -280,33 +874,384 @@ pub fn process_instruction(
             reflection_rate,
             staking_rate,
             lottery_rate,
+            liquidity_rate,
+            treasury_rate,
+            max_transfer_bps,
+            max_wallet_bps,
         } => {
             msg!("Instruction: InitializeCodoxToken");
-            process_initialize_codox_token(program_id, accounts, tax_rate, reflection_rate, staking_rate, lottery_rate)
+            process_initialize_codox_token(
+                program_id,
+                accounts,
+                tax_rate,
+                reflection_rate,
+                staking_rate,
+                lottery_rate,
+                liquidity_rate,
+                treasury_rate,
+                max_transfer_bps,
+                max_wallet_bps,
+            )
         }
         CodoxTokenInstruction::Transfer { amount } => {
             msg!("Instruction: Transfer");
+            if cfg!(feature = "token-2022") {
+                return Err(CodoxError::UseTokenProgramTransfer.into());
+            }
             process_transfer(program_id, accounts, amount)
         }
+        #[cfg(feature = "staking")]
         CodoxTokenInstruction::Stake { amount } => {
             msg!("Instruction: Stake");
             process_stake(program_id, accounts, amount)
         }
+        #[cfg(feature = "reflection")]
         CodoxTokenInstruction::ClaimReflection => {
             msg!("Instruction: ClaimReflection");
             process_claim_reflection(program_id, accounts)
         }
         CodoxTokenInstruction::ParticipateInLottery => {
             msg!("Instruction: ParticipateInLottery");
-            process_participate_in_lottery(program_id, accounts)
+            Err(CodoxError::InstructionRetired.into())
         }
+        #[cfg(feature = "lottery")]
         CodoxTokenInstruction::DrawLottery => {
             msg!("Instruction: DrawLottery");
-            process_draw_lottery(program_id, accounts)
+            lottery::process_draw_lottery(program_id, accounts)
+        }
+        CodoxTokenInstruction::DistributeTaxVault => {
+            msg!("Instruction: DistributeTaxVault");
+            process_distribute_tax_vault(program_id, accounts)
+        }
+        CodoxTokenInstruction::UpdateConfig { update } => {
+            msg!("Instruction: UpdateConfig");
+            process_update_config(program_id, accounts, *update)
+        }
+        CodoxTokenInstruction::RegisterHolder => {
+            msg!("Instruction: RegisterHolder");
+            process_register_holder(program_id, accounts)
+        }
+        CodoxTokenInstruction::SwapAndLiquify { minimum_quote_out } => {
+            msg!("Instruction: SwapAndLiquify");
+            process_swap_and_liquify(program_id, accounts, minimum_quote_out)
+        }
+        #[cfg(feature = "burn")]
+        CodoxTokenInstruction::BuybackAndBurn {
+            amount_in,
+            minimum_amount_out,
+        } => {
+            msg!("Instruction: BuybackAndBurn");
+            process_buyback_and_burn(program_id, accounts, amount_in, minimum_amount_out)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::InitializeLottery {
+            prize_tiers,
+            jackpot_chance,
+            deposit_mode,
+            claim_period,
+        } => {
+            msg!("Instruction: InitializeLottery");
+            lottery::process_initialize_lottery(program_id, accounts, prize_tiers, jackpot_chance, deposit_mode, claim_period)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::ClaimLotteryPrize { round, slot } => {
+            msg!("Instruction: ClaimLotteryPrize");
+            lottery::process_claim_lottery_prize(program_id, accounts, round, slot)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::DepositToLottery { amount } => {
+            msg!("Instruction: DepositToLottery");
+            lottery::process_deposit_to_lottery(program_id, accounts, amount)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::WithdrawFromLottery { amount } => {
+            msg!("Instruction: WithdrawFromLottery");
+            lottery::process_withdraw_from_lottery(program_id, accounts, amount)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::SnapshotDeposits => {
+            msg!("Instruction: SnapshotDeposits");
+            lottery::process_snapshot_deposits(program_id, accounts)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::ExpireLotteryPrizes { round } => {
+            msg!("Instruction: ExpireLotteryPrizes");
+            lottery::process_expire_lottery_prizes(program_id, accounts, round)
         }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::OpenTicketPage => {
+            msg!("Instruction: OpenTicketPage");
+            lottery::process_open_ticket_page(program_id, accounts)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::CloseTicketPage => {
+            msg!("Instruction: CloseTicketPage");
+            lottery::process_close_ticket_page(program_id, accounts)
+        }
+        #[cfg(feature = "reflection")]
+        CodoxTokenInstruction::PreviewClaim => {
+            msg!("Instruction: PreviewClaim");
+            preview::process_preview_claim(program_id, accounts)
+        }
+        CodoxTokenInstruction::PreviewTransferTax { amount } => {
+            msg!("Instruction: PreviewTransferTax");
+            preview::process_preview_transfer_tax(program_id, accounts, amount)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::PreviewLotteryOdds { holder } => {
+            msg!("Instruction: PreviewLotteryOdds");
+            preview::process_preview_lottery_odds(program_id, accounts, &holder)
+        }
+        #[cfg(feature = "staking")]
+        CodoxTokenInstruction::Unstake { amount } => {
+            msg!("Instruction: Unstake");
+            process_unstake(program_id, accounts, amount)
+        }
+        // Handled by the transfer hook deployment
+        #[cfg(feature = "token-2022")]
+        CodoxTokenInstruction::InitializeTransferHook => Err(ProgramError::InvalidInstructionData),
+        #[cfg(feature = "token-2022")]
+        CodoxTokenInstruction::HarvestFees => {
+            msg!("Instruction: HarvestFees");
+            transfer_hook::process_harvest_fees(program_id, accounts)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::CloseLotteryRound => {
+            msg!("Instruction: CloseLotteryRound");
+            lottery::process_close_lottery_round(program_id, accounts)
+        }
+        #[cfg(feature = "lottery")]
+        CodoxTokenInstruction::SnapshotBalances => {
+            msg!("Instruction: SnapshotBalances");
+            lottery::process_snapshot_balances(program_id, accounts)
+        }
+    }
+}
+
+/// Borrow the program state in place, checking it belongs to this program.
+/// Token-2022 passes the state account on to the transfer hook, so the borrow
+/// must be released before `token::transfer`.
+pub(crate) fn load_state<'a>(program_id: &Pubkey, state_info: &'a AccountInfo) -> Result<Ref<'a, CodoxTokenState>, ProgramError> {
+    if state_info.owner != program_id {
+        return Err(CodoxError::InvalidStateOwner.into());
+    }
+    let state = Ref::filter_map(state_info.data.borrow(), |data| pod::load::<CodoxTokenState>(data).ok())
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !state.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(state)
+}
+
+/// Mutably borrow the program state in place; see `load_state`
+pub(crate) fn load_state_mut<'a>(
+    program_id: &Pubkey,
+    state_info: &'a AccountInfo,
+) -> Result<RefMut<'a, CodoxTokenState>, ProgramError> {
+    load_state(program_id, state_info)?;
+    Ok(RefMut::map(state_info.data.borrow_mut(), |data| bytemuck::from_bytes_mut(data)))
+}
+
+/// Check `pool_authority_info` is the state's pool authority and return its bump seed
+pub(crate) fn check_pool_authority(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    pool_authority_info: &AccountInfo,
+) -> Result<u8, ProgramError> {
+    let (pool_authority, bump) = find_pool_authority(program_id, state);
+    if *pool_authority_info.key != pool_authority {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(bump)
+}
+
+/// Borrow a holder's state in place, checking it belongs to this program and to
+/// `holder`. The transfer hook gets the recipient's holder state too, so like
+/// `load_state` the borrow must be released before `token::transfer`.
+pub(crate) fn load_holder_state<'a>(
+    program_id: &Pubkey,
+    holder_state_info: &'a AccountInfo,
+    holder: &Pubkey,
+) -> Result<Ref<'a, HolderState>, ProgramError> {
+    if holder_state_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let holder_state = Ref::filter_map(holder_state_info.data.borrow(), |data| pod::load::<HolderState>(data).ok())
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    if !holder_state.is_initialized() {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    if holder_state.holder != *holder {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(holder_state)
+}
+
+/// Mutably borrow a holder's state in place; see `load_holder_state`
+pub(crate) fn load_holder_state_mut<'a>(
+    program_id: &Pubkey,
+    holder_state_info: &'a AccountInfo,
+    holder: &Pubkey,
+) -> Result<RefMut<'a, HolderState>, ProgramError> {
+    load_holder_state(program_id, holder_state_info, holder)?;
+    Ok(RefMut::map(holder_state_info.data.borrow_mut(), |data| bytemuck::from_bytes_mut(data)))
+}
+
+/// Mutably borrow `holder`'s state passed along with a change to their balance,
+/// checking it is their holder state PDA; `None` if they haven't registered
+pub(crate) fn load_holder_state_pda_mut<'a>(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    holder_state_info: &'a AccountInfo,
+    holder: &Pubkey,
+) -> Result<Option<RefMut<'a, HolderState>>, ProgramError> {
+    if *holder_state_info.key != find_holder_state(program_id, state, holder).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if holder_state_info.data_len() == 0 {
+        return Ok(None);
+    }
+    load_holder_state_mut(program_id, holder_state_info, holder).map(Some)
+}
+
+/// Settle the reflection of the holders on both sides of a transfer of `amount`,
+/// `net_amount` of which reaches the recipient, before their tracked balances
+/// change. `source_balance` is the source account's balance before the transfer.
+#[cfg(feature = "reflection")]
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn settle_transfer_reflection(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    reflection_index: u128,
+    source_holder_state_info: &AccountInfo,
+    source_owner: &Pubkey,
+    source_balance: u64,
+    recipient_holder_state_info: &AccountInfo,
+    recipient: &Pubkey,
+    amount: u64,
+    net_amount: u64,
+) -> ProgramResult {
+    // One at a time, as both are the same account when sending to yourself
+    if let Some(mut holder_state) = load_holder_state_pda_mut(program_id, state, source_holder_state_info, source_owner)? {
+        holder_state.settle_reflection(reflection_index, source_balance);
+        holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_sub(amount).into();
+    }
+    if let Some(mut holder_state) = load_holder_state_pda_mut(program_id, state, recipient_holder_state_info, recipient)? {
+        // Anyone can send to the recipient, so only the tracked balance counts
+        holder_state.settle_reflection(reflection_index, u64::MAX);
+        holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_add(net_amount).into();
+    }
+    Ok(())
+}
+
+/// Record the tokens a transfer brings the recipient during the current lottery
+/// round, which started at `round_start`, if they're registered
+#[cfg(feature = "lottery")]
+pub(crate) fn record_lottery_receipt(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    round_start: i64,
+    recipient_holder_state_info: &AccountInfo,
+    recipient: &Pubkey,
+    net_amount: u64,
+) -> ProgramResult {
+    if let Some(mut holder_state) = load_holder_state_pda_mut(program_id, state, recipient_holder_state_info, recipient)? {
+        holder_state.record_lottery_receipt(round_start, net_amount);
+    }
+    Ok(())
+}
+
+/// Create a program-owned account of `space` bytes at a PDA, paid for by `payer_info`.
+/// Anyone can send lamports to the address first, which `create_account` refuses,
+/// so a funded address is topped up to rent exemption, allocated and assigned instead.
+pub(crate) fn create_pda_account<'a>(
+    payer_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    system_program_info: &AccountInfo<'a>,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent_exempt = Rent::get()?.minimum_balance(space);
+    if account_info.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(payer_info.key, account_info.key, rent_exempt, space as u64, owner),
+            &[payer_info.clone(), account_info.clone(), system_program_info.clone()],
+            &[signer_seeds],
+        );
+    }
+    let top_up = rent_exempt.saturating_sub(account_info.lamports());
+    if top_up > 0 {
+        invoke(
+            &system_instruction::transfer(payer_info.key, account_info.key, top_up),
+            &[payer_info.clone(), account_info.clone(), system_program_info.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account_info.key, space as u64),
+        &[account_info.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account_info.key, owner),
+        &[account_info.clone(), system_program_info.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Create a holder's state account at its PDA, paid for by the holder
+fn create_holder_state<'a>(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    holder_info: &AccountInfo<'a>,
+    holder_state_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+) -> ProgramResult {
+    let (holder_state_address, bump) = find_holder_state(program_id, state, holder_info.key);
+    if *holder_state_info.key != holder_state_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        holder_info,
+        holder_state_info,
+        HolderState::LEN,
+        program_id,
+        system_program_info,
+        &[HOLDER_SEED, state.as_ref(), holder_info.key.as_ref(), &[bump]],
+    )
+}
+
+/// Reflection multiplier in percent: 100% plus 2% per day held, capped at 500%
+pub fn time_multiplier(holding_time: i64) -> u16 {
+    let days = (holding_time.max(0) / 86400).min(200) as u16;
+    100 + days * 2
+}
+
+/// Reflection index increase from spreading `amount` across every token in `supply`
+pub fn reflection_accrual(amount: u64, supply: u64) -> u128 {
+    if supply == 0 {
+        return 0;
     }
+    amount as u128 * REFLECTION_INDEX_SCALE / supply as u128
+}
+
+/// Reflection owed on `balance` for an index increase of `accrued_index`, boosted by `multiplier` percent
+pub fn reflection_reward(balance: u64, accrued_index: u128, multiplier: u16) -> u64 {
+    let base = (balance as u128).saturating_mul(accrued_index) / REFLECTION_INDEX_SCALE;
+    u64::try_from(base.saturating_mul(multiplier as u128) / 100).unwrap_or(u64::MAX)
+}
+
+/// Part of `amount` of reflection rewards set aside for the holding time multiplier
+pub fn reflection_bonus_share(amount: u64) -> u64 {
+    (amount as u128 * REFLECTION_BONUS_BPS as u128 / 10000) as u64
+}
+
+/// Holding time bonus on `reward` for a `multiplier` percent boost
+pub fn reflection_bonus(reward: u64, multiplier: u16) -> u64 {
+    u64::try_from(reward as u128 * multiplier.saturating_sub(100) as u128 / 100).unwrap_or(u64::MAX)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn process_initialize_codox_token(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -314,6 +1259,10 @@ fn process_initialize_codox_token(
     reflection_rate: u16,
     staking_rate: u16,
     lottery_rate: u16,
+    liquidity_rate: u16,
+    treasury_rate: u16,
+    max_transfer_bps: u16,
+    max_wallet_bps: u16,
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
     let authority_info = next_account_info(account_info_iter)?;
@@ -324,35 +1273,108 @@ fn process_initialize_codox_token(
     let lottery_pool_info = next_account_info(account_info_iter)?;
     let state_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
-    let system_program_info = next_account_info(account_info_iter)?;
-    let rent_info = next_account_info(account_info_iter)?;
+    let _system_program_info = next_account_info(account_info_iter)?;
+    let _rent_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let treasury_vault_info = next_account_info(account_info_iter)?;
 
-    // Validate tax rates
-    if tax_rate > 1000 || reflection_rate + staking_rate + lottery_rate != tax_rate {
-        return Err(ProgramError::InvalidArgument);
+    // The state account signs so nobody can initialize an account someone else
+    // created before its creator does
+    if !authority_info.is_signer || !state_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if state_info.owner != program_id {
+        return Err(CodoxError::InvalidStateOwner.into());
+    }
+    if pod::load::<CodoxTokenState>(&state_info.data.borrow())?.is_initialized() {
+        return Err(CodoxError::AlreadyInitialized.into());
     }
 
-    let mut state = CodoxTokenState {
-        is_initialized: true,
-        authority: *authority_info.key,
-        token_mint: *mint_info.key,
-        tax_vault: *tax_vault_info.key,
-        reflection_pool: *reflection_pool_info.key,
-        staking_pool: *staking_pool_info.key,
-        lottery_pool: *lottery_pool_info.key,
+    // Validate tax rates, which only share the tax between the mechanisms in this
+    // build; launch window buys pay the normal tax until a sniper tax is set
+    let rates = TaxRates {
         tax_rate,
         reflection_rate,
         staking_rate,
         lottery_rate,
-        total_staked: 0,
-        total_reflection_distributed: 0,
-        last_lottery_draw: 0,
-        lottery_interval: 86400, // 24 hours
+        liquidity_rate,
+        treasury_rate,
+        sniper_tax_rate: tax_rate,
     };
+    if !rates.is_valid() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if max_transfer_bps > 10000 || max_wallet_bps > 10000 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    // Without anti-whale nothing would enforce the limits
+    if !cfg!(feature = "anti-whale") && (max_transfer_bps != 0 || max_wallet_bps != 0) {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    if *token_program_info.key != token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let (pool_authority, _) = find_pool_authority(program_id, state_info.key);
+    for pool_info in [
+        tax_vault_info,
+        reflection_pool_info,
+        staking_pool_info,
+        lottery_pool_info,
+        liquidity_vault_info,
+        treasury_vault_info,
+    ] {
+        token::check_pool(pool_info, mint_info.key, &pool_authority)?;
+    }
 
-    CodoxTokenState::pack(state, &mut state_info.data.borrow_mut())?;
+    // In Token-2022 mode the mint's transfer fee is the tax, so it must agree with the split
+    #[cfg(feature = "token-2022")]
+    transfer_hook::check_mint(program_id, mint_info, tax_rate, &pool_authority)?;
 
-    msg!("Codox token initialized with {}% tax rate", tax_rate as f64 / 100.0);
+    *pod::load_mut(&mut state_info.data.borrow_mut())? = CodoxTokenState {
+        is_initialized: true.into(),
+        authority: *authority_info.key,
+        token_mint: *mint_info.key,
+        tax_vault: *tax_vault_info.key,
+        reflection_pool: *reflection_pool_info.key,
+        staking_pool: *staking_pool_info.key,
+        lottery_pool: *lottery_pool_info.key,
+        tax_rate: tax_rate.into(),
+        reflection_rate: reflection_rate.into(),
+        staking_rate: staking_rate.into(),
+        lottery_rate: lottery_rate.into(),
+        total_staked: 0.into(),
+        total_reflection_distributed: 0.into(),
+        last_lottery_draw: 0.into(),
+        lottery_interval: 86400.into(), // 24 hours
+        total_tax_collected: 0.into(),
+        reflection_index: 0.into(),
+        max_transfer_bps: max_transfer_bps.into(),
+        max_wallet_bps: max_wallet_bps.into(),
+        limit_exempt: [Pubkey::default(); MAX_LIMIT_EXEMPT],
+        trading_enabled_at: i64::MAX.into(), // Opened with UpdateConfig
+        launch_window: 0.into(),
+        buy_cooldown: 0.into(),
+        sniper_tax_rate: tax_rate.into(),
+        liquidity_rate: liquidity_rate.into(),
+        liquidity_vault: *liquidity_vault_info.key,
+        amm_program: Pubkey::default(), // Set with UpdateConfig
+        amm_pool: Pubkey::default(),
+        liquify_threshold: 0.into(),
+        total_liquified: 0.into(),
+        treasury_rate: treasury_rate.into(),
+        treasury_vault: *treasury_vault_info.key,
+        treasury_quote_vault: Pubkey::default(), // Set with UpdateConfig
+        keeper: Pubkey::default(),
+        buyback_max_per_call: 0.into(),
+        total_burned: 0.into(),
+        paused: false.into(),
+        undistributed: PodTaxSplit::default(),
+        reflection_bonus: 0.into(),
+        liquify_max_slippage_bps: DEFAULT_LIQUIFY_MAX_SLIPPAGE_BPS.into(),
+    };
+
+    msg!("Codox token initialized with a {} bps tax rate", tax_rate);
     Ok(())
 }
 
@@ -366,22 +1388,56 @@ fn process_transfer(
     let source_info = next_account_info(account_info_iter)?;
     let destination_info = next_account_info(account_info_iter)?;
     let tax_vault_info = next_account_info(account_info_iter)?;
-    let reflection_pool_info = next_account_info(account_info_iter)?;
-    let staking_pool_info = next_account_info(account_info_iter)?;
-    let lottery_pool_info = next_account_info(account_info_iter)?;
     let state_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    #[cfg_attr(not(feature = "reflection"), allow(unused_variables))]
+    let source_holder_state_info = next_account_info(account_info_iter)?;
+    let recipient_holder_state_info = next_account_info(account_info_iter)?;
 
-    let state = CodoxTokenState::unpack(&state_info.data.borrow())?;
+    // The state account isn't passed to the token transfers below, so it stays borrowed
+    let mut state = load_state_mut(program_id, state_info)?;
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    if *tax_vault_info.key != state.tax_vault {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    state.check_not_paused(source_info.key)?;
     
+    let source = token::unpack_account(source_info)?;
+    let destination = token::unpack_account(destination_info)?;
+    // Holder bookkeeping below only tracks CODOX
+    if source.mint != state.token_mint || destination.mint != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let source_exempt = state.is_limit_exempt(source_info.key, &source.owner);
+    let destination_exempt = state.is_limit_exempt(destination_info.key, &destination.owner);
+    let now = Clock::get()?.unix_timestamp;
+    let launch_buy = state.check_trading_open(now, source_exempt, destination_exempt)?;
+
+    // Launch window buys are rate limited per wallet and pay the sniper tax
+    if launch_buy {
+        load_holder_state_pda_mut(program_id, state_info.key, recipient_holder_state_info, &destination.owner)?
+            .ok_or(ProgramError::UninitializedAccount)?
+            .record_buy(now, state.buy_cooldown.get())?;
+    }
+
     // Calculate tax amounts
-    let tax_amount = (amount * state.tax_rate as u64) / 10000;
-    let reflection_tax = (tax_amount * state.reflection_rate as u64) / state.tax_rate as u64;
-    let staking_tax = (tax_amount * state.staking_rate as u64) / state.tax_rate as u64;
-    let lottery_tax = tax_amount - reflection_tax - staking_tax;
-    
+    let (tax_amount, split) = state.transfer_tax(amount, launch_buy);
+
     let net_amount = amount - tax_amount;
 
+    // Launch limits, checked against the recipient's balance after the transfer
+    #[cfg(feature = "anti-whale")]
+    state.check_launch_limits(
+        token::unpack_mint(mint_info)?.supply,
+        amount,
+        source_exempt,
+        destination_exempt,
+        destination.amount.saturating_add(net_amount),
+    )?;
+
     // Transfer net amount to destination
     let transfer_instruction = spl_token::instruction::transfer(
         token_program_info.key,
@@ -402,74 +1458,72 @@ fn process_transfer(
         ],
     )?;
 
-    // Distribute taxes
-    if reflection_tax > 0 {
-        let reflection_transfer = spl_token::instruction::transfer(
+    // The whole tax in one transfer; DistributeTaxVault moves each pool's share later
+    if tax_amount > 0 {
+        let tax_transfer = spl_token::instruction::transfer(
             token_program_info.key,
             source_info.key,
-            reflection_pool_info.key,
+            tax_vault_info.key,
             source_owner_info.key,
             &[],
-            reflection_tax,
+            tax_amount,
         )?;
 
         invoke(
-            &reflection_transfer,
+            &tax_transfer,
             &[
                 source_info.clone(),
-                reflection_pool_info.clone(),
+                tax_vault_info.clone(),
                 source_owner_info.clone(),
                 token_program_info.clone(),
             ],
         )?;
     }
 
-    if staking_tax > 0 {
-        let staking_transfer = spl_token::instruction::transfer(
-            token_program_info.key,
-            source_info.key,
-            staking_pool_info.key,
-            source_owner_info.key,
-            &[],
-            staking_tax,
-        )?;
+    state.total_tax_collected = state.total_tax_collected.get().saturating_add(tax_amount).into();
+    let mut undistributed = state.undistributed.get();
+    undistributed.add(&split);
+    state.undistributed = undistributed.into();
 
-        invoke(
-            &staking_transfer,
-            &[
-                source_info.clone(),
-                staking_pool_info.clone(),
-                source_owner_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
-    }
+    #[cfg(feature = "reflection")]
+    settle_transfer_reflection(
+        program_id,
+        state_info.key,
+        state.reflection_index.get(),
+        source_holder_state_info,
+        &source.owner,
+        source.amount,
+        recipient_holder_state_info,
+        &destination.owner,
+        amount,
+        net_amount,
+    )?;
 
-    if lottery_tax > 0 {
-        let lottery_transfer = spl_token::instruction::transfer(
-            token_program_info.key,
-            source_info.key,
-            lottery_pool_info.key,
-            source_owner_info.key,
-            &[],
-            lottery_tax,
-        )?;
+    #[cfg(feature = "lottery")]
+    record_lottery_receipt(
+        program_id,
+        state_info.key,
+        state.last_lottery_draw.get(),
+        recipient_holder_state_info,
+        &destination.owner,
+        net_amount,
+    )?;
 
-        invoke(
-            &lottery_transfer,
-            &[
-                source_info.clone(),
-                lottery_pool_info.clone(),
-                source_owner_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+    // The event carries the amounts; a formatted message on top would only add compute
+    CodoxEvent::Transfer {
+        source: *source_info.key,
+        destination: *destination_info.key,
+        amount,
+        net_amount,
+        tax_amount,
+        split,
+        launch_buy,
     }
-
-    msg!("Transfer completed: {} tokens sent, {} tokens taxed", net_amount, tax_amount);
+    .emit();
     Ok(())
 }
 
+#[cfg(feature = "staking")]
 fn process_stake(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -481,49 +1535,148 @@ fn process_stake(
     let staking_pool_info = next_account_info(account_info_iter)?;
     let staker_state_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let _rent_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    let state = load_state(program_id, state_info)?;
+    if *staking_pool_info.key != state.staking_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    state.check_not_paused(staker_token_info.key)?;
+    let reflection_index = state.reflection_index;
+    let last_lottery_draw = state.last_lottery_draw.get();
+    drop(state);
+    let balance = token::unpack_account(staker_token_info)?.amount;
 
     // Transfer tokens to staking pool
-    let transfer_instruction = spl_token::instruction::transfer(
-        token_program_info.key,
-        staker_token_info.key,
-        staking_pool_info.key,
-        staker_info.key,
-        &[],
+    token::transfer(
+        token_program_info,
+        staker_token_info,
+        mint_info,
+        staking_pool_info,
+        staker_info,
+        hook_accounts,
         amount,
-    )?;
-
-    invoke(
-        &transfer_instruction,
-        &[
-            staker_token_info.clone(),
-            staking_pool_info.clone(),
-            staker_info.clone(),
-            token_program_info.clone(),
-        ],
+        &[],
     )?;
 
     // Update staker state
-    let mut holder_state = if staker_state_info.data_len() == 0 {
-        HolderState {
+    let staked_amount = if staker_state_info.data_len() == 0 {
+        create_holder_state(program_id, state_info.key, staker_info, staker_state_info, system_program_info)?;
+        let current_time = Clock::get()?.unix_timestamp;
+        let mut holder_state = HolderState {
             holder: *staker_info.key,
-            last_reflection_claim: 0,
-            staked_amount: amount,
-            stake_time: solana_program::clock::Clock::get()?.unix_timestamp,
-            total_claimed: 0,
-            holding_multiplier: 100, // Base multiplier
-        }
+            last_reflection_claim: current_time.into(),
+            staked_amount: amount.into(),
+            stake_time: current_time.into(),
+            total_claimed: 0.into(),
+            holding_multiplier: 100.into(), // Base multiplier
+            reflection_index_checkpoint: reflection_index,
+            last_buy_time: 0.into(),
+            reflection_balance: balance.saturating_sub(amount).into(),
+            unpaid_reflection: 0.into(),
+            lottery_round_start: 0.into(),
+            lottery_received: 0.into(),
+        };
+        holder_state.skip_lottery_round(last_lottery_draw, current_time);
+        *pod::load_mut(&mut staker_state_info.data.borrow_mut())? = holder_state;
+        amount
     } else {
-        let mut state = HolderState::unpack(&staker_state_info.data.borrow())?;
-        state.staked_amount += amount;
-        state
+        let mut holder_state = load_holder_state_mut(program_id, staker_state_info, staker_info.key)?;
+        // Staked tokens leave the balance reflection accrues on
+        holder_state.settle_reflection(reflection_index.get(), balance);
+        holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_sub(amount).into();
+        holder_state.staked_amount = (holder_state.staked_amount.get() + amount).into();
+        holder_state.staked_amount.get()
     };
 
-    HolderState::pack(holder_state, &mut staker_state_info.data.borrow_mut())?;
+    let mut state = load_state_mut(program_id, state_info)?;
+    state.total_staked = state.total_staked.get().saturating_add(amount).into();
+    let total_staked = state.total_staked.get();
 
+    CodoxEvent::Stake {
+        staker: *staker_info.key,
+        amount,
+        staked_amount,
+        total_staked,
+    }
+    .emit();
     msg!("Staked {} tokens", amount);
     Ok(())
 }
 
+#[cfg(feature = "staking")]
+fn process_unstake(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let staker_info = next_account_info(account_info_iter)?;
+    let staker_token_info = next_account_info(account_info_iter)?;
+    let staking_pool_info = next_account_info(account_info_iter)?;
+    let staker_state_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    if !staker_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    if *staking_pool_info.key != state.staking_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let reflection_index = state.reflection_index.get();
+    drop(state);
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+    if amount == 0 || amount > load_holder_state(program_id, staker_state_info, staker_info.key)?.staked_amount.get() {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    token::transfer(
+        token_program_info,
+        staking_pool_info,
+        mint_info,
+        staker_token_info,
+        pool_authority_info,
+        hook_accounts,
+        amount,
+        &[&[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]]],
+    )?;
+
+    let mut holder_state = load_holder_state_mut(program_id, staker_state_info, staker_info.key)?;
+    holder_state.settle_reflection(reflection_index, u64::MAX);
+    holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_add(amount).into();
+    holder_state.staked_amount = (holder_state.staked_amount.get() - amount).into();
+    let staked_amount = holder_state.staked_amount.get();
+    let mut state = load_state_mut(program_id, state_info)?;
+    state.total_staked = state.total_staked.get().saturating_sub(amount).into();
+    let total_staked = state.total_staked.get();
+
+    CodoxEvent::Unstake {
+        staker: *staker_info.key,
+        amount,
+        staked_amount,
+        total_staked,
+    }
+    .emit();
+    msg!("Unstaked {} tokens", amount);
+    Ok(())
+}
+
+#[cfg(feature = "reflection")]
 fn process_claim_reflection(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
@@ -534,66 +1687,603 @@ fn process_claim_reflection(
     let reflection_pool_info = next_account_info(account_info_iter)?;
     let holder_state_info = next_account_info(account_info_iter)?;
     let token_program_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
 
-    let mut holder_state = HolderState::unpack(&holder_state_info.data.borrow())?;
-    
-    // Calculate reflection rewards based on holding time and amount
-    let current_time = solana_program::clock::Clock::get()?.unix_timestamp;
-    let holding_time = current_time - holder_state.last_reflection_claim;
-    
-    // Time-based multiplier (max 500% after 1 year)
-    let time_multiplier = std::cmp::min(500, 100 + (holding_time / 86400) as u16 * 2);
-    
-    let holder_balance = Account::unpack(&holder_token_info.data.borrow())?.amount;
-    let reflection_pool_balance = Account::unpack(&reflection_pool_info.data.borrow())?.amount;
+    if !holder_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    if *reflection_pool_info.key != state.reflection_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+    let holder_state = load_holder_state(program_id, holder_state_info, holder_info.key)?;
     
-    // Calculate reward based on balance percentage and multipliers
-    let reward = (reflection_pool_balance * holder_balance * time_multiplier as u64) / 10000000;
+    let holder_token = token::unpack_account(holder_token_info)?;
+    if holder_token.owner != *holder_info.key || holder_token.mint != state.token_mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let reflection_pool_balance = token::unpack_account(reflection_pool_info)?.amount;
+
+    // Settle what the tracked balance earned, paying what the pool holds and keeping the rest owed
+    let current_time = Clock::get()?.unix_timestamp;
+    let (reward, bonus, time_multiplier, mut settled) =
+        holder_state.claimable_reflection(&state, holder_token.amount, reflection_pool_balance, current_time);
+    drop(holder_state);
+    drop(state);
     
-    if reward > 0 {
+    let payout = reward + bonus;
+    if payout > 0 {
         // Transfer reflection rewards
-        let transfer_instruction = spl_token::instruction::transfer(
-            token_program_info.key,
-            reflection_pool_info.key,
-            holder_token_info.key,
-            &solana_program::system_program::ID, // Pool authority
-            &[],
-            reward,
+        token::transfer(
+            token_program_info,
+            reflection_pool_info,
+            mint_info,
+            holder_token_info,
+            pool_authority_info,
+            hook_accounts,
+            payout,
+            &[&[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]]],
         )?;
 
-        invoke(
-            &transfer_instruction,
-            &[
-                reflection_pool_info.clone(),
-                holder_token_info.clone(),
-                token_program_info.clone(),
-            ],
-        )?;
+        settled.last_reflection_claim = current_time.into();
+        settled.total_claimed = (settled.total_claimed.get() + payout).into();
+        settled.holding_multiplier = time_multiplier.into();
+        let received = payout - token::transfer_fee(mint_info, payout)?;
+        settled.reflection_balance = settled.reflection_balance.get().saturating_add(received).into();
+        let mut state = load_state_mut(program_id, state_info)?;
+        state.reflection_bonus = (state.reflection_bonus.get() - bonus).into();
 
-        holder_state.last_reflection_claim = current_time;
-        holder_state.total_claimed += reward;
-        holder_state.holding_multiplier = time_multiplier;
-        
-        HolderState::pack(holder_state, &mut holder_state_info.data.borrow_mut())?;
-        
-        msg!("Claimed {} reflection tokens with {}% multiplier", reward, time_multiplier);
+        CodoxEvent::Claim {
+            holder: *holder_info.key,
+            reward: payout,
+            multiplier: time_multiplier,
+            total_claimed: settled.total_claimed.get(),
+        }
+        .emit();
+        msg!("Claimed {} reflection tokens with {}% multiplier", payout, time_multiplier);
     }
 
+    *load_holder_state_mut(program_id, holder_state_info, holder_info.key)? = settled;
+
     Ok(())
 }
 
-fn process_participate_in_lottery(
+fn process_distribute_tax_vault(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    msg!("Lottery participation registered");
+    let account_info_iter = &mut accounts.iter();
+    let tax_vault_info = next_account_info(account_info_iter)?;
+    let reflection_pool_info = next_account_info(account_info_iter)?;
+    let staking_pool_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let treasury_vault_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    let state = load_state(program_id, state_info)?;
+    if *tax_vault_info.key != state.tax_vault
+        || *reflection_pool_info.key != state.reflection_pool
+        || *staking_pool_info.key != state.staking_pool
+        || *lottery_pool_info.key != state.lottery_pool
+        || *liquidity_vault_info.key != state.liquidity_vault
+        || *treasury_vault_info.key != state.treasury_vault
+    {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+
+    // Each pool's share of the transfer tax, and anything else that reached the vault split by the rates
+    let vault_balance = token::unpack_account(tax_vault_info)?.amount;
+    let undistributed = state.undistributed.get();
+    let untracked = vault_balance
+        .checked_sub(undistributed.total())
+        .ok_or(ProgramError::InsufficientFunds)?;
+    let mut split = state.split_tax(untracked);
+    split.add(&undistributed);
+    drop(state);
+
+    let signer_seeds: &[&[u8]] = &[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]];
+    for (pool_info, share) in [
+        (reflection_pool_info, split.reflection),
+        (staking_pool_info, split.staking),
+        (lottery_pool_info, split.lottery),
+        (liquidity_vault_info, split.liquidity),
+        (treasury_vault_info, split.treasury),
+    ] {
+        if share > 0 {
+            token::transfer(
+                token_program_info,
+                tax_vault_info,
+                mint_info,
+                pool_info,
+                pool_authority_info,
+                hook_accounts,
+                share,
+                &[signer_seeds],
+            )?;
+        }
+    }
+
+    // Only what lands in the reflection pool can be claimed
+    let reflection_received = split.reflection - token::transfer_fee(mint_info, split.reflection)?;
+    let mut state = load_state_mut(program_id, state_info)?;
+    state.accrue_reflection(reflection_received, token::unpack_mint(mint_info)?.supply);
+    state.undistributed = PodTaxSplit::default();
+
+    msg!(
+        "Distributed {} tax tokens: {} reflection, {} staking, {} lottery, {} liquidity, {} treasury",
+        vault_balance,
+        split.reflection,
+        split.staking,
+        split.lottery,
+        split.liquidity,
+        split.treasury
+    );
     Ok(())
 }
 
-fn process_draw_lottery(
+/// Raise or remove a launch limit; 0 means no limit, and a removed limit stays removed
+fn loosen_limit(current: u16, new: u16) -> Result<u16, ProgramError> {
+    if new > 10000 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    if new != 0 && (current == 0 || new < current) {
+        return Err(CodoxError::LimitTightened.into());
+    }
+    Ok(new)
+}
+
+fn process_update_config(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
+    update: ConfigUpdate,
 ) -> ProgramResult {
-    msg!("Lottery drawn");
+    let ConfigUpdate {
+        max_transfer_bps,
+        max_wallet_bps,
+        limit_exempt,
+        trading_enabled_at,
+        launch_window,
+        buy_cooldown,
+        sniper_tax_rate,
+        amm_program,
+        amm_pool,
+        liquify_threshold,
+        liquify_max_slippage_bps,
+        treasury_quote_vault,
+        keeper,
+        buyback_max_per_call,
+        paused,
+    } = update.clone();
+
+    let account_info_iter = &mut accounts.iter();
+    let authority_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+
+    let mut state = load_state_mut(program_id, state_info)?;
+    if !authority_info.is_signer || *authority_info.key != state.authority {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    if let Some(max_transfer_bps) = max_transfer_bps {
+        state.max_transfer_bps = loosen_limit(state.max_transfer_bps.get(), max_transfer_bps)?.into();
+    }
+    if let Some(max_wallet_bps) = max_wallet_bps {
+        state.max_wallet_bps = loosen_limit(state.max_wallet_bps.get(), max_wallet_bps)?.into();
+    }
+    if let Some(limit_exempt) = limit_exempt {
+        state.limit_exempt = limit_exempt;
+    }
+
+    // Launch settings
+    if trading_enabled_at.is_some() || launch_window.is_some() || buy_cooldown.is_some() || sniper_tax_rate.is_some() {
+        if Clock::get()?.unix_timestamp >= state.trading_enabled_at.get() {
+            return Err(CodoxError::TradingAlreadyEnabled.into());
+        }
+        if launch_window.unwrap_or(0) < 0 || buy_cooldown.unwrap_or(0) < 0 || sniper_tax_rate.unwrap_or(0) > 10000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        state.trading_enabled_at = trading_enabled_at.map_or(state.trading_enabled_at, PodI64::from);
+        state.launch_window = launch_window.map_or(state.launch_window, PodI64::from);
+        state.buy_cooldown = buy_cooldown.map_or(state.buy_cooldown, PodI64::from);
+        state.sniper_tax_rate = sniper_tax_rate.map_or(state.sniper_tax_rate, PodU16::from);
+    }
+
+    // Swap-and-liquify
+    state.amm_program = amm_program.unwrap_or(state.amm_program);
+    state.amm_pool = amm_pool.unwrap_or(state.amm_pool);
+    state.liquify_threshold = liquify_threshold.map_or(state.liquify_threshold, PodU64::from);
+    if let Some(liquify_max_slippage_bps) = liquify_max_slippage_bps {
+        if liquify_max_slippage_bps > 10000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        state.liquify_max_slippage_bps = liquify_max_slippage_bps.into();
+    }
+
+    // Buyback-and-burn
+    state.treasury_quote_vault = treasury_quote_vault.unwrap_or(state.treasury_quote_vault);
+    state.keeper = keeper.unwrap_or(state.keeper);
+    state.buyback_max_per_call = buyback_max_per_call.map_or(state.buyback_max_per_call, PodU64::from);
+
+    state.paused = paused.map_or(state.paused, PodBool::from);
+
+    msg!(
+        "Config updated: max transfer {} bps, max wallet {} bps",
+        state.max_transfer_bps.get(),
+        state.max_wallet_bps.get()
+    );
+
+    CodoxEvent::ConfigUpdated {
+        authority: *authority_info.key,
+        update,
+    }
+    .emit();
+    Ok(())
+}
+
+fn process_register_holder(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let holder_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let holder_token_info = next_account_info(account_info_iter)?;
+
+    if !holder_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    let holder_token = token::unpack_account(holder_token_info)?;
+    if holder_token.owner != *holder_info.key || holder_token.mint != state.token_mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let reflection_index = state.reflection_index;
+    let last_lottery_draw = state.last_lottery_draw.get();
+    drop(state);
+    if holder_state_info.data_len() != 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    create_holder_state(program_id, state_info.key, holder_info, holder_state_info, system_program_info)?;
+
+    let current_time = Clock::get()?.unix_timestamp;
+    let mut holder_state = HolderState {
+        holder: *holder_info.key,
+        last_reflection_claim: current_time.into(),
+        staked_amount: 0.into(),
+        stake_time: current_time.into(),
+        total_claimed: 0.into(),
+        holding_multiplier: 100.into(), // Base multiplier
+        reflection_index_checkpoint: reflection_index,
+        last_buy_time: 0.into(),
+        reflection_balance: holder_token.amount.into(),
+        unpaid_reflection: 0.into(),
+        lottery_round_start: 0.into(),
+        lottery_received: 0.into(),
+    };
+    holder_state.skip_lottery_round(last_lottery_draw, current_time);
+    *pod::load_mut(&mut holder_state_info.data.borrow_mut())? = holder_state;
+
+    msg!("Registered holder {}", holder_info.key);
     Ok(())
-} 
\ No newline at end of file
+}
+
+/// Check the AMM accounts are the configured AMM pool
+fn check_amm(state: &CodoxTokenState, amm_program_info: &AccountInfo, amm_pool_info: &AccountInfo) -> ProgramResult {
+    if state.amm_program == Pubkey::default()
+        || *amm_program_info.key != state.amm_program
+        || *amm_pool_info.key != state.amm_pool
+    {
+        return Err(CodoxError::InvalidAmm.into());
+    }
+    Ok(())
+}
+
+/// Find the CODOX side of an AMM pool's reserves: whether it is token A, and the quote mint
+fn orient_amm_reserves(
+    token_mint: &Pubkey,
+    reserve_a_info: &AccountInfo,
+    reserve_b_info: &AccountInfo,
+) -> Result<(bool, Pubkey), ProgramError> {
+    let reserve_a = token::unpack_account(reserve_a_info)?;
+    let reserve_b = token::unpack_account(reserve_b_info)?;
+    if reserve_a.mint == *token_mint {
+        Ok((true, reserve_b.mint))
+    } else if reserve_b.mint == *token_mint {
+        Ok((false, reserve_a.mint))
+    } else {
+        Err(CodoxError::InvalidAmm.into())
+    }
+}
+
+/// Check the caller of a keeper instruction is the authority or the configured keeper
+pub(crate) fn check_authority_or_keeper(state: &CodoxTokenState, caller_info: &AccountInfo) -> ProgramResult {
+    let is_keeper = state.keeper != Pubkey::default() && *caller_info.key == state.keeper;
+    if !caller_info.is_signer || (*caller_info.key != state.authority && !is_keeper) {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(())
+}
+
+fn process_swap_and_liquify(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    minimum_quote_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let liquidity_vault_info = next_account_info(account_info_iter)?;
+    let quote_vault_info = next_account_info(account_info_iter)?;
+    let lp_vault_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let amm_program_info = next_account_info(account_info_iter)?;
+    let amm_pool_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let reserve_a_info = next_account_info(account_info_iter)?;
+    let reserve_b_info = next_account_info(account_info_iter)?;
+    let pool_mint_info = next_account_info(account_info_iter)?;
+    let fee_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // The AMM isn't passed the state account, so it stays borrowed
+    let mut state = load_state_mut(program_id, state_info)?;
+    check_authority_or_keeper(&state, caller_info)?;
+    if *liquidity_vault_info.key != state.liquidity_vault {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    check_amm(&state, amm_program_info, amm_pool_info)?;
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+
+    // The swapped half and the LP tokens stay with the pool authority
+    let quote_vault = token::unpack_account(quote_vault_info)?;
+    let lp_vault = token::unpack_account(lp_vault_info)?;
+    if quote_vault.owner != *pool_authority_info.key
+        || lp_vault.owner != *pool_authority_info.key
+        || lp_vault.mint != *pool_mint_info.key
+    {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+
+    let (codox_is_a, quote_mint) = orient_amm_reserves(&state.token_mint, reserve_a_info, reserve_b_info)?;
+    let (codox_reserve_info, quote_reserve_info) = if codox_is_a {
+        (reserve_a_info, reserve_b_info)
+    } else {
+        (reserve_b_info, reserve_a_info)
+    };
+    if quote_vault.mint != quote_mint {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+
+    let vault_balance = token::unpack_account(liquidity_vault_info)?.amount;
+    if vault_balance == 0 || vault_balance < state.liquify_threshold.get() {
+        return Err(CodoxError::LiquifyThresholdNotReached.into());
+    }
+    let swap_amount = vault_balance / 2;
+    let liquidity_amount = vault_balance - swap_amount;
+
+    // The caller's bound can't be looser than the configured slippage from the pool's quote
+    let quote_before = quote_vault.amount;
+    let quote = amm::swap_amount_out(
+        swap_amount,
+        token::unpack_account(codox_reserve_info)?.amount,
+        token::unpack_account(quote_reserve_info)?.amount,
+    );
+    let slippage_bps = state.liquify_max_slippage_bps.get().min(10000) as u128;
+    let floor = (quote as u128 * (10000 - slippage_bps) / 10000) as u64;
+    if minimum_quote_out == 0 || minimum_quote_out < floor {
+        return Err(CodoxError::SlippageExceeded.into());
+    }
+
+    let amm_pool = amm::AmmPool {
+        program_id: amm_program_info.key,
+        pool: amm_pool_info.key,
+        authority: amm_authority_info.key,
+        reserve_a: reserve_a_info.key,
+        reserve_b: reserve_b_info.key,
+        pool_mint: pool_mint_info.key,
+        fee_account: fee_account_info.key,
+        token_program: token_program_info.key,
+    };
+    let signer_seeds: &[&[u8]] = &[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]];
+    let amm_accounts = [
+        amm_pool_info.clone(),
+        amm_authority_info.clone(),
+        pool_authority_info.clone(),
+        liquidity_vault_info.clone(),
+        quote_vault_info.clone(),
+        lp_vault_info.clone(),
+        reserve_a_info.clone(),
+        reserve_b_info.clone(),
+        pool_mint_info.clone(),
+        fee_account_info.clone(),
+        token_program_info.clone(),
+        amm_program_info.clone(),
+    ];
+
+    invoke_signed(
+        &amm::swap(
+            &amm_pool,
+            pool_authority_info.key,
+            liquidity_vault_info.key,
+            codox_reserve_info.key,
+            quote_reserve_info.key,
+            quote_vault_info.key,
+            swap_amount,
+            minimum_quote_out,
+        ),
+        &amm_accounts,
+        &[signer_seeds],
+    )?;
+
+    // Don't rely on the AMM to honour the bound
+    let quote_amount = token::unpack_account(quote_vault_info)?.amount;
+    if quote_amount.saturating_sub(quote_before) < minimum_quote_out {
+        return Err(CodoxError::SlippageExceeded.into());
+    }
+
+    // Deposit at the post-swap price; quote left over from earlier runs is used too
+    let codox_reserve = token::unpack_account(codox_reserve_info)?.amount;
+    let quote_reserve = token::unpack_account(quote_reserve_info)?.amount;
+    let pool_supply = token::unpack_mint(pool_mint_info)?.supply;
+    let pool_tokens = amm::pool_tokens_for_deposit(liquidity_amount, quote_amount, codox_reserve, quote_reserve, pool_supply);
+    if pool_tokens == 0 {
+        return Err(CodoxError::LiquifyThresholdNotReached.into());
+    }
+
+    let (source_a, source_b, maximum_a, maximum_b) = if codox_is_a {
+        (liquidity_vault_info.key, quote_vault_info.key, liquidity_amount, quote_amount)
+    } else {
+        (quote_vault_info.key, liquidity_vault_info.key, quote_amount, liquidity_amount)
+    };
+    invoke_signed(
+        &amm::deposit_all_token_types(
+            &amm_pool,
+            pool_authority_info.key,
+            source_a,
+            source_b,
+            lp_vault_info.key,
+            pool_tokens,
+            maximum_a,
+            maximum_b,
+        ),
+        &amm_accounts,
+        &[signer_seeds],
+    )?;
+
+    let liquified = vault_balance - token::unpack_account(liquidity_vault_info)?.amount;
+    state.total_liquified = state.total_liquified.get().saturating_add(liquified).into();
+
+    msg!("Swapped {} tokens and added liquidity for {} LP tokens", swap_amount, pool_tokens);
+    Ok(())
+}
+
+#[cfg(feature = "burn")]
+fn process_buyback_and_burn(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let treasury_quote_vault_info = next_account_info(account_info_iter)?;
+    let treasury_vault_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let amm_program_info = next_account_info(account_info_iter)?;
+    let amm_pool_info = next_account_info(account_info_iter)?;
+    let amm_authority_info = next_account_info(account_info_iter)?;
+    let reserve_a_info = next_account_info(account_info_iter)?;
+    let reserve_b_info = next_account_info(account_info_iter)?;
+    let pool_mint_info = next_account_info(account_info_iter)?;
+    let fee_account_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+
+    // Neither the AMM nor the burn is passed the state account, so it stays borrowed
+    let mut state = load_state_mut(program_id, state_info)?;
+    check_authority_or_keeper(&state, caller_info)?;
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    if *treasury_vault_info.key != state.treasury_vault
+        || state.treasury_quote_vault == Pubkey::default()
+        || *treasury_quote_vault_info.key != state.treasury_quote_vault
+    {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    check_amm(&state, amm_program_info, amm_pool_info)?;
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+
+    if amount_in == 0 || amount_in > state.buyback_max_per_call.get() {
+        return Err(CodoxError::BuybackLimitExceeded.into());
+    }
+    if minimum_amount_out == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (codox_is_a, quote_mint) = orient_amm_reserves(&state.token_mint, reserve_a_info, reserve_b_info)?;
+    let (codox_reserve_info, quote_reserve_info) = if codox_is_a {
+        (reserve_a_info, reserve_b_info)
+    } else {
+        (reserve_b_info, reserve_a_info)
+    };
+    let treasury_quote_vault = token::unpack_account(treasury_quote_vault_info)?;
+    if treasury_quote_vault.owner != *pool_authority_info.key || treasury_quote_vault.mint != quote_mint {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+
+    let balance_before = token::unpack_account(treasury_vault_info)?.amount;
+    let signer_seeds: &[&[u8]] = &[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]];
+    invoke_signed(
+        &amm::swap(
+            &amm::AmmPool {
+                program_id: amm_program_info.key,
+                pool: amm_pool_info.key,
+                authority: amm_authority_info.key,
+                reserve_a: reserve_a_info.key,
+                reserve_b: reserve_b_info.key,
+                pool_mint: pool_mint_info.key,
+                fee_account: fee_account_info.key,
+                token_program: token_program_info.key,
+            },
+            pool_authority_info.key,
+            treasury_quote_vault_info.key,
+            quote_reserve_info.key,
+            codox_reserve_info.key,
+            treasury_vault_info.key,
+            amount_in,
+            minimum_amount_out,
+        ),
+        &[
+            amm_pool_info.clone(),
+            amm_authority_info.clone(),
+            pool_authority_info.clone(),
+            treasury_quote_vault_info.clone(),
+            quote_reserve_info.clone(),
+            codox_reserve_info.clone(),
+            treasury_vault_info.clone(),
+            pool_mint_info.clone(),
+            fee_account_info.clone(),
+            token_program_info.clone(),
+            amm_program_info.clone(),
+        ],
+        &[signer_seeds],
+    )?;
+
+    // Don't rely on the AMM to honour the bound; the treasury's tax share is burned with what was bought
+    let balance_after = token::unpack_account(treasury_vault_info)?.amount;
+    let bought = balance_after.saturating_sub(balance_before);
+    if bought < minimum_amount_out {
+        return Err(CodoxError::SlippageExceeded.into());
+    }
+    token::burn(token_program_info, treasury_vault_info, mint_info, pool_authority_info, balance_after, &[signer_seeds])?;
+
+    state.total_burned = state.total_burned.get().saturating_add(balance_after).into();
+
+    msg!(
+        "Bought back {} tokens for {} quote tokens and burned {} tokens",
+        bought,
+        amount_in,
+        balance_after
+    );
+    Ok(())
+}
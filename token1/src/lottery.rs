@@ -0,0 +1,1240 @@
+//! Lottery funded by the `lottery_rate` share of the tax.
+//!
+//! Every registered holder takes part. During a round, anyone can record holder
+//! balances as tickets with `SnapshotBalances`, which reads each holder's
+//! associated token account and marks their holder state entered, so every
+//! holder has one entry a round whoever records them and in whatever order.
+//! Tickets are stored in
+//! fixed-size pages opened with `OpenTicketPage`, each entry holding a holder and
+//! its range of tickets, and pages of drawn rounds are closed with
+//! `CloseTicketPage` to refund their rent. `DrawLottery` picks winners by ticket,
+//! binary searching the cumulative ticket ranges, splitting the pool's new inflow into prize tiers drawn as separate
+//! winners; whatever the tiers don't pay out grows the jackpot, which is paid to
+//! one more winner with a configurable chance on every draw. The winners and
+//! prizes of every round are recorded in a round history account, which escrows
+//! them in the lottery pool: only the winner can claim a prize, and prizes left
+//! unclaimed past the claim deadline go back into the jackpot.
+//!
+//! A lottery initialized in deposit mode is a no-loss savings lottery instead:
+//! holders lock CODOX in the lottery pool with `DepositToLottery`, and
+//! `SnapshotDeposits` records the deposits rather than wallet balances, each
+//! deposit once a round. Prizes
+//! still come only from the tax inflow, since deposits are kept out of the prize
+//! pool, and principal can be withdrawn once the round it's entered in is drawn.
+//!
+//! Once the lottery interval has passed, the authority or keeper closes the round
+//! with `CloseLotteryRound`, which stops snapshots and fixes a draw slot
+//! `DRAW_SLOT_DELAY` slots ahead. `DrawLottery` then draws the winners from that
+//! slot's hash, or the next produced slot's if it was skipped, so the seed wasn't
+//! known while tickets could still be recorded. The leader of the draw slot can
+//! still withhold its block, which is accepted for prizes funded by the tax. A
+//! draw slot whose hash has left the slot hashes sysvar can't be drawn, and the
+//! round is closed again for a new one.
+//!
+//! Balances are recorded as of the snapshot, so tokens a holder receives during a
+//! round don't earn tickets in it: the holder state records what transfers bring
+//! them, and that's taken out of their balance, so tokens moved on from a holder
+//! already recorded aren't counted again. For the same reason holders registering
+//! during a round join from the next one. In SPL Token mode this only covers the
+//! program's `Transfer`, as Token-2022 mode's transfer hook covers every transfer.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    keccak,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+// The instructions are built with the `lottery` feature; the accounts and draw math always are
+#[cfg(feature = "lottery")]
+use solana_program::{
+    account_info::next_account_info,
+    clock::Clock,
+    msg,
+    slot_hashes,
+    sysvar::{self, Sysvar},
+};
+
+#[cfg(feature = "lottery")]
+use crate::{
+    check_authority_or_keeper, check_pool_authority, create_pda_account, error::CodoxError, events::CodoxEvent,
+    load_holder_state_pda_mut, load_state, load_state_mut, token, CodoxTokenState, POOL_AUTHORITY_SEED,
+};
+
+/// Seed for the lottery state account
+pub const LOTTERY_SEED: &[u8] = b"lottery";
+/// Seed for per-round lottery history accounts
+pub const LOTTERY_ROUND_SEED: &[u8] = b"lottery_round";
+/// Number of prize tiers drawn each round
+pub const MAX_PRIZE_TIERS: usize = 3;
+/// Prize slots in a round: the tiers followed by the jackpot
+pub const PRIZE_SLOTS: usize = MAX_PRIZE_TIERS + 1;
+/// Index of the jackpot in a round's prize slots
+pub const JACKPOT_SLOT: usize = MAX_PRIZE_TIERS;
+/// Seed for per-round ticket pages
+pub const LOTTERY_TICKETS_SEED: &[u8] = b"lottery_tickets";
+/// Entries in one ticket page
+pub const TICKET_PAGE_SIZE: usize = 128;
+/// Bytes of one ticket entry: the holder, its first ticket and its ticket count
+pub const TICKET_ENTRY_LEN: usize = 32 + 8 + 8;
+/// Space for a ticket page account
+pub const TICKET_PAGE_LEN: usize = TicketPage::LEN + TICKET_PAGE_SIZE * TICKET_ENTRY_LEN;
+/// Seed for a holder's lottery deposit account
+pub const LOTTERY_DEPOSIT_SEED: &[u8] = b"lottery_deposit";
+/// Redraws for a tier whose ticket landed on a holder who already won this round
+pub const MAX_DRAW_ATTEMPTS: u8 = 16;
+/// Slots from closing a round to the slot whose hash draws it, past the closing leader's own slots
+pub const DRAW_SLOT_DELAY: u64 = 8;
+
+/// Find the lottery state account
+pub fn find_lottery_state(program_id: &Pubkey, state: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOTTERY_SEED, state.as_ref()], program_id)
+}
+
+/// Find the history account of a lottery round
+pub fn find_lottery_round(program_id: &Pubkey, state: &Pubkey, round: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOTTERY_ROUND_SEED, state.as_ref(), &round.to_le_bytes()], program_id)
+}
+
+/// Find a page of a round's tickets
+pub fn find_ticket_page(program_id: &Pubkey, state: &Pubkey, round: u64, page: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[LOTTERY_TICKETS_SEED, state.as_ref(), &round.to_le_bytes(), &page.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Find a holder's lottery deposit account
+pub fn find_lottery_deposit(program_id: &Pubkey, state: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[LOTTERY_DEPOSIT_SEED, state.as_ref(), owner.as_ref()], program_id)
+}
+
+/// Lottery state
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LotteryState {
+    pub is_initialized: bool,
+    pub round: u64,                            // Round currently taking snapshots
+    pub jackpot: u64,                          // Carried over between rounds
+    pub prizes_owed: u64,                      // Escrowed for winners, drawn but not yet claimed
+    pub prize_tiers: [u16; MAX_PRIZE_TIERS],   // Basis points of each round's prize pool
+    pub jackpot_chance: u16,                   // Basis points chance of paying the jackpot each draw
+    pub ticket_pages: u32,                     // Ticket pages opened this round
+    pub total_tickets: u64,                    // Tickets recorded this round
+    pub deposit_mode: bool,                    // Tickets come from deposits instead of balances
+    pub total_deposits: u64,                   // Principal held in the lottery pool
+    pub claim_period: i64,                     // Seconds winners have to claim a prize
+    pub draw_slot: u64,                        // Slot whose hash draws the closed round, 0 while it takes tickets
+}
+
+impl LotteryState {
+    /// Space for the state
+    pub const LEN: usize = 1 + 8 + 8 + 8 + 2 * MAX_PRIZE_TIERS + 2 + 4 + 8 + 1 + 8 + 8 + 8;
+
+    /// Load the lottery state, checking it belongs to this program
+    pub fn load(program_id: &Pubkey, lottery_info: &AccountInfo) -> Result<Self, ProgramError> {
+        if lottery_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let lottery = Self::deserialize(&mut &lottery_info.data.borrow()[..])?;
+        if !lottery.is_initialized {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(lottery)
+    }
+
+    /// Write the lottery state back to its account
+    pub fn save(&self, lottery_info: &AccountInfo) -> ProgramResult {
+        self.serialize(&mut &mut lottery_info.data.borrow_mut()[..])?;
+        Ok(())
+    }
+
+    /// Tokens in the lottery pool not yet assigned to the jackpot, a winner or a depositor
+    pub fn prize_pool(&self, pool_balance: u64) -> u64 {
+        pool_balance
+            .saturating_sub(self.jackpot)
+            .saturating_sub(self.prizes_owed)
+            .saturating_sub(self.total_deposits)
+    }
+}
+
+/// Winners and prizes of a drawn round
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct LotteryRound {
+    pub round: u64,
+    pub drawn_at: i64,
+    pub participants: u64,               // Ticket entries of the round
+    pub prize_pool: u64,                 // New inflow split between the tiers
+    pub jackpot: u64,                    // Jackpot carried over after the draw
+    pub winners: [Pubkey; PRIZE_SLOTS],  // Tier winners, then the jackpot winner
+    pub prizes: [u64; PRIZE_SLOTS],
+    pub paid: u8,                        // Bit per prize slot
+    pub seed: [u8; 32],                  // Randomness the winners were drawn from
+    pub claim_deadline: i64,             // Prizes unclaimed by then go back to the jackpot
+    pub expired: bool,                   // Unclaimed prizes were returned to the jackpot
+}
+
+impl LotteryRound {
+    /// Whether the prize in `slot` has been paid out
+    pub fn is_paid(&self, slot: usize) -> bool {
+        self.paid & (1 << slot) != 0
+    }
+
+    /// Prizes still escrowed for their winners
+    pub fn unclaimed(&self) -> u64 {
+        if self.expired {
+            return 0;
+        }
+        (0..PRIZE_SLOTS).filter(|slot| !self.is_paid(*slot)).map(|slot| self.prizes[slot]).sum()
+    }
+}
+
+impl Sealed for LotteryRound {}
+
+impl IsInitialized for LotteryRound {
+    fn is_initialized(&self) -> bool {
+        self.drawn_at != 0
+    }
+}
+
+impl Pack for LotteryRound {
+    const LEN: usize = 8 + 8 + 8 + 8 + 8 + 32 * PRIZE_SLOTS + 8 * PRIZE_SLOTS + 1 + 32 + 8 + 1;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = arrayref::array_ref![src, 0, LotteryRound::LEN];
+        let (round, drawn_at, participants, prize_pool, jackpot, winners, prizes, paid, seed, claim_deadline, expired) =
+            arrayref::array_refs![src, 8, 8, 8, 8, 8, 32 * PRIZE_SLOTS, 8 * PRIZE_SLOTS, 1, 32, 8, 1];
+
+        Ok(LotteryRound {
+            round: u64::from_le_bytes(*round),
+            drawn_at: i64::from_le_bytes(*drawn_at),
+            participants: u64::from_le_bytes(*participants),
+            prize_pool: u64::from_le_bytes(*prize_pool),
+            jackpot: u64::from_le_bytes(*jackpot),
+            winners: std::array::from_fn(|i| Pubkey::new_from_array(*arrayref::array_ref![winners, 32 * i, 32])),
+            prizes: std::array::from_fn(|i| u64::from_le_bytes(*arrayref::array_ref![prizes, 8 * i, 8])),
+            paid: paid[0],
+            seed: *seed,
+            claim_deadline: i64::from_le_bytes(*claim_deadline),
+            expired: expired[0] != 0,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = arrayref::array_mut_ref![dst, 0, LotteryRound::LEN];
+        let (
+            round_dst,
+            drawn_at_dst,
+            participants_dst,
+            prize_pool_dst,
+            jackpot_dst,
+            winners_dst,
+            prizes_dst,
+            paid_dst,
+            seed_dst,
+            claim_deadline_dst,
+            expired_dst,
+        ) = arrayref::mut_array_refs![dst, 8, 8, 8, 8, 8, 32 * PRIZE_SLOTS, 8 * PRIZE_SLOTS, 1, 32, 8, 1];
+
+        *round_dst = self.round.to_le_bytes();
+        *drawn_at_dst = self.drawn_at.to_le_bytes();
+        *participants_dst = self.participants.to_le_bytes();
+        *prize_pool_dst = self.prize_pool.to_le_bytes();
+        *jackpot_dst = self.jackpot.to_le_bytes();
+        for (winner_dst, winner) in winners_dst.chunks_exact_mut(32).zip(self.winners.iter()) {
+            winner_dst.copy_from_slice(winner.as_ref());
+        }
+        for (prize_dst, prize) in prizes_dst.chunks_exact_mut(8).zip(self.prizes.iter()) {
+            prize_dst.copy_from_slice(&prize.to_le_bytes());
+        }
+        paid_dst[0] = self.paid;
+        *seed_dst = self.seed;
+        *claim_deadline_dst = self.claim_deadline.to_le_bytes();
+        expired_dst[0] = self.expired as u8;
+    }
+}
+
+/// A holder's principal in a deposit-mode lottery
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct LotteryDeposit {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub locked_round: u64,               // Withdrawable once this round is drawn
+    pub entered_round_start: i64,        // `last_lottery_draw` of the round it was last entered in
+}
+
+impl Sealed for LotteryDeposit {}
+
+impl IsInitialized for LotteryDeposit {
+    fn is_initialized(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+}
+
+impl Pack for LotteryDeposit {
+    const LEN: usize = 32 + 8 + 8 + 8;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = arrayref::array_ref![src, 0, LotteryDeposit::LEN];
+        let (owner, amount, locked_round, entered_round_start) = arrayref::array_refs![src, 32, 8, 8, 8];
+
+        Ok(LotteryDeposit {
+            owner: Pubkey::new_from_array(*owner),
+            amount: u64::from_le_bytes(*amount),
+            locked_round: u64::from_le_bytes(*locked_round),
+            entered_round_start: i64::from_le_bytes(*entered_round_start),
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = arrayref::array_mut_ref![dst, 0, LotteryDeposit::LEN];
+        let (owner_dst, amount_dst, locked_round_dst, entered_round_start_dst) =
+            arrayref::mut_array_refs![dst, 32, 8, 8, 8];
+
+        owner_dst.copy_from_slice(self.owner.as_ref());
+        *amount_dst = self.amount.to_le_bytes();
+        *locked_round_dst = self.locked_round.to_le_bytes();
+        *entered_round_start_dst = self.entered_round_start.to_le_bytes();
+    }
+}
+
+/// Header of a ticket page, followed in the account by `count` entries of
+/// (holder, first ticket, tickets) in ticket order. The page holds tickets
+/// `first_ticket..first_ticket + tickets`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default)]
+pub struct TicketPage {
+    pub round: u64,
+    pub page: u32,
+    pub count: u32,
+    pub first_ticket: u64,               // Tickets recorded on earlier pages
+    pub tickets: u64,                    // Tickets recorded on this page
+    pub payer: Pubkey,                   // Refunded the rent when the page is closed
+    pub is_initialized: bool,
+}
+
+impl TicketPage {
+    /// The holder, first ticket and ticket count of entry `index` in a page account's data
+    pub fn entry(data: &[u8], index: usize) -> (Pubkey, u64, u64) {
+        let offset = Self::LEN + index * TICKET_ENTRY_LEN;
+        let (holder, first_ticket, tickets) =
+            arrayref::array_refs![arrayref::array_ref![data, offset, TICKET_ENTRY_LEN], 32, 8, 8];
+        (Pubkey::new_from_array(*holder), u64::from_le_bytes(*first_ticket), u64::from_le_bytes(*tickets))
+    }
+
+    /// Write entry `index` into a page account's data
+    pub fn set_entry(data: &mut [u8], index: usize, holder: &Pubkey, first_ticket: u64, tickets: u64) {
+        let offset = Self::LEN + index * TICKET_ENTRY_LEN;
+        let (holder_dst, first_ticket_dst, tickets_dst) =
+            arrayref::mut_array_refs![arrayref::array_mut_ref![data, offset, TICKET_ENTRY_LEN], 32, 8, 8];
+        holder_dst.copy_from_slice(holder.as_ref());
+        *first_ticket_dst = first_ticket.to_le_bytes();
+        *tickets_dst = tickets.to_le_bytes();
+    }
+
+    /// Tickets held by `holder` on this page
+    pub fn holder_tickets(&self, data: &[u8], holder: &Pubkey) -> u64 {
+        (0..self.count as usize)
+            .map(|index| Self::entry(data, index))
+            .filter(|(owner, _, _)| owner == holder)
+            .map(|(_, _, tickets)| tickets)
+            .sum()
+    }
+
+    /// Whether `ticket` falls within this page
+    pub fn contains(&self, ticket: u64) -> bool {
+        ticket >= self.first_ticket && ticket - self.first_ticket < self.tickets
+    }
+
+    /// Holder owning `ticket`, binary searching the page's entries
+    pub fn holder_of(&self, data: &[u8], ticket: u64) -> Option<Pubkey> {
+        if !self.contains(ticket) {
+            return None;
+        }
+        // Last entry starting at or before the ticket
+        let (mut low, mut high) = (0, self.count as usize);
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if Self::entry(data, mid).1 <= ticket {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Some(Self::entry(data, low).0)
+    }
+}
+
+impl Sealed for TicketPage {}
+
+impl IsInitialized for TicketPage {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for TicketPage {
+    const LEN: usize = 8 + 4 + 4 + 8 + 8 + 32 + 1;
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let src = arrayref::array_ref![src, 0, TicketPage::LEN];
+        let (round, page, count, first_ticket, tickets, payer, is_initialized) =
+            arrayref::array_refs![src, 8, 4, 4, 8, 8, 32, 1];
+
+        Ok(TicketPage {
+            round: u64::from_le_bytes(*round),
+            page: u32::from_le_bytes(*page),
+            count: u32::from_le_bytes(*count),
+            first_ticket: u64::from_le_bytes(*first_ticket),
+            tickets: u64::from_le_bytes(*tickets),
+            payer: Pubkey::new_from_array(*payer),
+            is_initialized: is_initialized[0] != 0,
+        })
+    }
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let dst = arrayref::array_mut_ref![dst, 0, TicketPage::LEN];
+        let (round_dst, page_dst, count_dst, first_ticket_dst, tickets_dst, payer_dst, is_initialized_dst) =
+            arrayref::mut_array_refs![dst, 8, 4, 4, 8, 8, 32, 1];
+
+        *round_dst = self.round.to_le_bytes();
+        *page_dst = self.page.to_le_bytes();
+        *count_dst = self.count.to_le_bytes();
+        *first_ticket_dst = self.first_ticket.to_le_bytes();
+        *tickets_dst = self.tickets.to_le_bytes();
+        payer_dst.copy_from_slice(self.payer.as_ref());
+        is_initialized_dst[0] = self.is_initialized as u8;
+    }
+}
+
+/// Load the header of a ticket page, checking it belongs to this program
+#[cfg(feature = "lottery")]
+pub(crate) fn load_ticket_page(program_id: &Pubkey, page_info: &AccountInfo) -> Result<TicketPage, ProgramError> {
+    if page_info.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    TicketPage::unpack(page_info.data.borrow().get(..TicketPage::LEN).ok_or(ProgramError::InvalidAccountData)?)
+}
+
+/// Random ticket below `total` for prize slot `slot`, drawn from `seed`;
+/// `attempt` gives a fresh ticket when the first lands on a repeat winner
+pub fn draw_ticket(seed: &[u8; 32], slot: u8, attempt: u8, total: u64) -> u64 {
+    let hash = keccak::hashv(&[seed, &[slot, attempt]]).to_bytes();
+    u64::from_le_bytes(*arrayref::array_ref![hash, 0, 8]) % total
+}
+
+/// Prize of a tier paying `tier_bps` of `prize_pool`
+pub fn tier_prize(prize_pool: u64, tier_bps: u16) -> u64 {
+    (prize_pool as u128 * tier_bps as u128 / 10000) as u64
+}
+
+/// Whether the jackpot pays out this draw, with `chance` in basis points
+pub fn jackpot_hit(seed: &[u8; 32], chance: u16) -> bool {
+    let hash = keccak::hashv(&[seed, b"jackpot"]).to_bytes();
+    u64::from_le_bytes(*arrayref::array_ref![hash, 0, 8]) % 10000 < chance as u64
+}
+
+/// Draw `prize_tiers` winners (without repeats) and maybe the jackpot over
+/// `total_tickets` tickets, where `holder_of` maps a ticket to the holder owning
+/// it, returning the round's winners and prizes. A tier whose draws keep landing
+/// on earlier winners goes unpaid; tier prizes without a winner and the
+/// undistributed remainder are added to `jackpot`.
+pub fn draw_winners<F>(
+    seed: &[u8; 32],
+    total_tickets: u64,
+    prize_pool: u64,
+    prize_tiers: &[u16; MAX_PRIZE_TIERS],
+    jackpot: &mut u64,
+    jackpot_chance: u16,
+    mut holder_of: F,
+) -> Result<([Pubkey; PRIZE_SLOTS], [u64; PRIZE_SLOTS]), ProgramError>
+where
+    F: FnMut(u64) -> Result<Pubkey, ProgramError>,
+{
+    let mut winners = [Pubkey::default(); PRIZE_SLOTS];
+    let mut prizes = [0u64; PRIZE_SLOTS];
+    let mut distributed = 0u64;
+
+    if total_tickets > 0 {
+        for (tier, tier_bps) in prize_tiers.iter().enumerate() {
+            for attempt in 0..MAX_DRAW_ATTEMPTS {
+                let holder = holder_of(draw_ticket(seed, tier as u8, attempt, total_tickets))?;
+                if !winners[..tier].contains(&holder) {
+                    winners[tier] = holder;
+                    prizes[tier] = tier_prize(prize_pool, *tier_bps);
+                    distributed += prizes[tier];
+                    break;
+                }
+            }
+        }
+    }
+    *jackpot = jackpot.saturating_add(prize_pool - distributed);
+
+    if total_tickets > 0 && *jackpot > 0 && jackpot_hit(seed, jackpot_chance) {
+        winners[JACKPOT_SLOT] = holder_of(draw_ticket(seed, JACKPOT_SLOT as u8, 0, total_tickets))?;
+        prizes[JACKPOT_SLOT] = std::mem::take(jackpot);
+    }
+    Ok((winners, prizes))
+}
+
+/// Randomness for a draw: the hash of the round's draw slot, or of the first slot
+/// produced after it, mixed with the state and round
+#[cfg(feature = "lottery")]
+fn draw_seed(slot_hashes_info: &AccountInfo, draw_slot: u64, state: &Pubkey, round: u64) -> Result<[u8; 32], ProgramError> {
+    if *slot_hashes_info.key != sysvar::slot_hashes::id() {
+        return Err(ProgramError::InvalidArgument);
+    }
+    // Serialized as a u64 length, then (slot, hash) entries newest first
+    let data = slot_hashes_info.data.borrow();
+    let len = u64::from_le_bytes(*arrayref::array_ref![data.get(..8).ok_or(ProgramError::InvalidAccountData)?, 0, 8]);
+    let (mut draw_entry, mut reached_older) = (None, false);
+    for index in 0..len as usize {
+        let entry = data.get(8 + index * 40..8 + (index + 1) * 40).ok_or(ProgramError::InvalidAccountData)?;
+        let slot = u64::from_le_bytes(*arrayref::array_ref![entry, 0, 8]);
+        if slot < draw_slot {
+            reached_older = true;
+            break;
+        }
+        draw_entry = Some((slot, &entry[8..]));
+    }
+    let (slot, draw_hash) = draw_entry.ok_or(CodoxError::LotteryDrawTooEarly)?;
+    // Without an older entry, the first slot produced after the draw slot may have been dropped
+    if slot != draw_slot && !reached_older && len as usize >= slot_hashes::MAX_ENTRIES {
+        return Err(CodoxError::DrawSlotExpired.into());
+    }
+    Ok(keccak::hashv(&[draw_hash, state.as_ref(), &round.to_le_bytes()]).to_bytes())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_initialize_lottery(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    prize_tiers: [u16; MAX_PRIZE_TIERS],
+    jackpot_chance: u16,
+    deposit_mode: bool,
+    claim_period: i64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let mut state = load_state_mut(program_id, state_info)?;
+    if !authority_info.is_signer || *authority_info.key != state.authority {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if prize_tiers.iter().map(|tier| *tier as u32).sum::<u32>() > 10000 || jackpot_chance > 10000 || claim_period <= 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (lottery_address, bump) = find_lottery_state(program_id, state_info.key);
+    if *lottery_info.key != lottery_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    if lottery_info.data_len() != 0 {
+        return Err(CodoxError::AlreadyInitialized.into());
+    }
+    create_pda_account(
+        authority_info,
+        lottery_info,
+        LotteryState::LEN,
+        program_id,
+        system_program_info,
+        &[LOTTERY_SEED, state_info.key.as_ref(), &[bump]],
+    )?;
+
+    LotteryState {
+        is_initialized: true,
+        round: 0,
+        jackpot: 0,
+        prizes_owed: 0,
+        prize_tiers,
+        jackpot_chance,
+        ticket_pages: 0,
+        total_tickets: 0,
+        deposit_mode,
+        total_deposits: 0,
+        claim_period,
+        draw_slot: 0,
+    }
+    .save(lottery_info)?;
+    // Round 0 starts now: holders registering later join from round 1
+    state.last_lottery_draw = Clock::get()?.unix_timestamp.into();
+
+    msg!("Lottery initialized, deposit mode: {}", deposit_mode);
+    Ok(())
+}
+
+/// A holder's tickets from one of the accounts passed to a snapshot
+#[cfg(feature = "lottery")]
+struct SnapshotEntry {
+    holder: Pubkey,
+    tickets: u64,
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_snapshot_balances(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    record_snapshot(program_id, accounts, false, |state_address, state, _, entry_infos| {
+        let [holder_token_info, holder_state_info] = entry_infos else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        let holder_token = token::unpack_account(holder_token_info)?;
+        if holder_token.mint != state.token_mint {
+            return Err(CodoxError::InvalidMint.into());
+        }
+        // Program pools, the AMM and other exempt accounts don't play
+        if holder_token.amount == 0 || state.is_limit_exempt(holder_token_info.key, &holder_token.owner) {
+            return Ok(None);
+        }
+        // One account per holder, so nobody can enter a holder with only part of their balance
+        let associated_token = spl_associated_token_account::get_associated_token_address_with_program_id(
+            &holder_token.owner,
+            &state.token_mint,
+            &token::id(),
+        );
+        if *holder_token_info.key != associated_token {
+            return Err(CodoxError::InvalidSnapshotAccount.into());
+        }
+        // Only registered holders, whose holder state records what transfers bring
+        // them, so tokens moved after a snapshot aren't counted again
+        let Some(mut holder_state) =
+            load_holder_state_pda_mut(program_id, state_address, holder_state_info, &holder_token.owner)?
+        else {
+            return Ok(None);
+        };
+        let round_start = state.last_lottery_draw.get();
+        let received = holder_state.lottery_received(round_start);
+        // Entered holders count as having received everything, which leaves them no
+        // tickets if they're passed again this round
+        holder_state.record_lottery_receipt(round_start, u64::MAX);
+        Ok(Some(SnapshotEntry {
+            holder: holder_token.owner,
+            tickets: holder_token.amount.saturating_sub(received),
+        }))
+    })
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_snapshot_deposits(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    record_snapshot(program_id, accounts, true, |_, state, round, entry_infos| {
+        let [deposit_info] = entry_infos else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        if deposit_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut deposit = LotteryDeposit::unpack(&deposit_info.data.borrow())?;
+        let round_start = state.last_lottery_draw.get();
+        if deposit.amount == 0 || deposit.entered_round_start == round_start {
+            return Ok(None);
+        }
+        // Entered principal stays in the pool until this round is drawn, so it can't move
+        deposit.locked_round = round;
+        deposit.entered_round_start = round_start;
+        let entry = SnapshotEntry {
+            holder: deposit.owner,
+            tickets: deposit.amount,
+        };
+        LotteryDeposit::pack(deposit, &mut deposit_info.data.borrow_mut())?;
+        Ok(Some(entry))
+    })
+}
+
+/// Record the accounts after the fixed ones as tickets on the current round's
+/// last page, with `entry` giving the holder and tickets of each account (a token
+/// account and its owner's holder state in balance mode, a deposit in deposit
+/// mode) and marking it entered, or `None` to skip it
+#[cfg(feature = "lottery")]
+fn record_snapshot<F>(program_id: &Pubkey, accounts: &[AccountInfo], deposit_mode: bool, mut entry: F) -> ProgramResult
+where
+    F: FnMut(&Pubkey, &CodoxTokenState, u64, &[AccountInfo]) -> Result<Option<SnapshotEntry>, ProgramError>,
+{
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let page_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let entry_infos = account_info_iter.as_slice();
+
+    if !caller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    if lottery.deposit_mode != deposit_mode {
+        return Err(CodoxError::LotteryModeMismatch.into());
+    }
+    if lottery.draw_slot != 0 {
+        return Err(CodoxError::LotteryRoundClosed.into());
+    }
+    let round = lottery.round;
+
+    if lottery.ticket_pages == 0
+        || *page_info.key != find_ticket_page(program_id, state_info.key, round, lottery.ticket_pages - 1).0
+    {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let mut page = load_ticket_page(program_id, page_info)?;
+
+    let entries = entry_infos.chunks_exact(if deposit_mode { 1 } else { 2 });
+    if !entries.remainder().is_empty() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let mut data = page_info.data.borrow_mut();
+    for entry_accounts in entries {
+        let Some(SnapshotEntry { holder, tickets }) = entry(state_info.key, &state, round, entry_accounts)? else {
+            continue;
+        };
+        if tickets == 0 {
+            continue;
+        }
+        if page.count as usize >= TICKET_PAGE_SIZE {
+            return Err(CodoxError::TicketPageFull.into());
+        }
+        TicketPage::set_entry(&mut data, page.count as usize, &holder, lottery.total_tickets, tickets);
+        page.count += 1;
+        CodoxEvent::LotteryEntered {
+            round,
+            holder,
+            page: page.page,
+            first_ticket: lottery.total_tickets,
+            tickets,
+        }
+        .emit();
+        page.tickets += tickets;
+        lottery.total_tickets += tickets;
+    }
+    TicketPage::pack(page.clone(), &mut data[..TicketPage::LEN])?;
+    drop(data);
+    lottery.save(lottery_info)?;
+
+    msg!("Lottery round {} ticket page {}: {} entries", round, page.page, page.count);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_open_ticket_page(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let payer_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let page_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !payer_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    load_state(program_id, state_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    if lottery.draw_slot != 0 {
+        return Err(CodoxError::LotteryRoundClosed.into());
+    }
+    let round = lottery.round;
+
+    // Only once the last page is full, so the draw isn't padded with empty pages
+    if lottery.ticket_pages > 0 {
+        let previous_page_info = next_account_info(account_info_iter)?;
+        if *previous_page_info.key != find_ticket_page(program_id, state_info.key, round, lottery.ticket_pages - 1).0 {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if (load_ticket_page(program_id, previous_page_info)?.count as usize) < TICKET_PAGE_SIZE {
+            return Err(CodoxError::TicketPageNotFull.into());
+        }
+    }
+
+    let page_index = lottery.ticket_pages;
+    let (page_address, bump) = find_ticket_page(program_id, state_info.key, round, page_index);
+    if *page_info.key != page_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        payer_info,
+        page_info,
+        TICKET_PAGE_LEN,
+        program_id,
+        system_program_info,
+        &[
+            LOTTERY_TICKETS_SEED,
+            state_info.key.as_ref(),
+            &round.to_le_bytes(),
+            &page_index.to_le_bytes(),
+            &[bump],
+        ],
+    )?;
+    TicketPage::pack(
+        TicketPage {
+            round,
+            page: page_index,
+            count: 0,
+            first_ticket: lottery.total_tickets,
+            tickets: 0,
+            payer: *payer_info.key,
+            is_initialized: true,
+        },
+        &mut page_info.data.borrow_mut()[..TicketPage::LEN],
+    )?;
+
+    lottery.ticket_pages += 1;
+    lottery.save(lottery_info)?;
+
+    msg!("Opened lottery round {} ticket page {}", round, page_index);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_close_ticket_page(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let page_info = next_account_info(account_info_iter)?;
+    let payer_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+
+    load_state(program_id, state_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let lottery = LotteryState::load(program_id, lottery_info)?;
+
+    let page = load_ticket_page(program_id, page_info)?;
+    if *page_info.key != find_ticket_page(program_id, state_info.key, page.round, page.page).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    // The current round still needs its pages for the draw
+    if page.round >= lottery.round {
+        return Err(CodoxError::TicketPageInUse.into());
+    }
+    if *payer_info.key != page.payer {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let rent = page_info.lamports();
+    **page_info.lamports.borrow_mut() = 0;
+    **payer_info.lamports.borrow_mut() = payer_info.lamports().checked_add(rent).ok_or(ProgramError::ArithmeticOverflow)?;
+    page_info.data.borrow_mut().fill(0);
+
+    msg!("Closed lottery round {} ticket page {}", page.round, page.page);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_close_lottery_round(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_info)?;
+    check_authority_or_keeper(&state, caller_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+
+    let clock = Clock::get()?;
+    if clock.unix_timestamp < state.last_lottery_draw.get().saturating_add(state.lottery_interval.get()) {
+        return Err(CodoxError::LotteryDrawTooEarly.into());
+    }
+    // A closed round only gets a new draw slot once the old one can't be drawn
+    if lottery.draw_slot != 0 && clock.slot <= lottery.draw_slot.saturating_add(slot_hashes::MAX_ENTRIES as u64) {
+        return Err(CodoxError::LotteryRoundClosed.into());
+    }
+    lottery.draw_slot = clock.slot + DRAW_SLOT_DELAY;
+    lottery.save(lottery_info)?;
+
+    msg!("Lottery round {} closed, drawn from slot {}", lottery.round, lottery.draw_slot);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_draw_lottery(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let caller_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let round_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let slot_hashes_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let page_infos = account_info_iter.as_slice();
+
+    if !caller_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    // Only the system program is invoked, without the state account, so it stays borrowed
+    let mut state = load_state_mut(program_id, state_info)?;
+    if *lottery_pool_info.key != state.lottery_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    if lottery.draw_slot == 0 {
+        return Err(CodoxError::LotteryRoundOpen.into());
+    }
+    let round = lottery.round;
+    let seed = draw_seed(slot_hashes_info, lottery.draw_slot, state_info.key, round)?;
+    let current_time = Clock::get()?.unix_timestamp;
+
+    // The round's history account, paid for by the caller
+    let (round_address, bump) = find_lottery_round(program_id, state_info.key, round);
+    if *round_info.key != round_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    create_pda_account(
+        caller_info,
+        round_info,
+        LotteryRound::LEN,
+        program_id,
+        system_program_info,
+        &[LOTTERY_ROUND_SEED, state_info.key.as_ref(), &round.to_le_bytes(), &[bump]],
+    )?;
+
+    // Every ticket page of the round, in order
+    if page_infos.len() != lottery.ticket_pages as usize {
+        return Err(CodoxError::TicketPagesMissing.into());
+    }
+    let mut pages = Vec::with_capacity(page_infos.len());
+    let mut entries = 0u64;
+    for (index, page_info) in page_infos.iter().enumerate() {
+        if *page_info.key != find_ticket_page(program_id, state_info.key, round, index as u32).0 {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let page = load_ticket_page(program_id, page_info)?;
+        entries += page.count as u64;
+        pages.push(page);
+    }
+
+    let prize_pool = lottery.prize_pool(token::unpack_account(lottery_pool_info)?.amount);
+    let (winners, prizes) = draw_winners(
+        &seed,
+        lottery.total_tickets,
+        prize_pool,
+        &lottery.prize_tiers,
+        &mut lottery.jackpot,
+        lottery.jackpot_chance,
+        |ticket| {
+            // Pages are in ticket order; find the first one ending past the ticket
+            let index = pages.partition_point(|page| page.first_ticket + page.tickets <= ticket);
+            pages
+                .get(index)
+                .and_then(|page| page.holder_of(&page_infos[index].data.borrow(), ticket))
+                .ok_or(ProgramError::InvalidAccountData)
+        },
+    )?;
+    let total_prizes: u64 = prizes.iter().sum();
+    let claim_deadline = current_time.saturating_add(lottery.claim_period);
+
+    LotteryRound::pack(
+        LotteryRound {
+            round,
+            drawn_at: current_time,
+            participants: entries,
+            prize_pool,
+            jackpot: lottery.jackpot,
+            winners,
+            prizes,
+            paid: 0,
+            seed,
+            claim_deadline,
+            expired: false,
+        },
+        &mut round_info.data.borrow_mut(),
+    )?;
+    CodoxEvent::LotteryDrawn {
+        round,
+        total_tickets: lottery.total_tickets,
+        prize_pool,
+        prize_tiers: lottery.prize_tiers,
+        winners,
+        prizes,
+        jackpot: lottery.jackpot,
+        claim_deadline,
+    }
+    .emit();
+
+    lottery.prizes_owed = lottery.prizes_owed.saturating_add(total_prizes);
+    lottery.round += 1;
+    lottery.ticket_pages = 0;
+    lottery.total_tickets = 0;
+    lottery.draw_slot = 0;
+    lottery.save(lottery_info)?;
+
+    state.last_lottery_draw = current_time.into();
+
+    msg!("Lottery round {} drawn: {} tokens in prizes, jackpot {}", round, total_prizes, lottery.jackpot);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_claim_lottery_prize(program_id: &Pubkey, accounts: &[AccountInfo], round: u64, slot: u8) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let winner_info = next_account_info(account_info_iter)?;
+    let winner_token_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let round_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    let state = load_state(program_id, state_info)?;
+    if *lottery_pool_info.key != state.lottery_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    drop(state);
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    let mut lottery_round = load_lottery_round(program_id, state_info.key, round_info, round)?;
+
+    let slot = slot as usize;
+    if slot >= PRIZE_SLOTS || lottery_round.prizes[slot] == 0 || lottery_round.is_paid(slot) || lottery_round.expired {
+        return Err(CodoxError::PrizeUnavailable.into());
+    }
+    if !winner_info.is_signer || *winner_info.key != lottery_round.winners[slot] {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if Clock::get()?.unix_timestamp > lottery_round.claim_deadline {
+        return Err(CodoxError::ClaimDeadlinePassed.into());
+    }
+
+    let prize = lottery_round.prizes[slot];
+    token::transfer(
+        token_program_info,
+        lottery_pool_info,
+        mint_info,
+        winner_token_info,
+        pool_authority_info,
+        hook_accounts,
+        prize,
+        &[&[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]]],
+    )?;
+
+    lottery_round.paid |= 1 << slot;
+    LotteryRound::pack(lottery_round, &mut round_info.data.borrow_mut())?;
+    lottery.prizes_owed = lottery.prizes_owed.saturating_sub(prize);
+    lottery.save(lottery_info)?;
+
+    msg!("Claimed lottery round {} prize {}: {} tokens", round, slot, prize);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_expire_lottery_prizes(program_id: &Pubkey, accounts: &[AccountInfo], round: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lottery_info = next_account_info(account_info_iter)?;
+    let round_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+
+    load_state(program_id, state_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    let mut lottery_round = load_lottery_round(program_id, state_info.key, round_info, round)?;
+
+    if lottery_round.expired {
+        return Err(CodoxError::PrizeUnavailable.into());
+    }
+    if Clock::get()?.unix_timestamp <= lottery_round.claim_deadline {
+        return Err(CodoxError::ClaimPeriodActive.into());
+    }
+
+    // Unclaimed prizes leave the escrow for the next draw's jackpot
+    let unclaimed = lottery_round.unclaimed();
+    lottery_round.expired = true;
+    LotteryRound::pack(lottery_round, &mut round_info.data.borrow_mut())?;
+    lottery.prizes_owed = lottery.prizes_owed.saturating_sub(unclaimed);
+    lottery.jackpot = lottery.jackpot.saturating_add(unclaimed);
+    lottery.save(lottery_info)?;
+
+    msg!("Lottery round {} expired: {} unclaimed tokens added to the jackpot", round, unclaimed);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_deposit_to_lottery(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let depositor_info = next_account_info(account_info_iter)?;
+    let depositor_token_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let deposit_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    if !depositor_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    if *lottery_pool_info.key != state.lottery_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let reflection_index = state.reflection_index.get();
+    drop(state);
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+    if !lottery.deposit_mode {
+        return Err(CodoxError::LotteryModeMismatch.into());
+    }
+    if amount == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let (deposit_address, bump) = find_lottery_deposit(program_id, state_info.key, depositor_info.key);
+    if *deposit_info.key != deposit_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let mut deposit = if deposit_info.data_len() == 0 {
+        create_pda_account(
+            depositor_info,
+            deposit_info,
+            LotteryDeposit::LEN,
+            program_id,
+            system_program_info,
+            &[LOTTERY_DEPOSIT_SEED, state_info.key.as_ref(), depositor_info.key.as_ref(), &[bump]],
+        )?;
+        LotteryDeposit {
+            owner: *depositor_info.key,
+            ..LotteryDeposit::default()
+        }
+    } else {
+        LotteryDeposit::unpack(&deposit_info.data.borrow())?
+    };
+    let balance = token::unpack_account(depositor_token_info)?.amount;
+
+    token::transfer(
+        token_program_info,
+        depositor_token_info,
+        mint_info,
+        lottery_pool_info,
+        depositor_info,
+        hook_accounts,
+        amount,
+        &[],
+    )?;
+    // The mint's transfer fee never reaches the pool
+    let received = amount - token::transfer_fee(mint_info, amount)?;
+
+    // Deposited tokens leave the balance reflection accrues on, as staked ones do
+    if let Some(mut holder_state) =
+        load_holder_state_pda_mut(program_id, state_info.key, holder_state_info, depositor_info.key)?
+    {
+        holder_state.settle_reflection(reflection_index, balance);
+        holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_sub(amount).into();
+    }
+
+    deposit.amount += received;
+    deposit.locked_round = lottery.round;
+    LotteryDeposit::pack(deposit, &mut deposit_info.data.borrow_mut())?;
+    lottery.total_deposits += received;
+    lottery.save(lottery_info)?;
+
+    msg!("Deposited {} tokens into the lottery", received);
+    Ok(())
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_withdraw_from_lottery(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let depositor_info = next_account_info(account_info_iter)?;
+    let depositor_token_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let lottery_info = next_account_info(account_info_iter)?;
+    let deposit_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let hook_accounts = account_info_iter.as_slice();
+
+    if !depositor_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let state = load_state(program_id, state_info)?;
+    if *lottery_pool_info.key != state.lottery_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let reflection_index = state.reflection_index.get();
+    drop(state);
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+    check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let mut lottery = LotteryState::load(program_id, lottery_info)?;
+
+    if *deposit_info.key != find_lottery_deposit(program_id, state_info.key, depositor_info.key).0
+        || deposit_info.owner != program_id
+    {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let mut deposit = LotteryDeposit::unpack(&deposit_info.data.borrow())?;
+    if lottery.round <= deposit.locked_round {
+        return Err(CodoxError::DepositLocked.into());
+    }
+    if amount == 0 || amount > deposit.amount {
+        return Err(ProgramError::InsufficientFunds);
+    }
+
+    token::transfer(
+        token_program_info,
+        lottery_pool_info,
+        mint_info,
+        depositor_token_info,
+        pool_authority_info,
+        hook_accounts,
+        amount,
+        &[&[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]]],
+    )?;
+
+    // Withdrawn principal earns reflection again from here
+    if let Some(mut holder_state) =
+        load_holder_state_pda_mut(program_id, state_info.key, holder_state_info, depositor_info.key)?
+    {
+        holder_state.settle_reflection(reflection_index, u64::MAX);
+        holder_state.reflection_balance = holder_state.reflection_balance.get().saturating_add(amount).into();
+    }
+
+    deposit.amount -= amount;
+    LotteryDeposit::pack(deposit, &mut deposit_info.data.borrow_mut())?;
+    lottery.total_deposits = lottery.total_deposits.saturating_sub(amount);
+    lottery.save(lottery_info)?;
+
+    msg!("Withdrew {} tokens from the lottery", amount);
+    Ok(())
+}
+
+/// Load a round's history account, checking its address
+#[cfg(feature = "lottery")]
+fn load_lottery_round(
+    program_id: &Pubkey,
+    state: &Pubkey,
+    round_info: &AccountInfo,
+    round: u64,
+) -> Result<LotteryRound, ProgramError> {
+    if *round_info.key != find_lottery_round(program_id, state, round).0 || round_info.owner != program_id {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    LotteryRound::unpack(&round_info.data.borrow())
+}
+
+/// Check `lottery_info` is the state's lottery account
+#[cfg(feature = "lottery")]
+pub(crate) fn check_lottery_address(program_id: &Pubkey, state: &Pubkey, lottery_info: &AccountInfo) -> ProgramResult {
+    if *lottery_info.key != find_lottery_state(program_id, state).0 {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    Ok(())
+}
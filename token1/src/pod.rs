@@ -0,0 +1,92 @@
+//! Plain old data for zero-copy account layouts.
+//!
+//! Account data is only guaranteed to be byte aligned, so the state structs are
+//! built from these little-endian, alignment 1 stand-ins for the integer and
+//! `bool` fields. `bytemuck` then maps a struct directly onto the account's
+//! bytes, in the same layout the hand-written `Pack` code used to produce.
+
+use std::fmt;
+
+use bytemuck::{Pod, Zeroable};
+use solana_program::program_error::ProgramError;
+
+/// Borrow `data` as a `T` in place; the length must match exactly
+pub fn load<T: Pod>(data: &[u8]) -> Result<&T, ProgramError> {
+    bytemuck::try_from_bytes(data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// Mutably borrow `data` as a `T` in place; the length must match exactly
+pub fn load_mut<T: Pod>(data: &mut [u8]) -> Result<&mut T, ProgramError> {
+    bytemuck::try_from_bytes_mut(data).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+/// A `bool` stored as one byte; any non-zero byte reads as `true`
+#[repr(transparent)]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct PodBool(u8);
+
+impl PodBool {
+    pub const fn get(self) -> bool {
+        self.0 != 0
+    }
+}
+
+impl From<bool> for PodBool {
+    fn from(value: bool) -> Self {
+        PodBool(value as u8)
+    }
+}
+
+impl fmt::Debug for PodBool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.get().fmt(f)
+    }
+}
+
+macro_rules! pod_int {
+    ($(#[$doc:meta])* $pod:ident, $int:ty) => {
+        $(#[$doc])*
+        #[repr(transparent)]
+        #[derive(Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+        pub struct $pod([u8; std::mem::size_of::<$int>()]);
+
+        impl $pod {
+            pub const fn get(self) -> $int {
+                <$int>::from_le_bytes(self.0)
+            }
+        }
+
+        impl From<$int> for $pod {
+            fn from(value: $int) -> Self {
+                $pod(value.to_le_bytes())
+            }
+        }
+
+        impl fmt::Debug for $pod {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                self.get().fmt(f)
+            }
+        }
+    };
+}
+
+pod_int!(
+    /// A little-endian `u16`
+    PodU16,
+    u16
+);
+pod_int!(
+    /// A little-endian `u64`
+    PodU64,
+    u64
+);
+pod_int!(
+    /// A little-endian `i64`
+    PodI64,
+    i64
+);
+pod_int!(
+    /// A little-endian `u128`
+    PodU128,
+    u128
+);
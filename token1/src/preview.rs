@@ -0,0 +1,175 @@
+//! Read-only previews of rewards, taxes and lottery odds.
+//!
+//! Each preview runs the same code the corresponding instruction does and returns
+//! its result Borsh-serialized through `set_return_data`, so clients get exact
+//! figures by simulating a transaction instead of re-implementing the math.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    program::set_return_data,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+#[cfg(any(feature = "reflection", feature = "lottery"))]
+use solana_program::program_error::ProgramError;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+#[cfg(feature = "reflection")]
+use crate::load_holder_state;
+use crate::{
+    error::CodoxError,
+    load_state,
+    lottery::{self, LotteryState, MAX_PRIZE_TIERS},
+    token, TaxSplit,
+};
+
+/// Returned by `PreviewClaim`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub struct ClaimPreview {
+    pub reward: u64,       // Reflection `ClaimReflection` would pay
+    pub multiplier: u16,   // Holding time multiplier, in percent
+}
+
+/// Returned by `PreviewTransferTax`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub struct TransferTaxPreview {
+    pub net_amount: u64,   // Received by the destination
+    pub tax_amount: u64,
+    pub split: TaxSplit,   // Shares of the tax by pool
+    pub launch_buy: bool,  // Charged the sniper tax and subject to the buy cooldown
+}
+
+/// Returned by `PreviewLotteryOdds`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub struct LotteryOddsPreview {
+    pub round: u64,
+    pub tickets: u64,                          // Holder's tickets on the given pages
+    pub total_tickets: u64,
+    #[cfg_attr(feature = "wasm", wasm_bindgen(skip))]
+    pub tier_prizes: [u64; MAX_PRIZE_TIERS],   // Prizes if the round were drawn now
+    pub jackpot: u64,
+    pub jackpot_chance: u16,                   // Basis points
+}
+
+impl LotteryOddsPreview {
+    /// Odds of a holder with `tickets` in the lottery's current round, with
+    /// `pool_balance` in the lottery pool
+    pub fn new(lottery: &LotteryState, tickets: u64, pool_balance: u64) -> Self {
+        let prize_pool = lottery.prize_pool(pool_balance);
+        LotteryOddsPreview {
+            round: lottery.round,
+            tickets,
+            total_tickets: lottery.total_tickets,
+            tier_prizes: lottery.prize_tiers.map(|tier_bps| lottery::tier_prize(prize_pool, tier_bps)),
+            jackpot: lottery.jackpot,
+            jackpot_chance: lottery.jackpot_chance,
+        }
+    }
+}
+
+fn return_preview<T: BorshSerialize>(preview: &T) -> ProgramResult {
+    set_return_data(&borsh::to_vec(preview)?);
+    Ok(())
+}
+
+#[cfg(feature = "reflection")]
+pub fn process_preview_claim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let holder_token_info = next_account_info(account_info_iter)?;
+    let reflection_pool_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_info)?;
+    if *reflection_pool_info.key != state.reflection_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    let holder_token = token::unpack_account(holder_token_info)?;
+    if holder_token.mint != state.token_mint {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let holder_state = load_holder_state(program_id, holder_state_info, &holder_token.owner)?;
+
+    let (reward, multiplier) = holder_state.pending_reflection(
+        &state,
+        holder_token.amount,
+        token::unpack_account(reflection_pool_info)?.amount,
+        Clock::get()?.unix_timestamp,
+    );
+    return_preview(&ClaimPreview { reward, multiplier })
+}
+
+pub fn process_preview_transfer_tax(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+
+    let state = load_state(program_id, state_info)?;
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    let source = token::unpack_account(source_info)?;
+    let destination = token::unpack_account(destination_info)?;
+    let source_exempt = state.is_limit_exempt(source_info.key, &source.owner);
+    let destination_exempt = state.is_limit_exempt(destination_info.key, &destination.owner);
+    let launch_buy = state.check_trading_open(Clock::get()?.unix_timestamp, source_exempt, destination_exempt)?;
+
+    // Token-2022 withholds the mint's transfer fee, split when the vault is distributed
+    let (tax_amount, split) = if cfg!(feature = "token-2022") {
+        let tax_amount = token::transfer_fee(mint_info, amount)?;
+        (tax_amount, state.split_tax(tax_amount))
+    } else {
+        state.transfer_tax(amount, launch_buy)
+    };
+    #[cfg(feature = "anti-whale")]
+    state.check_launch_limits(
+        token::unpack_mint(mint_info)?.supply,
+        amount,
+        source_exempt,
+        destination_exempt,
+        destination.amount.saturating_add(amount - tax_amount),
+    )?;
+
+    return_preview(&TransferTaxPreview {
+        net_amount: amount - tax_amount,
+        tax_amount,
+        split,
+        launch_buy,
+    })
+}
+
+#[cfg(feature = "lottery")]
+pub fn process_preview_lottery_odds(program_id: &Pubkey, accounts: &[AccountInfo], holder: &Pubkey) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let lottery_info = next_account_info(account_info_iter)?;
+    let lottery_pool_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let page_infos = account_info_iter.as_slice();
+
+    let state = load_state(program_id, state_info)?;
+    if *lottery_pool_info.key != state.lottery_pool {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    lottery::check_lottery_address(program_id, state_info.key, lottery_info)?;
+    let lottery = LotteryState::load(program_id, lottery_info)?;
+
+    let mut tickets = 0u64;
+    for page_info in page_infos {
+        let page = lottery::load_ticket_page(program_id, page_info)?;
+        if page.round != lottery.round
+            || *page_info.key != lottery::find_ticket_page(program_id, state_info.key, page.round, page.page).0
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        tickets += page.holder_tickets(&page_info.data.borrow(), holder);
+    }
+
+    return_preview(&LotteryOddsPreview::new(&lottery, tickets, token::unpack_account(lottery_pool_info)?.amount))
+}
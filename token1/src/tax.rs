@@ -0,0 +1,159 @@
+//! Transfer tax math.
+//!
+//! Pure functions of the configured rates, shared by `Transfer`, the Token-2022
+//! transfer hook, `DistributeTaxVault` and the previews. Shares are rounded down
+//! and the rounding dust goes to the lottery, or in builds without the `lottery`
+//! feature to the treasury, or to the liquidity vault without `burn` either, so a
+//! split always adds up to the tax and the tax plus the net amount always adds up
+//! to the transfer.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+
+use crate::pod::PodU64;
+
+/// Highest tax rate, in basis points
+pub const MAX_TAX_RATE: u16 = 1000;
+
+/// Tax rates in basis points, from `CodoxTokenState`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub struct TaxRates {
+    pub tax_rate: u16,
+    pub reflection_rate: u16,
+    pub staking_rate: u16,
+    pub lottery_rate: u16,
+    pub liquidity_rate: u16,
+    pub treasury_rate: u16,
+    pub sniper_tax_rate: u16, // Charged on launch window buys
+}
+
+/// A tax amount split into its shares by the configured rates
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "wasm", wasm_bindgen::prelude::wasm_bindgen)]
+pub struct TaxSplit {
+    pub reflection: u64,
+    pub staking: u64,
+    pub lottery: u64,
+    pub liquidity: u64,
+    pub treasury: u64,
+}
+
+impl TaxSplit {
+    /// Sum of the shares
+    pub fn total(&self) -> u64 {
+        self.reflection + self.staking + self.lottery + self.liquidity + self.treasury
+    }
+
+    /// The share that takes the rounding dust and the sniper tax's excess: the
+    /// lottery's, or the treasury's in builds without the lottery, or the
+    /// liquidity vault's when nothing in the build would empty the treasury
+    fn remainder_mut(&mut self) -> &mut u64 {
+        if cfg!(feature = "lottery") {
+            &mut self.lottery
+        } else if cfg!(feature = "burn") {
+            &mut self.treasury
+        } else {
+            &mut self.liquidity
+        }
+    }
+
+    /// Add `other`'s shares to these
+    pub fn add(&mut self, other: &TaxSplit) {
+        self.reflection = self.reflection.saturating_add(other.reflection);
+        self.staking = self.staking.saturating_add(other.staking);
+        self.lottery = self.lottery.saturating_add(other.lottery);
+        self.liquidity = self.liquidity.saturating_add(other.liquidity);
+        self.treasury = self.treasury.saturating_add(other.treasury);
+    }
+}
+
+/// A `TaxSplit` as stored in the program state
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct PodTaxSplit {
+    pub reflection: PodU64,
+    pub staking: PodU64,
+    pub lottery: PodU64,
+    pub liquidity: PodU64,
+    pub treasury: PodU64,
+}
+
+impl PodTaxSplit {
+    pub fn get(self) -> TaxSplit {
+        TaxSplit {
+            reflection: self.reflection.get(),
+            staking: self.staking.get(),
+            lottery: self.lottery.get(),
+            liquidity: self.liquidity.get(),
+            treasury: self.treasury.get(),
+        }
+    }
+}
+
+impl From<TaxSplit> for PodTaxSplit {
+    fn from(split: TaxSplit) -> Self {
+        PodTaxSplit {
+            reflection: split.reflection.into(),
+            staking: split.staking.into(),
+            lottery: split.lottery.into(),
+            liquidity: split.liquidity.into(),
+            treasury: split.treasury.into(),
+        }
+    }
+}
+
+impl TaxRates {
+    /// Whether the tax is at most `MAX_TAX_RATE`, the shares add up to it, none
+    /// goes to a mechanism left out of this build and the sniper tax is at most 100%.
+    /// Only `BuybackAndBurn` empties the treasury vault, so its share needs `burn`.
+    pub fn is_valid(&self) -> bool {
+        let shares = self.reflection_rate as u32
+            + self.staking_rate as u32
+            + self.lottery_rate as u32
+            + self.liquidity_rate as u32
+            + self.treasury_rate as u32;
+        let built = (cfg!(feature = "reflection") || self.reflection_rate == 0)
+            && (cfg!(feature = "staking") || self.staking_rate == 0)
+            && (cfg!(feature = "lottery") || self.lottery_rate == 0)
+            && (cfg!(feature = "burn") || self.treasury_rate == 0);
+        self.tax_rate <= MAX_TAX_RATE && shares == self.tax_rate as u32 && built && self.sniper_tax_rate <= 10000
+    }
+
+    /// Split a tax amount into its shares; rounding dust goes where
+    /// `TaxSplit::remainder_mut` says
+    pub fn split(&self, tax_amount: u64) -> TaxSplit {
+        // With no shares to split by, all of it is dust
+        let mut split = TaxSplit::default();
+        if self.tax_rate != 0 {
+            let share = |rate: u16| (tax_amount as u128 * rate as u128 / self.tax_rate as u128) as u64;
+            split = TaxSplit {
+                reflection: share(self.reflection_rate),
+                staking: share(self.staking_rate),
+                lottery: share(self.lottery_rate),
+                liquidity: share(self.liquidity_rate),
+                treasury: share(self.treasury_rate),
+            };
+        }
+        *split.remainder_mut() += tax_amount - split.total();
+        split
+    }
+
+    /// Tax on a transfer of `amount` and its split; on a launch window buy the
+    /// sniper tax applies, its excess over the normal tax going where the dust does
+    pub fn transfer_tax(&self, amount: u64, launch_buy: bool) -> (u64, TaxSplit) {
+        let mut tax_amount = bps_of(amount, self.tax_rate);
+        let mut split = self.split(tax_amount);
+        if launch_buy {
+            let sniper_tax = bps_of(amount, self.sniper_tax_rate);
+            *split.remainder_mut() += sniper_tax.saturating_sub(tax_amount);
+            tax_amount = tax_amount.max(sniper_tax);
+        }
+        (tax_amount, split)
+    }
+}
+
+/// `bps` basis points of `amount`, rounded down
+pub fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / 10000) as u64
+}
@@ -0,0 +1,181 @@
+//! Token program access for the build mode: SPL Token by default, Token-2022
+//! with the `token-2022` feature.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[cfg(not(feature = "token-2022"))]
+pub use spl_token::{
+    id,
+    state::{Account, Mint},
+};
+#[cfg(feature = "token-2022")]
+pub use spl_token_2022::{
+    id,
+    state::{Account, Mint},
+};
+
+#[cfg(not(feature = "token-2022"))]
+use solana_program::program_pack::Pack;
+#[cfg(feature = "token-2022")]
+use spl_token_2022::extension::StateWithExtensions;
+
+/// Unpack a token account, ignoring any Token-2022 extensions
+pub fn unpack_account(account_info: &AccountInfo) -> Result<Account, ProgramError> {
+    if *account_info.owner != id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account_info.data.borrow();
+    #[cfg(not(feature = "token-2022"))]
+    return Account::unpack(&data);
+    #[cfg(feature = "token-2022")]
+    return StateWithExtensions::<Account>::unpack(&data).map(|account| account.base);
+}
+
+/// Unpack a mint, ignoring any Token-2022 extensions
+pub fn unpack_mint(mint_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    if *mint_info.owner != id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = mint_info.data.borrow();
+    #[cfg(not(feature = "token-2022"))]
+    return Mint::unpack(&data);
+    #[cfg(feature = "token-2022")]
+    return StateWithExtensions::<Mint>::unpack(&data).map(|mint| mint.base);
+}
+
+/// Fee the token program withholds from a transfer of `amount`
+pub fn transfer_fee(mint_info: &AccountInfo, amount: u64) -> Result<u64, ProgramError> {
+    #[cfg(not(feature = "token-2022"))]
+    {
+        let _ = (mint_info, amount);
+        Ok(0)
+    }
+    #[cfg(feature = "token-2022")]
+    {
+        use solana_program::{clock::Clock, sysvar::Sysvar};
+        use spl_token_2022::extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions};
+
+        let data = mint_info.data.borrow();
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(fee_config) => fee_config
+                .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                .ok_or(ProgramError::ArithmeticOverflow),
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+/// Check a pool or vault holds `mint` and can only be moved by the pool authority
+pub fn check_pool(pool_info: &AccountInfo, mint: &Pubkey, pool_authority: &Pubkey) -> ProgramResult {
+    let pool = unpack_account(pool_info)?;
+    if pool.mint != *mint || pool.owner != *pool_authority || pool.delegate.is_some() || pool.close_authority.is_some() {
+        return Err(crate::error::CodoxError::InvalidPoolAccount.into());
+    }
+    Ok(())
+}
+
+/// `TransferChecked` through the token program, signed with `seeds` when the
+/// authority is a PDA. In Token-2022 mode `additional_accounts` carries the
+/// transfer hook's accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn transfer<'a>(
+    token_program_info: &AccountInfo<'a>,
+    source_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    additional_accounts: &[AccountInfo<'a>],
+    amount: u64,
+    seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let decimals = unpack_mint(mint_info)?.decimals;
+
+    #[cfg(not(feature = "token-2022"))]
+    {
+        let _ = additional_accounts;
+        let transfer_instruction = spl_token::instruction::transfer_checked(
+            token_program_info.key,
+            source_info.key,
+            mint_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+            decimals,
+        )?;
+        solana_program::program::invoke_signed(
+            &transfer_instruction,
+            &[
+                source_info.clone(),
+                mint_info.clone(),
+                destination_info.clone(),
+                authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            seeds,
+        )
+    }
+    #[cfg(feature = "token-2022")]
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program_info.key,
+        source_info.clone(),
+        mint_info.clone(),
+        destination_info.clone(),
+        authority_info.clone(),
+        additional_accounts,
+        amount,
+        decimals,
+        seeds,
+    )
+}
+
+/// `BurnChecked` through the token program, signed with `seeds` when the
+/// authority is a PDA
+pub fn burn<'a>(
+    token_program_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    amount: u64,
+    seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    let decimals = unpack_mint(mint_info)?.decimals;
+
+    #[cfg(not(feature = "token-2022"))]
+    let burn_instruction = spl_token::instruction::burn_checked(
+        token_program_info.key,
+        account_info.key,
+        mint_info.key,
+        authority_info.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+    #[cfg(feature = "token-2022")]
+    let burn_instruction = spl_token_2022::instruction::burn_checked(
+        token_program_info.key,
+        account_info.key,
+        mint_info.key,
+        authority_info.key,
+        &[],
+        amount,
+        decimals,
+    )?;
+
+    solana_program::program::invoke_signed(
+        &burn_instruction,
+        &[
+            account_info.clone(),
+            mint_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        seeds,
+    )
+}
@@ -0,0 +1,412 @@
+//! Token-2022 transfer hook mode.
+//!
+//! The tax is withheld by the mint's transfer-fee extension, and Token-2022 calls the
+//! mint's transfer hook on every transfer, so wallet and DEX transfers can't skip the
+//! Codox bookkeeping the way a plain SPL Token `transfer` skips `Transfer`.
+//!
+//! The hook is a second deployment of this crate built with the `transfer-hook`
+//! feature. It can't be the Codox program itself: payouts from the pools are
+//! Token-2022 transfers made by the Codox program, and the runtime rejects the
+//! hook calling back into a program that is already on the stack. The hook
+//! forwards user transfers to the Codox program's `Execute` handler, where the
+//! bookkeeping runs against the accounts it owns, and skips transfers out of the
+//! Codox pools, which the Codox program accounts for itself.
+
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_option::COption,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_token_2022::{
+    extension::{
+        transfer_fee::{
+            instruction::{withdraw_withheld_tokens_from_accounts, withdraw_withheld_tokens_from_mint},
+            TransferFeeConfig,
+        },
+        transfer_hook::{self, TransferHookAccount},
+        BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account, Mint},
+};
+use spl_transfer_hook_interface::{
+    collect_extra_account_metas_signer_seeds, get_extra_account_metas_address_and_bump_seed,
+    instruction::{ExecuteInstruction, TransferHookInstruction},
+};
+use borsh::BorshDeserialize;
+
+use crate::{
+    check_pool_authority, create_pda_account, error::CodoxError, events::CodoxEvent, load_holder_state_pda_mut, load_state,
+    load_state_mut, token, CodoxTokenInstruction, HOLDER_SEED, POOL_AUTHORITY_SEED,
+};
+
+/// Extra accounts Token-2022 passes to the hook after the validation account:
+/// the Codox program, the program state, the recipient's holder state and the
+/// sender's holder state
+pub fn extra_account_metas(codox_program: &Pubkey, state: &Pubkey) -> Result<Vec<ExtraAccountMeta>, ProgramError> {
+    Ok(vec![
+        ExtraAccountMeta::new_with_pubkey(codox_program, false, false)?,
+        ExtraAccountMeta::new_with_pubkey(state, false, true)?,
+        // Holder state PDA of the destination token account's owner
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[
+                Seed::Literal { bytes: HOLDER_SEED.to_vec() },
+                Seed::AccountKey { index: 6 },
+                Seed::AccountData { account_index: 2, data_index: 32, length: 32 },
+            ],
+            false,
+            true,
+        )?,
+        // Holder state PDA of the source token account's owner
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[
+                Seed::Literal { bytes: HOLDER_SEED.to_vec() },
+                Seed::AccountKey { index: 6 },
+                Seed::AccountData { account_index: 0, data_index: 32, length: 32 },
+            ],
+            false,
+            true,
+        )?,
+    ])
+}
+
+/// Check the mint withholds `tax_rate` on every transfer, lets the pool authority
+/// withdraw the withheld fees, and has a separate hook program
+pub fn check_mint(program_id: &Pubkey, mint_info: &AccountInfo, tax_rate: u16, pool_authority: &Pubkey) -> ProgramResult {
+    if *mint_info.owner != spl_token_2022::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let mint_data = mint_info.data.borrow();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+
+    let fee_config = mint.get_extension::<TransferFeeConfig>()?;
+    let fee = fee_config.get_epoch_fee(Clock::get()?.epoch);
+    // A capped fee would let large transfers pay less than the configured rate
+    if u16::from(fee.transfer_fee_basis_points) != tax_rate || u64::from(fee.maximum_fee) != u64::MAX {
+        return Err(CodoxError::TransferFeeMismatch.into());
+    }
+    if Option::<Pubkey>::from(fee_config.withdraw_withheld_authority) != Some(*pool_authority) {
+        return Err(CodoxError::InvalidWithdrawAuthority.into());
+    }
+
+    match transfer_hook::get_program_id(&mint) {
+        Some(hook_program) if hook_program != *program_id => Ok(()),
+        _ => Err(CodoxError::InvalidTransferHook.into()),
+    }
+}
+
+/// Entrypoint of the `transfer-hook` deployment
+pub fn process_hook_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    if let Ok(TransferHookInstruction::Execute { amount }) = TransferHookInstruction::unpack(instruction_data) {
+        msg!("Instruction: Execute");
+        return process_forward_execute(program_id, accounts, amount);
+    }
+
+    match CodoxTokenInstruction::try_from_slice(instruction_data)? {
+        CodoxTokenInstruction::InitializeTransferHook => {
+            msg!("Instruction: InitializeTransferHook");
+            process_initialize_transfer_hook(program_id, accounts)
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn process_initialize_transfer_hook(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let authority_info = next_account_info(account_info_iter)?;
+    let extra_metas_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let codox_program_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    if !authority_info.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    {
+        let mint_data = mint_info.data.borrow();
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        if transfer_hook::get_program_id(&mint) != Some(*program_id) {
+            return Err(CodoxError::InvalidTransferHook.into());
+        }
+        // Whoever registers the hook decides which Codox state every transfer reports to
+        if mint.base.mint_authority != COption::Some(*authority_info.key) {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+    }
+
+    let state = load_state(codox_program_info.key, state_info)?;
+    if state.authority != *authority_info.key {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if state.token_mint != *mint_info.key {
+        return Err(CodoxError::InvalidMint.into());
+    }
+
+    let (extra_metas_address, bump) = get_extra_account_metas_address_and_bump_seed(mint_info.key, program_id);
+    if *extra_metas_info.key != extra_metas_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+
+    let extra_metas = extra_account_metas(codox_program_info.key, state_info.key)?;
+    let account_size = ExtraAccountMetaList::size_of(extra_metas.len())?;
+    let bump_seed = [bump];
+    create_pda_account(
+        authority_info,
+        extra_metas_info,
+        account_size,
+        program_id,
+        system_program_info,
+        &collect_extra_account_metas_signer_seeds(mint_info.key, &bump_seed),
+    )?;
+
+    ExtraAccountMetaList::init::<ExecuteInstruction>(&mut extra_metas_info.data.borrow_mut(), &extra_metas)?;
+
+    msg!("Transfer hook initialized for mint {}", mint_info.key);
+    Ok(())
+}
+
+/// Hook `Execute`, called by Token-2022
+/// Accounts expected:
+/// 0. [] Source token account
+/// 1. [] Token mint
+/// 2. [] Destination token account
+/// 3. [] Source token account owner
+/// 4. [] Extra account metas account
+/// 5. [] Codox program
+/// 6. [writable] Program state account
+/// 7. [writable] Destination owner's holder state account
+/// 8. [writable] Source owner's holder state account
+fn process_forward_execute(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let owner_info = next_account_info(account_info_iter)?;
+    let extra_metas_info = next_account_info(account_info_iter)?;
+    let codox_program_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    let source_holder_state_info = next_account_info(account_info_iter)?;
+
+    let (extra_metas_address, _) = get_extra_account_metas_address_and_bump_seed(mint_info.key, program_id);
+    if *extra_metas_info.key != extra_metas_address {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    ExtraAccountMetaList::check_account_infos::<ExecuteInstruction>(
+        accounts,
+        &TransferHookInstruction::Execute { amount }.pack(),
+        program_id,
+        &extra_metas_info.data.borrow(),
+    )?;
+
+    // Transfers in and out of the pools come from the Codox program, which is already on the stack
+    let state = load_state(codox_program_info.key, state_info)?;
+    if state.is_pool(source_info.key) || state.is_pool(destination_info.key) {
+        return Ok(());
+    }
+
+    invoke(
+        &Instruction {
+            program_id: *codox_program_info.key,
+            accounts: vec![
+                AccountMeta::new_readonly(*source_info.key, false),
+                AccountMeta::new_readonly(*mint_info.key, false),
+                AccountMeta::new_readonly(*destination_info.key, false),
+                AccountMeta::new_readonly(*owner_info.key, false),
+                AccountMeta::new(*state_info.key, false),
+                AccountMeta::new(*holder_state_info.key, false),
+                AccountMeta::new(*source_holder_state_info.key, false),
+            ],
+            data: TransferHookInstruction::Execute { amount }.pack(),
+        },
+        &[
+            source_info.clone(),
+            mint_info.clone(),
+            destination_info.clone(),
+            owner_info.clone(),
+            state_info.clone(),
+            holder_state_info.clone(),
+            source_holder_state_info.clone(),
+            codox_program_info.clone(),
+        ],
+    )
+}
+
+/// Codox-side `Execute`, forwarded by the hook program
+/// Accounts expected:
+/// 0. [] Source token account
+/// 1. [] Token mint
+/// 2. [] Destination token account
+/// 3. [] Source token account owner
+/// 4. [writable] Program state account
+/// 5. [writable] Destination owner's holder state account
+/// 6. [writable] Source owner's holder state account
+///
+/// The sniper tax needs the Codox `Transfer`; here the launch window only applies
+/// the buy cooldown, as the mint's transfer fee can't change per transfer. Every
+/// transfer settles the reflection of registered holders on both sides and
+/// records what the recipient received during the lottery round.
+pub fn process_execute(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_info = next_account_info(account_info_iter)?;
+    let mint_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let _owner_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let holder_state_info = next_account_info(account_info_iter)?;
+    #[cfg_attr(not(feature = "reflection"), allow(unused_variables))]
+    let source_holder_state_info = next_account_info(account_info_iter)?;
+
+    let mut state = load_state_mut(program_id, state_info)?;
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    state.check_not_paused(source_info.key)?;
+
+    // Token-2022 only sets the flag while it is calling the hook, so this rejects
+    // anyone invoking `Execute` directly to skew the bookkeeping
+    let (source_owner, source_balance) = {
+        let source_data = source_info.data.borrow();
+        let source = StateWithExtensions::<Account>::unpack(&source_data)?;
+        if source.base.mint != state.token_mint
+            || !bool::from(source.get_extension::<TransferHookAccount>()?.transferring)
+        {
+            return Err(CodoxError::NotTransferring.into());
+        }
+        (source.base.owner, source.base.amount)
+    };
+
+    // The destination has already been credited, so its balance is the post-transfer one
+    let destination = token::unpack_account(destination_info)?;
+    let source_exempt = state.is_limit_exempt(source_info.key, &source_owner);
+    let destination_exempt = state.is_limit_exempt(destination_info.key, &destination.owner);
+    let now = Clock::get()?.unix_timestamp;
+    let launch_buy = state.check_trading_open(now, source_exempt, destination_exempt)?;
+    if launch_buy {
+        load_holder_state_pda_mut(program_id, state_info.key, holder_state_info, &destination.owner)?
+            .ok_or(ProgramError::UninitializedAccount)?
+            .record_buy(now, state.buy_cooldown.get())?;
+    }
+    #[cfg(feature = "anti-whale")]
+    state.check_launch_limits(
+        token::unpack_mint(mint_info)?.supply,
+        amount,
+        source_exempt,
+        destination_exempt,
+        destination.amount,
+    )?;
+
+    let tax_amount = token::transfer_fee(mint_info, amount)?;
+
+    // The source has already been debited, so add the amount back for its balance before the transfer
+    #[cfg(feature = "reflection")]
+    crate::settle_transfer_reflection(
+        program_id,
+        state_info.key,
+        state.reflection_index.get(),
+        source_holder_state_info,
+        &source_owner,
+        source_balance.saturating_add(amount),
+        holder_state_info,
+        &destination.owner,
+        amount,
+        amount - tax_amount,
+    )?;
+    #[cfg(feature = "lottery")]
+    crate::record_lottery_receipt(
+        program_id,
+        state_info.key,
+        state.last_lottery_draw.get(),
+        holder_state_info,
+        &destination.owner,
+        amount - tax_amount,
+    )?;
+
+    state.total_tax_collected = state.total_tax_collected.get().saturating_add(tax_amount).into();
+    CodoxEvent::Transfer {
+        source: *source_info.key,
+        destination: *destination_info.key,
+        amount,
+        net_amount: amount - tax_amount,
+        tax_amount,
+        split: state.split_tax(tax_amount),
+        launch_buy,
+    }
+    .emit();
+    Ok(())
+}
+
+pub fn process_harvest_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let mint_info = next_account_info(account_info_iter)?;
+    let tax_vault_info = next_account_info(account_info_iter)?;
+    let state_info = next_account_info(account_info_iter)?;
+    let pool_authority_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let source_infos = account_info_iter.as_slice();
+
+    let state = load_state(program_id, state_info)?;
+    if *mint_info.key != state.token_mint {
+        return Err(CodoxError::InvalidMint.into());
+    }
+    if *tax_vault_info.key != state.tax_vault {
+        return Err(CodoxError::InvalidPoolAccount.into());
+    }
+    if *token_program_info.key != spl_token_2022::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let bump = check_pool_authority(program_id, state_info.key, pool_authority_info)?;
+    let signer_seeds: &[&[u8]] = &[POOL_AUTHORITY_SEED, state_info.key.as_ref(), &[bump]];
+
+    if !source_infos.is_empty() {
+        let sources: Vec<&Pubkey> = source_infos.iter().map(|source_info| source_info.key).collect();
+        let withdraw_instruction = withdraw_withheld_tokens_from_accounts(
+            token_program_info.key,
+            mint_info.key,
+            tax_vault_info.key,
+            pool_authority_info.key,
+            &[],
+            &sources,
+        )?;
+        let mut withdraw_accounts = vec![mint_info.clone(), tax_vault_info.clone(), pool_authority_info.clone()];
+        withdraw_accounts.extend_from_slice(source_infos);
+        invoke_signed(&withdraw_instruction, &withdraw_accounts, &[signer_seeds])?;
+    }
+
+    // Anyone can harvest withheld fees into the mint, so sweep those too
+    let mint_withheld = {
+        let mint_data = mint_info.data.borrow();
+        let mint = StateWithExtensions::<Mint>::unpack(&mint_data)?;
+        u64::from(mint.get_extension::<TransferFeeConfig>()?.withheld_amount)
+    };
+    if mint_withheld > 0 {
+        invoke_signed(
+            &withdraw_withheld_tokens_from_mint(
+                token_program_info.key,
+                mint_info.key,
+                tax_vault_info.key,
+                pool_authority_info.key,
+                &[],
+            )?,
+            &[mint_info.clone(), tax_vault_info.clone(), pool_authority_info.clone()],
+            &[signer_seeds],
+        )?;
+    }
+
+    msg!("Harvested withheld fees from {} accounts and {} from the mint", source_infos.len(), mint_withheld);
+    Ok(())
+}
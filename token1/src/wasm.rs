@@ -0,0 +1,290 @@
+//! WebAssembly bindings for web clients.
+//!
+//! Exports the program's own tax, reflection, staking and lottery math through
+//! `wasm-bindgen`, along with decoding of the state accounts from their raw
+//! bytes, so a web app computes exactly what the program does. `u64` and `u128`
+//! amounts are `BigInt`s in JavaScript, public keys are base58 strings and
+//! struct fields keep their Rust names, as in the IDL. Built with the `wasm`
+//! feature:
+//!
+//! ```bash
+//! wasm-pack build --target web -- --features wasm
+//! ```
+
+use std::str::FromStr;
+
+use borsh::BorshDeserialize;
+use solana_program::{program_error::ProgramError, program_pack::Pack, pubkey::Pubkey};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    lottery::{LotteryState, TicketPage},
+    pod::{PodBool, PodI64, PodU128, PodU16, PodU64},
+    preview::{ClaimPreview, LotteryOddsPreview, TransferTaxPreview},
+    CodoxTokenState, HolderState, PodTaxSplit, TaxRates, TaxSplit, MAX_LIMIT_EXEMPT,
+};
+
+fn js_error(error: ProgramError) -> JsError {
+    JsError::new(&error.to_string())
+}
+
+#[wasm_bindgen]
+impl TaxRates {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        tax_rate: u16,
+        reflection_rate: u16,
+        staking_rate: u16,
+        lottery_rate: u16,
+        liquidity_rate: u16,
+        treasury_rate: u16,
+        sniper_tax_rate: u16,
+    ) -> TaxRates {
+        TaxRates {
+            tax_rate,
+            reflection_rate,
+            staking_rate,
+            lottery_rate,
+            liquidity_rate,
+            treasury_rate,
+            sniper_tax_rate,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl LotteryOddsPreview {
+    /// Prizes of the tiers if the round were drawn now
+    #[wasm_bindgen(getter)]
+    pub fn tier_prizes(&self) -> Vec<u64> {
+        self.tier_prizes.to_vec()
+    }
+}
+
+/// Split a tax amount into its shares; see `TaxRates::split`
+#[wasm_bindgen(js_name = taxSplit)]
+pub fn tax_split(rates: &TaxRates, tax_amount: u64) -> TaxSplit {
+    rates.split(tax_amount)
+}
+
+/// Tax on a `Transfer` of `amount` in SPL Token mode, as `PreviewTransferTax` returns it
+#[wasm_bindgen(js_name = transferTax)]
+pub fn transfer_tax(rates: &TaxRates, amount: u64, launch_buy: bool) -> TransferTaxPreview {
+    let (tax_amount, split) = rates.transfer_tax(amount, launch_buy);
+    TransferTaxPreview {
+        net_amount: amount - tax_amount,
+        tax_amount,
+        split,
+        launch_buy,
+    }
+}
+
+/// Staking share of the tax on a `Transfer` of `amount`, which goes to the staking
+/// pool. `Unstake` returns principal only; nothing pays the pool out to stakers.
+#[wasm_bindgen(js_name = stakingTaxShare)]
+pub fn staking_tax_share(rates: &TaxRates, amount: u64, launch_buy: bool) -> u64 {
+    rates.transfer_tax(amount, launch_buy).1.staking
+}
+
+/// Reflection index increase from spreading `amount` over `supply`; see `reflection_accrual`.
+/// Distributing reflection spreads it less the bonus share.
+#[wasm_bindgen(js_name = reflectionAccrual)]
+pub fn reflection_accrual(amount: u64, supply: u64) -> u128 {
+    crate::reflection_accrual(amount, supply)
+}
+
+/// Part of distributed reflection set aside for the holding time multiplier; see `reflection_bonus_share`
+#[wasm_bindgen(js_name = reflectionBonusShare)]
+pub fn reflection_bonus_share(amount: u64) -> u64 {
+    crate::reflection_bonus_share(amount)
+}
+
+/// Reflection owed on `balance` for an index increase; see `reflection_reward`
+#[wasm_bindgen(js_name = reflectionReward)]
+pub fn reflection_reward(balance: u64, accrued_index: u128, multiplier: u16) -> u64 {
+    crate::reflection_reward(balance, accrued_index, multiplier)
+}
+
+/// Reflection multiplier in percent after holding for `holding_time` seconds
+#[wasm_bindgen(js_name = timeMultiplier)]
+pub fn time_multiplier(holding_time: i64) -> u16 {
+    crate::time_multiplier(holding_time)
+}
+
+/// Reflection `ClaimReflection` would pay at `now`, as `PreviewClaim` returns it
+#[wasm_bindgen(js_name = pendingReflection)]
+pub fn pending_reflection(
+    state: &JsCodoxTokenState,
+    holder_state: &JsHolderState,
+    balance: u64,
+    pool_balance: u64,
+    now: i64,
+) -> ClaimPreview {
+    let (reward, multiplier) = holder_state.0.pending_reflection(&state.0, balance, pool_balance, now);
+    ClaimPreview { reward, multiplier }
+}
+
+/// Odds of a holder with `tickets` in the current round of the lottery state
+/// account `lottery_data`, as `PreviewLotteryOdds` returns them
+#[wasm_bindgen(js_name = lotteryOdds)]
+pub fn lottery_odds(lottery_data: &[u8], tickets: u64, pool_balance: u64) -> Result<LotteryOddsPreview, JsError> {
+    let lottery = LotteryState::deserialize(&mut &lottery_data[..]).map_err(|_| js_error(ProgramError::InvalidAccountData))?;
+    if !lottery.is_initialized {
+        return Err(js_error(ProgramError::UninitializedAccount));
+    }
+    Ok(LotteryOddsPreview::new(&lottery, tickets, pool_balance))
+}
+
+/// Tickets `holder` has on the ticket page account `page_data`
+#[wasm_bindgen(js_name = holderTickets)]
+pub fn holder_tickets(page_data: &[u8], holder: &str) -> Result<u64, JsError> {
+    let holder = Pubkey::from_str(holder).map_err(|_| js_error(ProgramError::InvalidArgument))?;
+    let header = page_data.get(..TicketPage::LEN).ok_or_else(|| js_error(ProgramError::InvalidAccountData))?;
+    let page = TicketPage::unpack(header).map_err(js_error)?;
+    Ok(page.holder_tickets(page_data, &holder))
+}
+
+/// A state field as JavaScript sees it
+trait ToJs<T> {
+    fn to_js(&self) -> T;
+}
+
+impl ToJs<bool> for PodBool {
+    fn to_js(&self) -> bool {
+        self.get()
+    }
+}
+
+macro_rules! pod_to_js {
+    ($($pod:ty => $int:ty),*) => {
+        $(
+            impl ToJs<$int> for $pod {
+                fn to_js(&self) -> $int {
+                    self.get()
+                }
+            }
+        )*
+    };
+}
+
+pod_to_js!(PodU16 => u16, PodU64 => u64, PodI64 => i64, PodU128 => u128);
+
+impl ToJs<String> for Pubkey {
+    fn to_js(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ToJs<Vec<String>> for [Pubkey; MAX_LIMIT_EXEMPT] {
+    fn to_js(&self) -> Vec<String> {
+        self.iter().map(Pubkey::to_string).collect()
+    }
+}
+
+impl ToJs<TaxSplit> for PodTaxSplit {
+    fn to_js(&self) -> TaxSplit {
+        self.get()
+    }
+}
+
+/// Read-only JavaScript properties for the fields of the wrapped state
+macro_rules! getters {
+    ($wrapper:ident, $js_class:ident { $($field:ident: $ty:ty),* $(,)? }) => {
+        #[wasm_bindgen(js_class = $js_class)]
+        impl $wrapper {
+            $(
+                #[wasm_bindgen(getter)]
+                pub fn $field(&self) -> $ty {
+                    self.0.$field.to_js()
+                }
+            )*
+        }
+    };
+}
+
+/// Program state decoded from its account
+#[wasm_bindgen(js_name = CodoxTokenState)]
+pub struct JsCodoxTokenState(CodoxTokenState);
+
+#[wasm_bindgen(js_class = CodoxTokenState)]
+impl JsCodoxTokenState {
+    /// Decode the program state account's data
+    pub fn decode(data: &[u8]) -> Result<JsCodoxTokenState, JsError> {
+        CodoxTokenState::unpack(data).map(JsCodoxTokenState).map_err(js_error)
+    }
+
+    /// The configured tax rates
+    #[wasm_bindgen(js_name = taxRates)]
+    pub fn tax_rates(&self) -> TaxRates {
+        self.0.tax_rates()
+    }
+}
+
+getters!(JsCodoxTokenState, CodoxTokenState {
+    authority: String,
+    token_mint: String,
+    tax_vault: String,
+    reflection_pool: String,
+    staking_pool: String,
+    lottery_pool: String,
+    tax_rate: u16,
+    reflection_rate: u16,
+    staking_rate: u16,
+    lottery_rate: u16,
+    total_staked: u64,
+    total_reflection_distributed: u64,
+    last_lottery_draw: i64,
+    lottery_interval: i64,
+    total_tax_collected: u64,
+    reflection_index: u128,
+    max_transfer_bps: u16,
+    max_wallet_bps: u16,
+    limit_exempt: Vec<String>,
+    trading_enabled_at: i64,
+    launch_window: i64,
+    buy_cooldown: i64,
+    sniper_tax_rate: u16,
+    liquidity_rate: u16,
+    liquidity_vault: String,
+    amm_program: String,
+    amm_pool: String,
+    liquify_threshold: u64,
+    total_liquified: u64,
+    treasury_rate: u16,
+    treasury_vault: String,
+    treasury_quote_vault: String,
+    keeper: String,
+    buyback_max_per_call: u64,
+    total_burned: u64,
+    paused: bool,
+    undistributed: TaxSplit,
+    reflection_bonus: u64,
+    liquify_max_slippage_bps: u16,
+});
+
+/// A holder's state decoded from its account
+#[wasm_bindgen(js_name = HolderState)]
+pub struct JsHolderState(HolderState);
+
+#[wasm_bindgen(js_class = HolderState)]
+impl JsHolderState {
+    /// Decode a holder state account's data
+    pub fn decode(data: &[u8]) -> Result<JsHolderState, JsError> {
+        HolderState::unpack(data).map(JsHolderState).map_err(js_error)
+    }
+}
+
+getters!(JsHolderState, HolderState {
+    holder: String,
+    last_reflection_claim: i64,
+    staked_amount: u64,
+    stake_time: i64,
+    total_claimed: u64,
+    holding_multiplier: u16,
+    reflection_index_checkpoint: u128,
+    last_buy_time: i64,
+    reflection_balance: u64,
+    unpaid_reflection: u128,
+    lottery_round_start: i64,
+    lottery_received: u64,
+});
@@ -0,0 +1,146 @@
+//! Swap-and-liquify and buyback-and-burn against the mock constant-product AMM.
+#![cfg(not(feature = "token-2022"))]
+
+mod common;
+
+use codox::{error::CodoxError, instruction, ConfigUpdate};
+use common::{mock_amm::MockAmm, *};
+use solana_sdk::{instruction::InstructionError, pubkey::Pubkey};
+
+const RESERVE: u64 = 100_000_000;
+
+/// A deployment trading against a mock pool of `RESERVE` CODOX and `RESERVE` quote tokens
+async fn start_with_amm() -> (TestEnv, MockAmm) {
+    let mut env = TestEnv::start(Options::default()).await;
+    let amm = env.create_amm(RESERVE, RESERVE).await;
+    env.update_config(ConfigUpdate {
+        amm_program: Some(amm.program_id),
+        amm_pool: Some(amm.pool),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    (env, amm)
+}
+
+/// Constant-product output of the mock pool
+fn amount_out(reserve_in: u64, reserve_out: u64, amount_in: u64) -> u64 {
+    (reserve_out as u128 * amount_in as u128 / (reserve_in as u128 + amount_in as u128)) as u64
+}
+
+#[tokio::test]
+async fn swap_and_liquify() {
+    let (mut env, amm) = start_with_amm().await;
+    let pool_authority = env.codox.pool_authority();
+    let quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+    let lp_vault = env.create_token_account(&amm.pool_mint, &pool_authority).await;
+    let (mint, liquidity_vault) = (env.codox.mint, env.codox.liquidity_vault);
+    env.mint_to(&mint, &liquidity_vault, 2_000_000).await;
+    env.update_config(ConfigUpdate {
+        liquify_threshold: Some(1_000_000),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+
+    let result = env
+        .update_config(ConfigUpdate {
+            liquify_max_slippage_bps: Some(10_001),
+            ..ConfigUpdate::default()
+        })
+        .await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+
+    // Only the authority or keeper can trigger the swap
+    let quote_out = amount_out(RESERVE, RESERVE, 1_000_000);
+    let outsider = env.new_holder(0).await;
+    let liquify =
+        instruction::swap_and_liquify(&env.codox, &outsider.pubkey(), &amm.accounts(), &quote_vault, &lp_vault, quote_out);
+    assert_instruction_error(env.process(&[liquify], &[&outsider.wallet]).await, InstructionError::MissingRequiredSignature);
+
+    // A minimum looser than the default 1% below the pool's quote is rejected
+    let payer = env.payer();
+    let floor = quote_out * 9900 / 10000;
+    let liquify = instruction::swap_and_liquify(&env.codox, &payer, &amm.accounts(), &quote_vault, &lp_vault, floor - 1);
+    assert_codox_error(env.process(&[liquify], &[]).await, CodoxError::SlippageExceeded);
+
+    // Half is sold for quote tokens, then both go in at the post-swap price
+    let liquify = instruction::swap_and_liquify(&env.codox, &payer, &amm.accounts(), &quote_vault, &lp_vault, floor);
+    env.process(&[liquify], &[]).await.unwrap();
+
+    let liquidity_left = env.balance(&liquidity_vault).await;
+    let lp_tokens = env.balance(&lp_vault).await;
+    assert!(lp_tokens > 0);
+    assert!(liquidity_left < 2_000_000 - 1_000_000);
+    assert_eq!(env.balance(&amm.codox_reserve).await, RESERVE + 2_000_000 - liquidity_left);
+    assert_eq!(
+        env.balance(&amm.quote_reserve).await,
+        RESERVE - quote_out + (quote_out - env.balance(&quote_vault).await)
+    );
+    assert_eq!(env.state().await.total_liquified.get(), 2_000_000 - liquidity_left);
+}
+
+#[cfg(feature = "burn")]
+#[tokio::test]
+async fn buyback_and_burn() {
+    let (mut env, amm) = start_with_amm().await;
+    let keeper = env.new_holder(0).await;
+    let pool_authority = env.codox.pool_authority();
+    let treasury_quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+    env.mint_to(&amm.quote_mint, &treasury_quote_vault, 5_000_000).await;
+    env.update_config(ConfigUpdate {
+        treasury_quote_vault: Some(treasury_quote_vault),
+        keeper: Some(keeper.pubkey()),
+        buyback_max_per_call: Some(1_000_000),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    let supply = env.supply().await;
+
+    // The keeper may trigger buybacks; what's bought is burned from the treasury vault
+    let bought = amount_out(RESERVE, RESERVE, 1_000_000);
+    let buyback = instruction::buyback_and_burn(
+        &env.codox,
+        &keeper.pubkey(),
+        &amm.accounts(),
+        &treasury_quote_vault,
+        1_000_000,
+        bought,
+    );
+    env.process(&[buyback], &[&keeper.wallet]).await.unwrap();
+    assert_eq!(env.balance(&treasury_quote_vault).await, 4_000_000);
+    assert_eq!(env.balance(&amm.quote_reserve).await, RESERVE + 1_000_000);
+    assert_eq!(env.balance(&amm.codox_reserve).await, RESERVE - bought);
+    assert_eq!(env.pool_balances().await[4], 0);
+    assert_eq!(env.supply().await, supply - bought);
+    assert_eq!(env.state().await.total_burned.get(), bought);
+
+    // The mock ignores the minimum, so the program's own check has to catch it
+    let payer = env.payer();
+    let expected = amount_out(RESERVE + 1_000_000, RESERVE - bought, 1_000_000);
+    let buyback =
+        instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1_000_000, expected + 1);
+    assert_codox_error(env.process(&[buyback], &[]).await, CodoxError::SlippageExceeded);
+
+    let buyback =
+        instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1_000_001, expected);
+    assert_codox_error(env.process(&[buyback], &[]).await, CodoxError::BuybackLimitExceeded);
+}
+
+#[tokio::test]
+async fn amm_must_match_config() {
+    let (mut env, mut amm) = start_with_amm().await;
+    let pool_authority = env.codox.pool_authority();
+    let quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+    let lp_vault = env.create_token_account(&amm.pool_mint, &pool_authority).await;
+
+    // Empty liquidity vault
+    let payer = env.payer();
+    let liquify = instruction::swap_and_liquify(&env.codox, &payer, &amm.accounts(), &quote_vault, &lp_vault, 1);
+    assert_codox_error(env.process(&[liquify], &[]).await, CodoxError::LiquifyThresholdNotReached);
+
+    amm.pool = Pubkey::new_unique();
+    let liquify = instruction::swap_and_liquify(&env.codox, &payer, &amm.accounts(), &quote_vault, &lp_vault, 1);
+    assert_codox_error(env.process(&[liquify], &[]).await, CodoxError::InvalidAmm);
+}
@@ -0,0 +1,221 @@
+//! `codox-cli` argument parsing and the transactions `init --print` signs. Even
+//! `--print` asks the RPC node for a blockhash and rent, so a stub node answers those.
+#![cfg(feature = "cli")]
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use codox::{
+    instruction::{self, CodoxAccounts},
+    CodoxTokenState,
+};
+use solana_sdk::{
+    hash::Hash,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signer},
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::Transaction,
+};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    path::PathBuf,
+    process::{Command, Output},
+    thread,
+};
+
+const RENT: u64 = 2_000_000;
+
+/// A JSON-RPC node answering the version, blockhash and rent requests `--print` makes
+fn stub_rpc(blockhash: Hash) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let body = String::from_utf8(body).unwrap();
+
+            let result = if body.contains("getLatestBlockhash") {
+                format!(r#"{{"context":{{"slot":1}},"value":{{"blockhash":"{}","lastValidBlockHeight":100}}}}"#, blockhash)
+            } else if body.contains("getMinimumBalanceForRentExemption") {
+                RENT.to_string()
+            } else if body.contains("getVersion") {
+                r#"{"solana-core":"1.18.0","feature-set":0}"#.to_string()
+            } else {
+                panic!("unexpected request {}", body)
+            };
+            let response = format!(r#"{{"jsonrpc":"2.0","result":{},"id":1}}"#, result);
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        }
+    });
+    url
+}
+
+/// A payer keypair file only this test uses
+fn keypair_file(payer: &Keypair, name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("codox-cli-{}-{}.json", name, std::process::id()));
+    write_keypair_file(payer, &path).unwrap();
+    path
+}
+
+fn codox_cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_codox-cli")).args(args).output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn parses_arguments() {
+    let help = codox_cli(&["--help"]);
+    assert!(help.status.success());
+    let help = String::from_utf8_lossy(&help.stdout);
+    for command in ["init", "update-config", "pause", "unpause", "inspect-state"] {
+        assert!(help.contains(command), "{} missing from\n{}", command, help);
+    }
+
+    // Usage errors exit with 2 before anything is read or sent
+    let program_id = Pubkey::new_unique().to_string();
+    let missing_program = codox_cli(&["pause"]);
+    assert_eq!(missing_program.status.code(), Some(2));
+    assert!(stderr(&missing_program).contains("--program-id"));
+    let missing_rate = codox_cli(&["--program-id", &program_id, "init", "--mint", &program_id, "--tax-rate", "500"]);
+    assert_eq!(missing_rate.status.code(), Some(2));
+    assert!(stderr(&missing_rate).contains("--reflection-rate"));
+    let bad_pubkey = codox_cli(&["--program-id", "not-a-pubkey", "pause"]);
+    assert_eq!(bad_pubkey.status.code(), Some(2));
+    let bad_rate = codox_cli(&["--program-id", &program_id, "update-config", "--max-wallet-bps", "-1"]);
+    assert_eq!(bad_rate.status.code(), Some(2));
+
+    // Commands other than `init` need the state account
+    let payer = Keypair::new();
+    let keypair = keypair_file(&payer, "parse");
+    let no_state = codox_cli(&["--program-id", &program_id, "-k", keypair.to_str().unwrap(), "--print", "pause"]);
+    std::fs::remove_file(&keypair).unwrap();
+    assert_eq!(no_state.status.code(), Some(1));
+    assert!(stderr(&no_state).contains("--state is required"));
+}
+
+#[test]
+fn init_creates_and_initializes_the_state_in_one_transaction() {
+    let blockhash = Hash::new_unique();
+    let url = stub_rpc(blockhash);
+    let payer = Keypair::new();
+    let keypair = keypair_file(&payer, "init");
+    let program_id = Pubkey::new_unique();
+    let mint = Pubkey::new_unique();
+    let output = codox_cli(&[
+        "-u",
+        &url,
+        "-k",
+        keypair.to_str().unwrap(),
+        "--program-id",
+        &program_id.to_string(),
+        "--print",
+        "init",
+        "--mint",
+        &mint.to_string(),
+        "--tax-rate",
+        "500",
+        "--reflection-rate",
+        "200",
+        "--staking-rate",
+        "150",
+        "--lottery-rate",
+        "100",
+        "--liquidity-rate",
+        "50",
+        "--max-wallet-bps",
+        "200",
+    ]);
+    std::fs::remove_file(&keypair).unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    let transactions: Vec<Transaction> = lines
+        .iter()
+        .filter_map(|line| STANDARD.decode(line).ok())
+        .map(|bytes| bincode::deserialize(&bytes).unwrap())
+        .collect();
+    // Six pool token accounts, three per transaction, then the state
+    assert_eq!(transactions.len(), 3);
+    for transaction in &transactions {
+        assert_eq!(transaction.message.account_keys[0], payer.pubkey());
+        assert_eq!(transaction.message.recent_blockhash, blockhash);
+        transaction.verify().unwrap();
+    }
+    for transaction in &transactions[..2] {
+        assert_eq!(transaction.message.instructions.len(), 6);
+    }
+
+    let initialize = &transactions[2];
+    let [create, init] = &initialize.message.instructions[..] else {
+        panic!("expected create_account and InitializeCodoxToken, got {:?}", initialize.message.instructions);
+    };
+    let keys = &initialize.message.account_keys;
+    assert_eq!(keys[create.program_id_index as usize], system_program::id());
+    assert_eq!(
+        bincode::deserialize::<SystemInstruction>(&create.data).unwrap(),
+        SystemInstruction::CreateAccount {
+            lamports: RENT,
+            space: CodoxTokenState::LEN as u64,
+            owner: program_id,
+        }
+    );
+    let state = keys[create.accounts[1] as usize];
+    assert!(initialize.message.is_signer(create.accounts[1] as usize));
+
+    // The pools the earlier transactions created
+    let vaults: Vec<Pubkey> = transactions[..2]
+        .iter()
+        .flat_map(|transaction| {
+            let keys = &transaction.message.account_keys;
+            transaction.message.instructions.iter().step_by(2).map(|create| keys[create.accounts[1] as usize])
+        })
+        .collect();
+    let codox = CodoxAccounts {
+        program_id,
+        state,
+        mint,
+        tax_vault: vaults[0],
+        reflection_pool: vaults[1],
+        staking_pool: vaults[2],
+        lottery_pool: vaults[3],
+        liquidity_vault: vaults[4],
+        treasury_vault: vaults[5],
+        token_program: codox::token::id(),
+    };
+    let expected = instruction::initialize_codox_token(&codox, &payer.pubkey(), 500, 200, 150, 100, 50, 0, 0, 200);
+    assert_eq!(keys[init.program_id_index as usize], program_id);
+    assert_eq!(init.data, expected.data);
+    for (&index, meta) in init.accounts.iter().zip(&expected.accounts) {
+        let index = index as usize;
+        assert_eq!(keys[index], meta.pubkey);
+        assert_eq!(initialize.message.is_signer(index), meta.is_signer, "{}", meta.pubkey);
+    }
+    assert!(lines.contains(&format!("State: {}", state).as_str()));
+    assert!(lines.contains(&format!("Pool authority: {}", codox.pool_authority()).as_str()));
+}
@@ -0,0 +1,162 @@
+//! Minimal constant-product AMM speaking the `codox::amm` interface, so the
+//! swap-and-liquify and buyback paths run against real token accounts.
+//!
+//! The pool authority is the PDA of `[pool]`. `Swap` pays out at the
+//! constant-product price without a fee and deliberately ignores the minimum
+//! output, leaving the Codox program's own slippage check as the only guard.
+
+use codox::amm::{AmmPool, DEPOSIT_ALL_TOKEN_TYPES_TAG, SWAP_TAG};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use solana_sdk::signature::Signer;
+use spl_token::{
+    instruction::{mint_to, set_authority, transfer, AuthorityType},
+    state::{Account, Mint},
+};
+
+use super::TestEnv;
+
+/// LP tokens minted to the fee account when the pool is created
+pub const INITIAL_LP_SUPPLY: u64 = 1_000_000;
+
+pub fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (&tag, args) = data.split_first().ok_or(ProgramError::InvalidInstructionData)?;
+    let args: Vec<u64> = args
+        .chunks_exact(8)
+        .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    let [pool, authority, user_authority, rest @ ..] = accounts else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    let (expected_authority, bump) = Pubkey::find_program_address(&[pool.key.as_ref()], program_id);
+    if *authority.key != expected_authority {
+        return Err(ProgramError::InvalidSeeds);
+    }
+    let seeds: &[&[u8]] = &[pool.key.as_ref(), &[bump]];
+
+    match (tag, rest, args.as_slice()) {
+        (SWAP_TAG, [source, reserve_source, reserve_destination, destination, _pool_mint, _fee_account, token_program], &[amount_in, _minimum_amount_out]) => {
+            let reserve_in = balance(reserve_source)? as u128;
+            let reserve_out = balance(reserve_destination)? as u128;
+            let amount_out = (reserve_out * amount_in as u128 / (reserve_in + amount_in as u128)) as u64;
+
+            invoke(
+                &transfer(token_program.key, source.key, reserve_source.key, user_authority.key, &[], amount_in)?,
+                &[source.clone(), reserve_source.clone(), user_authority.clone(), token_program.clone()],
+            )?;
+            invoke_signed(
+                &transfer(token_program.key, reserve_destination.key, destination.key, authority.key, &[], amount_out)?,
+                &[reserve_destination.clone(), destination.clone(), authority.clone(), token_program.clone()],
+                &[seeds],
+            )
+        }
+        (
+            DEPOSIT_ALL_TOKEN_TYPES_TAG,
+            [source_a, source_b, reserve_a, reserve_b, pool_mint, destination, token_program],
+            &[pool_tokens, maximum_a, maximum_b],
+        ) => {
+            let supply = Mint::unpack(&pool_mint.data.borrow())?.supply as u128;
+            let amount_a = (pool_tokens as u128 * balance(reserve_a)? as u128).div_ceil(supply) as u64;
+            let amount_b = (pool_tokens as u128 * balance(reserve_b)? as u128).div_ceil(supply) as u64;
+            if amount_a > maximum_a || amount_b > maximum_b {
+                return Err(ProgramError::InsufficientFunds);
+            }
+
+            for (source, reserve, amount) in [(source_a, reserve_a, amount_a), (source_b, reserve_b, amount_b)] {
+                invoke(
+                    &transfer(token_program.key, source.key, reserve.key, user_authority.key, &[], amount)?,
+                    &[source.clone(), reserve.clone(), user_authority.clone(), token_program.clone()],
+                )?;
+            }
+            invoke_signed(
+                &mint_to(token_program.key, pool_mint.key, destination.key, authority.key, &[], pool_tokens)?,
+                &[pool_mint.clone(), destination.clone(), authority.clone(), token_program.clone()],
+                &[seeds],
+            )
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn balance(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(Account::unpack(&token_account.data.borrow())?.amount)
+}
+
+/// Accounts of a mock pool between CODOX and a quote token
+pub struct MockAmm {
+    pub program_id: Pubkey,
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub quote_mint: Pubkey,
+    pub codox_reserve: Pubkey,
+    pub quote_reserve: Pubkey,
+    pub pool_mint: Pubkey,
+    pub fee_account: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl MockAmm {
+    /// The pool with CODOX as token A
+    pub fn accounts(&self) -> AmmPool<'_> {
+        AmmPool {
+            program_id: &self.program_id,
+            pool: &self.pool,
+            authority: &self.authority,
+            reserve_a: &self.codox_reserve,
+            reserve_b: &self.quote_reserve,
+            pool_mint: &self.pool_mint,
+            fee_account: &self.fee_account,
+            token_program: &self.token_program,
+        }
+    }
+}
+
+impl TestEnv {
+    /// A mock pool seeded with `codox_amount` CODOX and `quote_amount` of a new quote mint
+    pub async fn create_amm(&mut self, codox_amount: u64, quote_amount: u64) -> MockAmm {
+        let payer = self.payer();
+        let program_id = self.amm_program_id;
+        let pool = Pubkey::new_unique();
+        let (authority, _) = Pubkey::find_program_address(&[pool.as_ref()], &program_id);
+
+        let codox_mint = self.codox.mint;
+        let quote_mint = self.create_mint(&payer).await;
+        let codox_reserve = self.create_token_account(&codox_mint, &authority).await;
+        let quote_reserve = self.create_token_account(&quote_mint, &authority).await;
+        self.mint_to(&codox_mint, &codox_reserve, codox_amount).await;
+        self.mint_to(&quote_mint, &quote_reserve, quote_amount).await;
+
+        // LP tokens outstanding before the first deposit, then minted only by the pool
+        let pool_mint = self.create_mint(&payer).await;
+        let fee_account = self.create_token_account(&pool_mint, &payer).await;
+        self.mint_to(&pool_mint, &fee_account, INITIAL_LP_SUPPLY).await;
+        let handover = set_authority(
+            &spl_token::id(),
+            &pool_mint,
+            Some(&authority),
+            AuthorityType::MintTokens,
+            &self.context.payer.pubkey(),
+            &[],
+        )
+        .unwrap();
+        self.process(&[handover], &[]).await.unwrap();
+
+        MockAmm {
+            program_id,
+            pool,
+            authority,
+            quote_mint,
+            codox_reserve,
+            quote_reserve,
+            pool_mint,
+            fee_account,
+            token_program: spl_token::id(),
+        }
+    }
+}
@@ -0,0 +1,532 @@
+//! Shared setup for the integration tests: a `ProgramTest` bank running the
+//! Codox program natively, with the mint, state account and pools of a fresh
+//! deployment and helpers to drive it and read its accounts back.
+#![allow(dead_code)]
+
+pub mod mock_amm;
+
+use std::collections::HashSet;
+
+use borsh::BorshDeserialize;
+use codox::{
+    instruction::{self, CodoxAccounts},
+    lottery::{LotteryDeposit, LotteryRound, LotteryState, TicketPage, MAX_PRIZE_TIERS},
+    ConfigUpdate, CodoxTokenState, HolderState,
+};
+use solana_program_test::{processor, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use solana_sdk::{
+    account::{Account, AccountSharedData},
+    clock::Clock,
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::{Keypair, Signature, Signer},
+    signer::keypair::keypair_from_seed,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+
+pub const DECIMALS: u8 = 6;
+/// Shares of the mechanisms this build has; the tax is what they add up to
+pub const REFLECTION_RATE: u16 = if cfg!(feature = "reflection") { 200 } else { 0 };
+pub const STAKING_RATE: u16 = if cfg!(feature = "staking") { 100 } else { 0 };
+pub const LOTTERY_RATE: u16 = if cfg!(feature = "lottery") { 100 } else { 0 };
+pub const LIQUIDITY_RATE: u16 = 50;
+pub const TREASURY_RATE: u16 = if cfg!(feature = "burn") { 50 } else { 0 };
+pub const TAX_RATE: u16 = REFLECTION_RATE + STAKING_RATE + LOTTERY_RATE + LIQUIDITY_RATE + TREASURY_RATE;
+pub const PRIZE_TIERS: [u16; MAX_PRIZE_TIERS] = [5000, 3000, 1000];
+pub const CLAIM_PERIOD: i64 = 7 * 86400;
+pub const DAY: i64 = 86400;
+/// Hash of every lottery draw slot. The state and holder wallets are fixed too,
+/// so a test draws the same winners every run.
+pub const DRAW_HASH: Hash = Hash::new_from_array([7; 32]);
+
+/// How the deployment is initialized
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    pub max_transfer_bps: u16,
+    pub max_wallet_bps: u16,
+    /// Open trading right after initializing
+    pub open_trading: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            max_transfer_bps: 0,
+            max_wallet_bps: 0,
+            open_trading: true,
+        }
+    }
+}
+
+/// A wallet with SOL for rent and a CODOX token account
+pub struct Holder {
+    pub wallet: Keypair,
+    pub token: Pubkey,
+}
+
+impl Holder {
+    pub fn pubkey(&self) -> Pubkey {
+        self.wallet.pubkey()
+    }
+}
+
+/// A bank with an initialized Codox deployment; the payer is both the program
+/// authority and the mint authority
+pub struct TestEnv {
+    pub context: ProgramTestContext,
+    pub codox: CodoxAccounts,
+    /// Signs `InitializeCodoxToken` for `codox.state`
+    pub state_keypair: Keypair,
+    pub amm_program_id: Pubkey,
+    sent: HashSet<Signature>,
+    /// Wallets `new_holder` created so far
+    holders: u32,
+}
+
+impl TestEnv {
+    pub async fn start(options: Options) -> Self {
+        let mut env = Self::start_uninitialized().await;
+        let payer = env.payer();
+        let state = env.state_keypair.insecure_clone();
+        env.process(
+            &[instruction::initialize_codox_token(
+                &env.codox,
+                &payer,
+                TAX_RATE,
+                REFLECTION_RATE,
+                STAKING_RATE,
+                LOTTERY_RATE,
+                LIQUIDITY_RATE,
+                TREASURY_RATE,
+                options.max_transfer_bps,
+                options.max_wallet_bps,
+            )],
+            &[&state],
+        )
+        .await
+        .unwrap();
+        if options.open_trading {
+            env.update_config(ConfigUpdate {
+                trading_enabled_at: Some(0),
+                ..ConfigUpdate::default()
+            })
+            .await
+            .unwrap();
+        }
+        env
+    }
+
+    /// The mint, an empty state account and the pools, before `InitializeCodoxToken`
+    pub async fn start_uninitialized() -> Self {
+        let program_id = Pubkey::new_unique();
+        let amm_program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new("codox", program_id, processor!(codox::process_instruction));
+        program_test.add_program("mock_amm", amm_program_id, processor!(mock_amm::process_instruction));
+        let context = program_test.start_with_context().await;
+
+        let state_keypair = fixed_keypair(u32::MAX);
+        let state = state_keypair.pubkey();
+        let placeholder = CodoxAccounts {
+            program_id,
+            state,
+            mint: Pubkey::default(),
+            tax_vault: Pubkey::default(),
+            reflection_pool: Pubkey::default(),
+            staking_pool: Pubkey::default(),
+            lottery_pool: Pubkey::default(),
+            liquidity_vault: Pubkey::default(),
+            treasury_vault: Pubkey::default(),
+            token_program: spl_token::id(),
+        };
+        let mut env = TestEnv {
+            context,
+            codox: placeholder,
+            state_keypair,
+            amm_program_id,
+            sent: HashSet::new(),
+            holders: 0,
+        };
+
+        let payer = env.payer();
+        let mint = env.create_mint(&payer).await;
+        let pool_authority = env.codox.pool_authority();
+        let mut vaults = [Pubkey::default(); 6];
+        for vault in vaults.iter_mut() {
+            *vault = env.create_token_account(&mint, &pool_authority).await;
+        }
+        let [tax_vault, reflection_pool, staking_pool, lottery_pool, liquidity_vault, treasury_vault] = vaults;
+        env.codox = CodoxAccounts {
+            mint,
+            tax_vault,
+            reflection_pool,
+            staking_pool,
+            lottery_pool,
+            liquidity_vault,
+            treasury_vault,
+            ..env.codox
+        };
+        let lamports = env.rent().await.minimum_balance(CodoxTokenState::LEN);
+        env.set_account(
+            &state,
+            Account {
+                lamports,
+                data: vec![0; CodoxTokenState::LEN],
+                owner: program_id,
+                ..Account::default()
+            },
+        );
+        env
+    }
+
+    pub fn payer(&self) -> Pubkey {
+        self.context.payer.pubkey()
+    }
+
+    /// Send `instructions` in one transaction paid for by the payer. A repeat of an
+    /// earlier transaction waits for a new blockhash instead of being dropped as a duplicate.
+    pub async fn process(&mut self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransactionError> {
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let payer = Some(self.context.payer.pubkey());
+        let mut blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = loop {
+            let transaction = Transaction::new_signed_with_payer(instructions, payer.as_ref(), &all_signers, blockhash);
+            if self.sent.insert(transaction.signatures[0]) {
+                break transaction;
+            }
+            blockhash = self.context.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+        };
+        self.context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .map_err(|error| error.unwrap())
+    }
+
+    /// Simulate a preview instruction and decode its return data
+    pub async fn simulate<T: BorshDeserialize>(&mut self, instruction: Instruction) -> T {
+        let blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&self.context.payer.pubkey()),
+            &[&self.context.payer],
+            blockhash,
+        );
+        let simulation = self.context.banks_client.simulate_transaction(transaction).await.unwrap();
+        let details = simulation.simulation_details.unwrap();
+        T::try_from_slice(&details.return_data.unwrap().data).unwrap()
+    }
+
+    /// Compute units `instruction` consumes, simulated on its own
+    pub async fn compute_units(&mut self, instruction: &Instruction, signers: &[&Keypair]) -> u64 {
+        let mut all_signers = vec![&self.context.payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = self.context.banks_client.get_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.context.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let simulation = self.context.banks_client.simulate_transaction(transaction).await.unwrap();
+        let details = simulation.simulation_details.unwrap();
+        if let Err(error) = simulation.result.unwrap() {
+            panic!("simulation failed with {error}: {:#?}", details.logs);
+        }
+        details.units_consumed
+    }
+
+    pub async fn rent(&mut self) -> Rent {
+        self.context.banks_client.get_rent().await.unwrap()
+    }
+
+    pub fn set_account(&mut self, address: &Pubkey, account: Account) {
+        self.context.set_account(address, &AccountSharedData::from(account));
+    }
+
+    pub async fn account(&mut self, address: &Pubkey) -> Option<Account> {
+        self.context.banks_client.get_account(*address).await.unwrap()
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        self.context.banks_client.get_sysvar::<Clock>().await.unwrap().unix_timestamp
+    }
+
+    /// Move to a later slot and the clock `seconds` forward
+    pub async fn advance_clock(&mut self, seconds: i64) {
+        let slot = self.context.banks_client.get_sysvar::<Clock>().await.unwrap().slot;
+        self.context.warp_to_slot(slot + 1).unwrap();
+        let mut clock = self.context.banks_client.get_sysvar::<Clock>().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.context.set_sysvar(&clock);
+    }
+
+    /// Move one slot at a time to the slot after `slot`, so each gets a slot hash
+    pub async fn advance_past_slot(&mut self, slot: u64) {
+        let mut current = self.context.banks_client.get_sysvar::<Clock>().await.unwrap().slot;
+        while current <= slot {
+            current += 1;
+            self.context.warp_to_slot(current).unwrap();
+        }
+    }
+
+    pub async fn update_config(&mut self, update: ConfigUpdate) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let instruction = instruction::update_config(&self.codox, &payer, update);
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn fund(&mut self, wallet: &Pubkey, lamports: u64) {
+        let payer = self.payer();
+        self.process(&[system_instruction::transfer(&payer, wallet, lamports)], &[])
+            .await
+            .unwrap();
+    }
+
+    pub async fn create_mint(&mut self, authority: &Pubkey) -> Pubkey {
+        let mint = Keypair::new();
+        let payer = self.payer();
+        let lamports = self.rent().await.minimum_balance(spl_token::state::Mint::LEN);
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    lamports,
+                    spl_token::state::Mint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_mint2(&spl_token::id(), &mint.pubkey(), authority, None, DECIMALS)
+                    .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+        mint.pubkey()
+    }
+
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let account = Keypair::new();
+        let payer = self.payer();
+        let lamports = self.rent().await.minimum_balance(spl_token::state::Account::LEN);
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &account.pubkey(),
+                    lamports,
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account3(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+            ],
+            &[&account],
+        )
+        .await
+        .unwrap();
+        account.pubkey()
+    }
+
+    /// Mint `amount` of a mint whose authority is the payer
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let payer = self.payer();
+        self.process(
+            &[spl_token::instruction::mint_to(&spl_token::id(), mint, account, &payer, &[], amount).unwrap()],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// A funded wallet holding `amount` CODOX in its associated token account
+    pub async fn new_holder(&mut self, amount: u64) -> Holder {
+        let wallet = fixed_keypair(self.holders);
+        self.holders += 1;
+        self.fund(&wallet.pubkey(), 1_000_000_000).await;
+        let mint = self.codox.mint;
+        let payer = self.payer();
+        let token = spl_associated_token_account::get_associated_token_address(&wallet.pubkey(), &mint);
+        self.process(
+            &[spl_associated_token_account::instruction::create_associated_token_account(
+                &payer,
+                &wallet.pubkey(),
+                &mint,
+                &spl_token::id(),
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        if amount > 0 {
+            self.mint_to(&mint, &token, amount).await;
+        }
+        Holder { wallet, token }
+    }
+
+    /// Taxed transfer between two holders
+    pub async fn transfer(&mut self, from: &Holder, to: &Holder, amount: u64) -> Result<(), TransactionError> {
+        let instruction = instruction::transfer(&self.codox, &from.pubkey(), &from.token, &to.token, &to.pubkey(), amount);
+        self.process(&[instruction], &[&from.wallet]).await
+    }
+
+    /// Move the tax vault into the pools
+    pub async fn distribute_tax_vault(&mut self) -> Result<(), TransactionError> {
+        let instruction = instruction::distribute_tax_vault(&self.codox, &[]);
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn register_holder(&mut self, holder: &Holder) -> Result<(), TransactionError> {
+        let instruction = instruction::register_holder(&self.codox, &holder.pubkey(), &holder.token);
+        self.process(&[instruction], &[&holder.wallet]).await
+    }
+
+    #[cfg(feature = "lottery")]
+    pub async fn initialize_lottery(&mut self, deposit_mode: bool) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let instruction = instruction::initialize_lottery(&self.codox, &payer, PRIZE_TIERS, 0, deposit_mode, CLAIM_PERIOD);
+        self.process(&[instruction], &[]).await
+    }
+
+    #[cfg(feature = "lottery")]
+    pub async fn open_ticket_page(&mut self, round: u64, page: u32) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let instruction = instruction::open_ticket_page(&self.codox, &payer, round, page);
+        self.process(&[instruction], &[]).await
+    }
+
+    /// `SnapshotBalances` of the holders' token accounts, signed by the payer
+    #[cfg(feature = "lottery")]
+    pub async fn snapshot_balances(&mut self, round: u64, page: u32, holders: &[&Holder]) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let holder_tokens: Vec<_> = holders.iter().map(|holder| (holder.token, holder.pubkey())).collect();
+        let instruction = instruction::snapshot_balances(&self.codox, &payer, round, page, &holder_tokens);
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Close the current lottery round and move past its draw slot, which hashes to `DRAW_HASH`
+    #[cfg(feature = "lottery")]
+    pub async fn close_lottery_round(&mut self) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let instruction = instruction::close_lottery_round(&self.codox, &payer);
+        self.process(&[instruction], &[]).await?;
+        let draw_slot = self.lottery().await.draw_slot;
+        self.advance_past_slot(draw_slot).await;
+        self.context.set_sysvar(&solana_sdk::slot_hashes::SlotHashes::new(&[(draw_slot, DRAW_HASH)]));
+        Ok(())
+    }
+
+    #[cfg(feature = "lottery")]
+    pub async fn draw_lottery(&mut self, round: u64, ticket_pages: u32) -> Result<(), TransactionError> {
+        let payer = self.payer();
+        let instruction = instruction::draw_lottery(&self.codox, &payer, round, ticket_pages);
+        self.process(&[instruction], &[]).await
+    }
+
+    /// Overwrite a ticket page as full, with `TICKET_PAGE_SIZE` entries of one
+    /// ticket each held by `holder`
+    pub async fn fill_ticket_page(&mut self, round: u64, page: u32, holder: &Pubkey) {
+        use codox::lottery::TICKET_PAGE_SIZE;
+
+        let address = self.codox.ticket_page(round, page);
+        let mut account = self.account(&address).await.unwrap();
+        let mut header = TicketPage::unpack(&account.data[..TicketPage::LEN]).unwrap();
+        for index in 0..TICKET_PAGE_SIZE {
+            TicketPage::set_entry(&mut account.data, index, holder, header.first_ticket + index as u64, 1);
+        }
+        header.count = TICKET_PAGE_SIZE as u32;
+        header.tickets = TICKET_PAGE_SIZE as u64;
+        TicketPage::pack(header, &mut account.data[..TicketPage::LEN]).unwrap();
+        self.set_account(&address, account);
+    }
+
+    pub async fn balance(&mut self, token_account: &Pubkey) -> u64 {
+        let account = self.account(token_account).await.unwrap();
+        spl_token::state::Account::unpack(&account.data).unwrap().amount
+    }
+
+    pub async fn supply(&mut self) -> u64 {
+        let mint = self.codox.mint;
+        let account = self.account(&mint).await.unwrap();
+        spl_token::state::Mint::unpack(&account.data).unwrap().supply
+    }
+
+    pub async fn state(&mut self) -> CodoxTokenState {
+        let state = self.codox.state;
+        let account = self.account(&state).await.unwrap();
+        CodoxTokenState::unpack(&account.data).unwrap()
+    }
+
+    pub async fn holder_state(&mut self, holder: &Pubkey) -> HolderState {
+        let address = self.codox.holder_state(holder);
+        let account = self.account(&address).await.unwrap();
+        HolderState::unpack(&account.data).unwrap()
+    }
+
+    pub async fn lottery(&mut self) -> LotteryState {
+        let address = self.codox.lottery();
+        let account = self.account(&address).await.unwrap();
+        LotteryState::deserialize(&mut &account.data[..]).unwrap()
+    }
+
+    pub async fn lottery_round(&mut self, round: u64) -> LotteryRound {
+        let address = self.codox.lottery_round(round);
+        let account = self.account(&address).await.unwrap();
+        LotteryRound::unpack(&account.data).unwrap()
+    }
+
+    pub async fn lottery_deposit(&mut self, owner: &Pubkey) -> LotteryDeposit {
+        let address = self.codox.lottery_deposit(owner);
+        let account = self.account(&address).await.unwrap();
+        LotteryDeposit::unpack(&account.data).unwrap()
+    }
+
+    pub async fn ticket_page(&mut self, round: u64, page: u32) -> TicketPage {
+        let address = self.codox.ticket_page(round, page);
+        let account = self.account(&address).await.unwrap();
+        TicketPage::unpack(&account.data[..TicketPage::LEN]).unwrap()
+    }
+
+    /// Balances of the reflection, staking, lottery, liquidity and treasury pools
+    pub async fn pool_balances(&mut self) -> [u64; 5] {
+        let CodoxAccounts {
+            reflection_pool,
+            staking_pool,
+            lottery_pool,
+            liquidity_vault,
+            treasury_vault,
+            ..
+        } = self.codox;
+        [
+            self.balance(&reflection_pool).await,
+            self.balance(&staking_pool).await,
+            self.balance(&lottery_pool).await,
+            self.balance(&liquidity_vault).await,
+            self.balance(&treasury_vault).await,
+        ]
+    }
+}
+
+/// The same keypair for `index` every run
+fn fixed_keypair(index: u32) -> Keypair {
+    let mut seed = [0; 32];
+    seed[..4].copy_from_slice(&index.to_le_bytes());
+    keypair_from_seed(&seed).unwrap()
+}
+
+/// Check a transaction failed with `expected` in any of its instructions
+pub fn assert_instruction_error(result: Result<(), TransactionError>, expected: InstructionError) {
+    match result {
+        Err(TransactionError::InstructionError(_, error)) => assert_eq!(error, expected),
+        other => panic!("expected {:?}, got {:?}", expected, other),
+    }
+}
+
+/// Check a transaction failed with a Codox program error
+pub fn assert_codox_error(result: Result<(), TransactionError>, expected: codox::error::CodoxError) {
+    assert_instruction_error(result, InstructionError::Custom(expected as u32));
+}
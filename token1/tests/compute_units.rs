@@ -0,0 +1,254 @@
+//! Compute units of every instruction against the budgets committed in
+//! `compute_budgets.txt`.
+//!
+//! Only an SBF build is metered like a validator would, so the test is ignored
+//! by a native `cargo test` and run with `cargo test-sbf --test compute_units --
+//! --ignored`. `CODOX_UPDATE_BUDGETS=1` rewrites the budgets from the
+//! measurements plus headroom.
+#![cfg(not(feature = "token-2022"))]
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use codox::{instruction, ConfigUpdate};
+use common::*;
+use solana_sdk::{instruction::Instruction, signature::Keypair};
+
+const BUDGETS_FILE: &str = "tests/compute_budgets.txt";
+const BUDGETS: &str = include_str!("compute_budgets.txt");
+
+/// Headroom over the measured units when rewriting the budgets, in percent
+const HEADROOM: u64 = 20;
+
+const SUPPLY: u64 = 1_000_000_000;
+const RESERVE: u64 = 100_000_000;
+/// Max-transaction limit the transfers are checked against, where the build has them
+const LIMIT_BPS: u16 = if cfg!(feature = "anti-whale") { 100 } else { 0 };
+
+/// Highest compute units measured per instruction
+#[derive(Default)]
+struct Measurements(BTreeMap<&'static str, u64>);
+
+impl Measurements {
+    /// Measure `instruction`, then process it
+    async fn run(&mut self, env: &mut TestEnv, name: &'static str, instruction: Instruction, signers: &[&Keypair]) {
+        self.simulate(env, name, &instruction, signers).await;
+        env.process(&[instruction], signers).await.unwrap();
+    }
+
+    /// Measure `instruction` without processing it, for the previews
+    async fn simulate(&mut self, env: &mut TestEnv, name: &'static str, instruction: &Instruction, signers: &[&Keypair]) {
+        let units = env.compute_units(instruction, signers).await;
+        let entry = self.0.entry(name).or_default();
+        *entry = (*entry).max(units);
+    }
+}
+
+fn on_sbf() -> bool {
+    std::env::var("SBF_OUT_DIR").is_ok() || std::env::var("BPF_OUT_DIR").is_ok()
+}
+
+fn budgets() -> BTreeMap<&'static str, u64> {
+    BUDGETS
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, units) = line.split_once(char::is_whitespace).expect("`Instruction units` lines");
+            (name, units.trim().parse().expect("units are a number"))
+        })
+        .collect()
+}
+
+fn write_budgets(measured: &BTreeMap<&'static str, u64>) {
+    let header: String = BUDGETS.lines().take_while(|line| line.starts_with('#')).map(|line| format!("{line}\n")).collect();
+    let lines: String = measured
+        .iter()
+        .map(|(name, units)| format!("{name} {}\n", (units * (100 + HEADROOM) / 100).div_ceil(1000) * 1000))
+        .collect();
+    std::fs::write(BUDGETS_FILE, header + &lines).unwrap();
+}
+
+/// Taxed transfers, staking, claims and the tax vault
+async fn core(measurements: &mut Measurements) {
+    let mut env = TestEnv::start_uninitialized().await;
+    let payer = env.payer();
+    let initialize = instruction::initialize_codox_token(
+        &env.codox,
+        &payer,
+        TAX_RATE,
+        REFLECTION_RATE,
+        STAKING_RATE,
+        LOTTERY_RATE,
+        LIQUIDITY_RATE,
+        TREASURY_RATE,
+        LIMIT_BPS,
+        2 * LIMIT_BPS,
+    );
+    let state = env.state_keypair.insecure_clone();
+    measurements.run(&mut env, "InitializeCodoxToken", initialize, &[&state]).await;
+    let open_trading = instruction::update_config(
+        &env.codox,
+        &payer,
+        ConfigUpdate {
+            trading_enabled_at: Some(0),
+            ..ConfigUpdate::default()
+        },
+    );
+    measurements.run(&mut env, "UpdateConfig", open_trading, &[]).await;
+
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(SUPPLY / 100).await;
+    let register = instruction::register_holder(&env.codox, &alice.pubkey(), &alice.token);
+    measurements.run(&mut env, "RegisterHolder", register, &[&alice.wallet]).await;
+
+    // With the launch limits checked, into an existing balance
+    let transfer = instruction::transfer(&env.codox, &alice.pubkey(), &alice.token, &bob.token, &bob.pubkey(), 1_000_000);
+    measurements.run(&mut env, "Transfer", transfer, &[&alice.wallet]).await;
+    let preview = instruction::preview_transfer_tax(&env.codox, &alice.token, &bob.token, 1_000_000);
+    measurements.simulate(&mut env, "PreviewTransferTax", &preview, &[]).await;
+
+    // Creating the staker's holder state
+    #[cfg(feature = "staking")]
+    {
+        let stake = instruction::stake(&env.codox, &bob.pubkey(), &bob.token, 1_000_000, &[]);
+        measurements.run(&mut env, "Stake", stake, &[&bob.wallet]).await;
+        let unstake = instruction::unstake(&env.codox, &bob.pubkey(), &bob.token, 500_000, &[]);
+        measurements.run(&mut env, "Unstake", unstake, &[&bob.wallet]).await;
+    }
+
+    // The transfer's tax, plus tokens outside the tracked shares
+    let (mint, tax_vault) = (env.codox.mint, env.codox.tax_vault);
+    env.mint_to(&mint, &tax_vault, 1_000_000).await;
+    let distribute = instruction::distribute_tax_vault(&env.codox, &[]);
+    measurements.run(&mut env, "DistributeTaxVault", distribute, &[]).await;
+
+    #[cfg(feature = "reflection")]
+    {
+        env.advance_clock(5 * DAY).await;
+        let preview = instruction::preview_claim(&env.codox, &alice.pubkey(), &alice.token);
+        measurements.simulate(&mut env, "PreviewClaim", &preview, &[]).await;
+        let claim = instruction::claim_reflection(&env.codox, &alice.pubkey(), &alice.token, &[]);
+        measurements.run(&mut env, "ClaimReflection", claim, &[&alice.wallet]).await;
+    }
+}
+
+/// A balance lottery round over two holders, then a deposit round
+#[cfg(feature = "lottery")]
+async fn lottery(measurements: &mut Measurements) {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+    let alice = env.new_holder(SUPPLY / 2).await;
+    let bob = env.new_holder(SUPPLY / 2).await;
+    env.register_holder(&alice).await.unwrap();
+    env.register_holder(&bob).await.unwrap();
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+
+    let initialize = instruction::initialize_lottery(&env.codox, &payer, PRIZE_TIERS, 100, false, CLAIM_PERIOD);
+    measurements.run(&mut env, "InitializeLottery", initialize, &[]).await;
+    let open = instruction::open_ticket_page(&env.codox, &payer, 0, 0);
+    measurements.run(&mut env, "OpenTicketPage", open, &[]).await;
+    let holder_tokens = [(alice.token, alice.pubkey()), (bob.token, bob.pubkey())];
+    let snapshot = instruction::snapshot_balances(&env.codox, &payer, 0, 0, &holder_tokens);
+    measurements.run(&mut env, "SnapshotBalances", snapshot, &[]).await;
+    let odds = instruction::preview_lottery_odds(&env.codox, &alice.pubkey(), 0, 1);
+    measurements.simulate(&mut env, "PreviewLotteryOdds", &odds, &[]).await;
+    env.advance_clock(DAY).await;
+    let close = instruction::close_lottery_round(&env.codox, &payer);
+    measurements.run(&mut env, "CloseLotteryRound", close, &[]).await;
+    let draw_slot = env.lottery().await.draw_slot;
+    env.advance_past_slot(draw_slot).await;
+    let draw = instruction::draw_lottery(&env.codox, &payer, 0, 1);
+    measurements.run(&mut env, "DrawLottery", draw, &[]).await;
+
+    let round = env.lottery_round(0).await;
+    let winner = if round.winners[0] == alice.pubkey() { &alice } else { &bob };
+    let claim = instruction::claim_lottery_prize(&env.codox, &winner.pubkey(), &winner.token, 0, 0, &[]);
+    measurements.run(&mut env, "ClaimLotteryPrize", claim, &[&winner.wallet]).await;
+    let close = instruction::close_ticket_page(&env.codox, &payer, 0, 0);
+    measurements.run(&mut env, "CloseTicketPage", close, &[]).await;
+    env.advance_clock(CLAIM_PERIOD + 1).await;
+    let expire = instruction::expire_lottery_prizes(&env.codox, 0);
+    measurements.run(&mut env, "ExpireLotteryPrizes", expire, &[]).await;
+
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(10_000_000).await;
+    env.initialize_lottery(true).await.unwrap();
+    // Creating the deposit account
+    let deposit = instruction::deposit_to_lottery(&env.codox, &alice.pubkey(), &alice.token, 4_000_000, &[]);
+    measurements.run(&mut env, "DepositToLottery", deposit, &[&alice.wallet]).await;
+    env.open_ticket_page(0, 0).await.unwrap();
+    let payer = env.payer();
+    let snapshot = instruction::snapshot_deposits(&env.codox, &payer, 0, 0, &[alice.pubkey()]);
+    measurements.run(&mut env, "SnapshotDeposits", snapshot, &[]).await;
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    let withdraw = instruction::withdraw_from_lottery(&env.codox, &alice.pubkey(), &alice.token, 4_000_000, &[]);
+    measurements.run(&mut env, "WithdrawFromLottery", withdraw, &[&alice.wallet]).await;
+}
+
+/// Swap-and-liquify and a buyback against the mock AMM
+async fn amm(measurements: &mut Measurements) {
+    let mut env = TestEnv::start(Options::default()).await;
+    let amm = env.create_amm(RESERVE, RESERVE).await;
+    let pool_authority = env.codox.pool_authority();
+    let quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+    let lp_vault = env.create_token_account(&amm.pool_mint, &pool_authority).await;
+    let (mint, liquidity_vault) = (env.codox.mint, env.codox.liquidity_vault);
+    env.mint_to(&mint, &liquidity_vault, 2_000_000).await;
+    env.mint_to(&amm.quote_mint, &quote_vault, 1_000_000).await;
+    env.update_config(ConfigUpdate {
+        amm_program: Some(amm.program_id),
+        amm_pool: Some(amm.pool),
+        liquify_threshold: Some(1_000_000),
+        treasury_quote_vault: Some(quote_vault),
+        buyback_max_per_call: Some(1_000_000),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+
+    let payer = env.payer();
+    let quote_out = codox::amm::swap_amount_out(1_000_000, RESERVE, RESERVE);
+    let liquify = instruction::swap_and_liquify(&env.codox, &payer, &amm.accounts(), &quote_vault, &lp_vault, quote_out);
+    measurements.run(&mut env, "SwapAndLiquify", liquify, &[]).await;
+    #[cfg(feature = "burn")]
+    {
+        let buyback = instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &quote_vault, 500_000, 1);
+        measurements.run(&mut env, "BuybackAndBurn", buyback, &[]).await;
+    }
+}
+
+#[tokio::test]
+#[ignore = "compute units are only metered on an SBF build: cargo test-sbf --test compute_units -- --ignored"]
+async fn instructions_fit_their_compute_budgets() {
+    assert!(on_sbf(), "compute units are only metered on an SBF build");
+    let mut measurements = Measurements::default();
+    core(&mut measurements).await;
+    #[cfg(feature = "lottery")]
+    lottery(&mut measurements).await;
+    amm(&mut measurements).await;
+    let measured = measurements.0;
+
+    let budgets = budgets();
+    for (name, units) in &measured {
+        println!("{name:<24} {units:>8} / {:>8}", budgets.get(name).copied().unwrap_or_default());
+    }
+    if std::env::var("CODOX_UPDATE_BUDGETS").is_ok() {
+        write_budgets(&measured);
+        return;
+    }
+
+    let over: Vec<String> = measured
+        .iter()
+        .filter_map(|(name, units)| match budgets.get(name) {
+            Some(budget) if units <= budget => None,
+            Some(budget) => Some(format!("{name} used {units} compute units, over its budget of {budget}")),
+            None => Some(format!("{name} used {units} compute units and has no budget")),
+        })
+        .collect();
+    assert!(over.is_empty(), "{}", over.join("\n"));
+}
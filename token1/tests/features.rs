@@ -0,0 +1,97 @@
+//! What the mechanism features leave out of a build: the instructions of a
+//! missing mechanism don't decode, and `InitializeCodoxToken` rejects a share of
+//! the tax or a launch limit nothing in the build would act on.
+#![cfg(not(feature = "token-2022"))]
+
+mod common;
+
+use borsh::BorshDeserialize;
+use codox::{idl, instruction, CodoxTokenInstruction};
+use common::*;
+use solana_sdk::{
+    instruction::InstructionError, program_error::ProgramError, pubkey::Pubkey, transaction::TransactionError,
+};
+
+/// Whether this build has a feature the IDL names
+fn built(feature: &str) -> bool {
+    match feature {
+        "reflection" => cfg!(feature = "reflection"),
+        "staking" => cfg!(feature = "staking"),
+        "lottery" => cfg!(feature = "lottery"),
+        "anti-whale" => cfg!(feature = "anti-whale"),
+        "burn" => cfg!(feature = "burn"),
+        "token-2022" => cfg!(feature = "token-2022"),
+        _ => panic!("unknown feature {feature}"),
+    }
+}
+
+/// `InitializeCodoxToken` on a fresh deployment with the tax, its shares and the limits given
+async fn initialize(rates: [u16; 6], max_transfer_bps: u16, max_wallet_bps: u16) -> Result<(), TransactionError> {
+    let mut env = TestEnv::start_uninitialized().await;
+    let payer = env.payer();
+    let [tax_rate, reflection_rate, staking_rate, lottery_rate, liquidity_rate, treasury_rate] = rates;
+    let initialize = instruction::initialize_codox_token(
+        &env.codox,
+        &payer,
+        tax_rate,
+        reflection_rate,
+        staking_rate,
+        lottery_rate,
+        liquidity_rate,
+        treasury_rate,
+        max_transfer_bps,
+        max_wallet_bps,
+    );
+    let state = env.state_keypair.insecure_clone();
+    env.process(&[initialize], &[&state]).await
+}
+
+#[tokio::test]
+async fn shares_need_their_mechanism_built() {
+    let rates = [TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE];
+    initialize(rates, 0, 0).await.unwrap();
+
+    for (index, feature) in [(1, "reflection"), (2, "staking"), (3, "lottery"), (5, "burn")] {
+        // The liquidity vault's share moved to the mechanism, or to the treasury
+        // `BuybackAndBurn` empties
+        let mut rates = rates;
+        rates[index] += std::mem::take(&mut rates[4]);
+        let result = initialize(rates, 0, 0).await;
+        if built(feature) {
+            result.unwrap();
+        } else {
+            assert_instruction_error(result, InstructionError::InvalidArgument);
+        }
+    }
+}
+
+#[tokio::test]
+async fn limits_need_anti_whale() {
+    let rates = [TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE];
+    for (max_transfer_bps, max_wallet_bps) in [(100, 0), (0, 200)] {
+        let result = initialize(rates, max_transfer_bps, max_wallet_bps).await;
+        if built("anti-whale") {
+            result.unwrap();
+        } else {
+            assert_instruction_error(result, InstructionError::InvalidArgument);
+        }
+    }
+}
+
+#[test]
+fn instructions_decode_only_when_built() {
+    for (tag, instruction) in idl::INSTRUCTIONS.iter().enumerate() {
+        let is_built = instruction.feature.is_none_or(built);
+        // Zeros decode as any arguments, and the tags stay put whatever the build leaves out
+        let mut data = vec![0; 256];
+        data[0] = tag as u8;
+        let decoded = CodoxTokenInstruction::deserialize(&mut &data[..]);
+        assert_eq!(decoded.is_ok(), is_built, "{} decodes", instruction.name);
+        if let Ok(decoded) = decoded {
+            assert!(format!("{decoded:?}").starts_with(instruction.name), "tag {tag} is {}", instruction.name);
+        } else {
+            let result = codox::process_instruction(&Pubkey::new_unique(), &[], &data[..1]);
+            assert!(matches!(result, Err(ProgramError::BorshIoError(_))), "{} is rejected", instruction.name);
+        }
+    }
+}
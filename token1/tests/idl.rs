@@ -0,0 +1,389 @@
+//! `idl/codox.json` is rendered from `codox::idl`, which is checked here against
+//! `CodoxTokenInstruction` and the account docs on its variants, the instruction
+//! builders and the account structs. After an intended change,
+//! `CODOX_UPDATE_IDL=1 cargo test --test idl` rewrites the JSON.
+
+use std::fmt::Debug;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use codox::{
+    amm::AmmPool,
+    idl::{self, IdlAccount, IdlField, IdlInstruction, IdlType},
+    instruction::{self, CodoxAccounts},
+    lottery::{LotteryDeposit, LotteryRound, LotteryState, TicketPage, TICKET_ENTRY_LEN},
+    preview::{ClaimPreview, LotteryOddsPreview, TransferTaxPreview},
+    CodoxTokenInstruction, CodoxTokenState, ConfigUpdate, HolderState, TaxSplit,
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+
+const IDL_FILE: &str = "idl/codox.json";
+const IDL: &str = include_str!("../idl/codox.json");
+const LIB: &str = include_str!("../src/lib.rs");
+
+#[test]
+fn idl_file_is_current() {
+    let json = idl::to_json();
+    if std::env::var("CODOX_UPDATE_IDL").is_ok() {
+        std::fs::write(IDL_FILE, json).unwrap();
+        return;
+    }
+    assert!(json == IDL, "{IDL_FILE} is out of date, rewrite it with `CODOX_UPDATE_IDL=1 cargo test --test idl`");
+}
+
+/// Top-level `name: value` pairs of a struct's or struct variant's `Debug` output
+fn debug_fields(debug: &str) -> Vec<(String, String)> {
+    let Some(start) = debug.find('{') else {
+        return Vec::new();
+    };
+    let inner = debug[start + 1..debug.len() - 1].trim();
+    let (mut fields, mut depth, mut field_start) = (Vec::new(), 0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' | '[' | '(' => depth += 1,
+            '}' | ']' | ')' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(&inner[field_start..i]);
+                field_start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&inner[field_start..]);
+    fields
+        .into_iter()
+        .map(|field| {
+            let (name, value) = field.trim().split_once(": ").expect("`name: value` fields");
+            (name.to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn field_names(fields: &[IdlField]) -> Vec<&str> {
+    fields.iter().map(|field| field.name).collect()
+}
+
+/// Whether this build has the instruction
+fn built(instruction: &IdlInstruction) -> bool {
+    instruction.feature.is_none_or(|feature| match feature {
+        "reflection" => cfg!(feature = "reflection"),
+        "staking" => cfg!(feature = "staking"),
+        "lottery" => cfg!(feature = "lottery"),
+        "burn" => cfg!(feature = "burn"),
+        "token-2022" => cfg!(feature = "token-2022"),
+        _ => panic!("unknown feature {feature}"),
+    })
+}
+
+/// One instruction of each kind, with two accounts in every variadic list
+fn instructions(codox: &CodoxAccounts) -> Vec<Instruction> {
+    let [user, token, other] = [(); 3].map(|_| Pubkey::new_unique());
+    let amm_keys = [(); 8].map(|_| Pubkey::new_unique());
+    let amm = AmmPool {
+        program_id: &amm_keys[0],
+        pool: &amm_keys[1],
+        authority: &amm_keys[2],
+        reserve_a: &amm_keys[3],
+        reserve_b: &amm_keys[4],
+        pool_mint: &amm_keys[5],
+        fee_account: &amm_keys[6],
+        token_program: &amm_keys[7],
+    };
+    let hook = [AccountMeta::new_readonly(Pubkey::new_unique(), false), AccountMeta::new_readonly(Pubkey::new_unique(), false)];
+    let update = ConfigUpdate {
+        paused: Some(true),
+        ..ConfigUpdate::default()
+    };
+
+    // In tag order, leaving out what this build doesn't have
+    let mut instructions = vec![
+        instruction::initialize_codox_token(codox, &user, 1, 2, 3, 4, 5, 6, 7, 8),
+        instruction::transfer(codox, &user, &token, &other, &other, 1),
+    ];
+    #[cfg(feature = "staking")]
+    instructions.push(instruction::stake(codox, &user, &token, 1, &hook));
+    #[cfg(feature = "reflection")]
+    instructions.push(instruction::claim_reflection(codox, &user, &token, &hook));
+    // Retired, so there's no builder
+    let retired = borsh::to_vec(&CodoxTokenInstruction::ParticipateInLottery).unwrap();
+    instructions.push(Instruction::new_with_bytes(codox.program_id, &retired, vec![]));
+    #[cfg(feature = "lottery")]
+    instructions.push(instruction::draw_lottery(codox, &user, 0, 2));
+    instructions.extend([
+        instruction::distribute_tax_vault(codox, &hook),
+        instruction::update_config(codox, &user, update),
+        instruction::register_holder(codox, &user, &token),
+        instruction::swap_and_liquify(codox, &user, &amm, &token, &other, 1),
+    ]);
+    #[cfg(feature = "burn")]
+    instructions.push(instruction::buyback_and_burn(codox, &user, &amm, &token, 1, 1));
+    #[cfg(feature = "lottery")]
+    instructions.extend([
+        instruction::initialize_lottery(codox, &user, [1, 2, 3], 4, true, 5),
+        instruction::claim_lottery_prize(codox, &user, &token, 0, 1, &hook),
+        instruction::deposit_to_lottery(codox, &user, &token, 1, &hook),
+        instruction::withdraw_from_lottery(codox, &user, &token, 1, &hook),
+        instruction::snapshot_deposits(codox, &user, 0, 0, &[user, other]),
+        instruction::expire_lottery_prizes(codox, 0),
+        instruction::open_ticket_page(codox, &user, 0, 1),
+        instruction::close_ticket_page(codox, &user, 0, 0),
+    ]);
+    #[cfg(feature = "reflection")]
+    instructions.push(instruction::preview_claim(codox, &user, &token));
+    instructions.push(instruction::preview_transfer_tax(codox, &token, &other, 1));
+    #[cfg(feature = "lottery")]
+    instructions.push(instruction::preview_lottery_odds(codox, &user, 0, 2));
+    #[cfg(feature = "staking")]
+    instructions.push(instruction::unstake(codox, &user, &token, 1, &hook));
+    #[cfg(feature = "token-2022")]
+    instructions.extend([
+        instruction::initialize_transfer_hook(codox, &Pubkey::new_unique(), &user),
+        instruction::harvest_fees(codox, &[token, other]),
+    ]);
+    #[cfg(feature = "lottery")]
+    instructions.extend([
+        instruction::close_lottery_round(codox, &user),
+        instruction::snapshot_balances(codox, &user, 0, 0, &[(token, user), (other, other)]),
+    ]);
+    instructions
+}
+
+fn check_account(instruction: &str, account: &IdlAccount, meta: &AccountMeta) {
+    assert_eq!(
+        (meta.is_signer, meta.is_writable),
+        (account.signer, account.writable),
+        "{instruction} account `{}` signer and writable flags",
+        account.name
+    );
+}
+
+#[test]
+fn instructions_match_the_builders() {
+    let codox = CodoxAccounts {
+        program_id: Pubkey::new_unique(),
+        state: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        tax_vault: Pubkey::new_unique(),
+        reflection_pool: Pubkey::new_unique(),
+        staking_pool: Pubkey::new_unique(),
+        lottery_pool: Pubkey::new_unique(),
+        liquidity_vault: Pubkey::new_unique(),
+        treasury_vault: Pubkey::new_unique(),
+        token_program: Pubkey::new_unique(),
+    };
+
+    let mut names = Vec::new();
+    for built_instruction in instructions(&codox) {
+        let debug = format!("{:?}", CodoxTokenInstruction::try_from_slice(&built_instruction.data).unwrap());
+        let name = debug.split(' ').next().unwrap().to_string();
+        let (discriminant, instruction) = idl::INSTRUCTIONS
+            .iter()
+            .enumerate()
+            .find(|(_, instruction)| instruction.name == name)
+            .unwrap_or_else(|| panic!("{name} is missing from the IDL"));
+        assert_eq!(built_instruction.data[0] as usize, discriminant, "{name} discriminant");
+
+        let args: Vec<String> = debug_fields(&debug).into_iter().map(|(arg, _)| arg).collect();
+        assert_eq!(args, field_names(instruction.args), "{name} arguments");
+        let args_size: Option<usize> = instruction.args.iter().map(|arg| arg.ty.size()).sum();
+        if let Some(args_size) = args_size {
+            assert_eq!(built_instruction.data.len(), 1 + args_size, "{name} argument sizes");
+        }
+
+        let (fixed, variadic) = match instruction.accounts.split_last() {
+            Some((last, fixed)) if last.variadic => (fixed, Some(last)),
+            _ => (instruction.accounts, None),
+        };
+        let metas = &built_instruction.accounts;
+        assert!(metas.len() >= fixed.len(), "{name} has {} accounts, the IDL lists {}", metas.len(), fixed.len());
+        for (account, meta) in fixed.iter().zip(metas) {
+            check_account(&name, account, meta);
+        }
+        let rest = &metas[fixed.len()..];
+        match variadic {
+            Some(variadic) => rest.iter().for_each(|meta| check_account(&name, variadic, meta)),
+            None => assert!(rest.is_empty(), "{name} has {} accounts, the IDL lists {}", metas.len(), fixed.len()),
+        }
+        names.push(name);
+    }
+
+    let expected: Vec<&str> = idl::INSTRUCTIONS.iter().filter(|instruction| built(instruction)).map(|instruction| instruction.name).collect();
+    assert_eq!(names, expected);
+}
+
+/// A `CodoxTokenInstruction` variant as written in the source
+struct Variant {
+    name: String,
+    docs: Vec<String>,
+    feature: Option<String>,
+}
+
+/// Doc comment lines and `cfg` feature of each `CodoxTokenInstruction` variant, in variant order
+fn variants() -> Vec<Variant> {
+    let body = LIB.split("pub enum CodoxTokenInstruction {").nth(1).unwrap().split("\n}\n").next().unwrap();
+    let (mut variants, mut docs, mut feature) = (Vec::new(), Vec::new(), None);
+    for line in body.lines() {
+        let line = line.strip_prefix("    ").unwrap_or(line);
+        if let Some(doc) = line.trim().strip_prefix("///") {
+            docs.push(doc.trim().to_string());
+        } else if let Some(cfg) = line.strip_prefix("#[cfg(feature = \"") {
+            feature = Some(cfg.trim_end_matches("\")]").to_string());
+        } else if line.starts_with(char::is_uppercase) {
+            let name = line.trim().trim_end_matches([',', '{']).trim();
+            variants.push(Variant {
+                name: name.to_string(),
+                docs: std::mem::take(&mut docs),
+                feature: feature.take(),
+            });
+        }
+    }
+    variants
+}
+
+#[test]
+fn instructions_match_the_docs() {
+    let variants = variants();
+    let names: Vec<&str> = variants.iter().map(|variant| variant.name.as_str()).collect();
+    let expected: Vec<&str> = idl::INSTRUCTIONS.iter().map(|instruction| instruction.name).collect();
+    assert_eq!(names, expected, "instructions in variant order");
+
+    for (Variant { name, docs, feature }, instruction) in variants.iter().zip(idl::INSTRUCTIONS) {
+        assert_eq!(feature.as_deref(), instruction.feature, "{name} feature");
+        // Retired instructions list no accounts
+        let accounts_start = docs.iter().position(|line| line == "Accounts expected:").unwrap_or(docs.len());
+        assert_eq!(docs[..accounts_start], *instruction.docs, "{name} docs");
+        let accounts: Vec<String> = instruction
+            .accounts
+            .iter()
+            .enumerate()
+            .map(|(index, account)| {
+                let flags: Vec<&str> = [(account.signer, "signer"), (account.writable, "writable")]
+                    .into_iter()
+                    .filter_map(|(set, flag)| set.then_some(flag))
+                    .collect();
+                format!("{index}. [{}] {}", flags.join(", "), account.docs)
+            })
+            .collect();
+        assert_eq!(docs.get(accounts_start + 1..).unwrap_or_default(), accounts, "{name} accounts");
+        if let Some(returns) = instruction.returns {
+            assert!(docs.join(" ").contains(&format!("preview::{returns}")), "{name} return type");
+        }
+    }
+}
+
+/// A field's value read from its bytes, as `Debug` prints it
+fn render(ty: &IdlType, bytes: &[u8]) -> String {
+    match ty {
+        IdlType::Bool => (bytes[0] != 0).to_string(),
+        IdlType::U8 => bytes[0].to_string(),
+        IdlType::U16 => u16::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        IdlType::U32 => u32::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        IdlType::U64 => u64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        IdlType::I64 => i64::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        IdlType::U128 => u128::from_le_bytes(bytes.try_into().unwrap()).to_string(),
+        IdlType::PublicKey => Pubkey::new_from_array(bytes.try_into().unwrap()).to_string(),
+        IdlType::Array(ty, _) => {
+            let items: Vec<String> = bytes.chunks(ty.size().unwrap()).map(|item| render(ty, item)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        IdlType::Defined(name) => {
+            let ty = idl::TYPES.iter().find(|ty| ty.name == *name).unwrap();
+            let fields: Vec<String> = layout(ty.fields)
+                .map(|(field, offset, size)| format!("{}: {}", field.name, render(&field.ty, &bytes[offset..offset + size])))
+                .collect();
+            format!("{{ {} }}", fields.join(", "))
+        }
+        IdlType::Option(_) => unreachable!("account fields have a fixed size"),
+    }
+}
+
+/// Fields with their offsets and sizes, back to back
+fn layout(fields: &[IdlField]) -> impl Iterator<Item = (&IdlField, usize, usize)> {
+    fields.iter().scan(0, |offset, field| {
+        let size = field.ty.size().expect("fixed-size field");
+        *offset += size;
+        Some((field, *offset - size, size))
+    })
+}
+
+/// `Debug` output of the struct `data` decodes to
+fn decode_account(name: &str, data: &[u8]) -> String {
+    match name {
+        "CodoxTokenState" => format!("{:?}", CodoxTokenState::unpack_unchecked(data).unwrap()),
+        "HolderState" => format!("{:?}", HolderState::unpack_unchecked(data).unwrap()),
+        "LotteryState" => format!("{:?}", LotteryState::try_from_slice(data).unwrap()),
+        "LotteryRound" => format!("{:?}", LotteryRound::unpack_unchecked(data).unwrap()),
+        "LotteryDeposit" => format!("{:?}", LotteryDeposit::unpack_unchecked(data).unwrap()),
+        "TicketPage" => format!("{:?}", TicketPage::unpack_unchecked(data).unwrap()),
+        _ => panic!("no struct for account {name}"),
+    }
+}
+
+#[test]
+fn account_layouts_match_the_structs() {
+    for account in idl::ACCOUNTS {
+        // No two neighbouring bytes alike, so a shifted field shows; Borsh wants bools of 0 or 1
+        let mut data: Vec<u8> = (0..account.size).map(|i| (i * 7 % 251) as u8 + 1).collect();
+        let fields: Vec<_> = layout(account.fields).collect();
+        for (field, offset, _) in &fields {
+            if field.ty == IdlType::Bool {
+                data[*offset] = 1;
+            }
+        }
+        let fields_size: usize = fields.iter().map(|(_, _, size)| size).sum();
+        assert_eq!(fields_size, account.size, "{} fields cover the account", account.name);
+
+        let expected: Vec<(String, String)> = fields
+            .iter()
+            .map(|(field, offset, size)| (field.name.to_string(), render(&field.ty, &data[*offset..offset + size])))
+            .collect();
+        let decoded: Vec<(String, String)> = debug_fields(&decode_account(account.name, &data))
+            .into_iter()
+            .map(|(name, value)| match value.split_once(" {") {
+                // Nested structs are rendered without their name
+                Some((_, fields)) => (name, format!("{{{fields}")),
+                None => (name, value),
+            })
+            .collect();
+        assert_eq!(decoded, expected, "{} layout", account.name);
+    }
+}
+
+/// `Debug` output and encoded size of a type's default value
+fn sample<T: Debug + Default + BorshSerialize>() -> (String, usize) {
+    let value = T::default();
+    (format!("{value:?}"), borsh::to_vec(&value).unwrap().len())
+}
+
+#[test]
+fn types_match_the_structs() {
+    for ty in idl::TYPES {
+        let (debug, encoded_size) = match ty.name {
+            "ConfigUpdate" => sample::<ConfigUpdate>(),
+            "TaxSplit" => sample::<TaxSplit>(),
+            "ClaimPreview" => sample::<ClaimPreview>(),
+            "TransferTaxPreview" => sample::<TransferTaxPreview>(),
+            "LotteryOddsPreview" => sample::<LotteryOddsPreview>(),
+            "TicketEntry" => {
+                let holder = Pubkey::new_unique();
+                let mut data = vec![0; TicketPage::LEN + TICKET_ENTRY_LEN];
+                TicketPage::set_entry(&mut data, 0, &holder, 7, 9);
+                let entry = &data[TicketPage::LEN..];
+                let fields: Vec<(&str, String)> = layout(ty.fields)
+                    .map(|(field, offset, size)| (field.name, render(&field.ty, &entry[offset..offset + size])))
+                    .collect();
+                assert_eq!(fields, [("holder", holder.to_string()), ("first_ticket", "7".into()), ("tickets", "9".into())]);
+                continue;
+            }
+            name => panic!("no struct for type {name}"),
+        };
+        let names: Vec<String> = debug_fields(&debug).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, field_names(ty.fields), "{} fields", ty.name);
+        if let Some(size) = IdlType::Defined(ty.name).size() {
+            assert_eq!(size, encoded_size, "{} size", ty.name);
+        }
+    }
+}
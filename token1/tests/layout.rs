@@ -0,0 +1,131 @@
+//! The zero-copy state structs read deployed accounts: every field sits where
+//! the original hand-written `Pack` layout put it, in order, little-endian and
+//! without padding.
+
+use codox::{CodoxTokenState, HolderState};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+/// Reads the fields of the original layout one after another
+struct Fields<'a>(&'a [u8]);
+
+impl Fields<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let (field, rest) = self.0.split_at(N);
+        self.0 = rest;
+        field.try_into().unwrap()
+    }
+
+    fn flag(&mut self) -> bool {
+        self.take::<1>()[0] != 0
+    }
+
+    fn pubkey(&mut self) -> Pubkey {
+        Pubkey::new_from_array(self.take())
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn i64(&mut self) -> i64 {
+        i64::from_le_bytes(self.take())
+    }
+
+    fn u128(&mut self) -> u128 {
+        u128::from_le_bytes(self.take())
+    }
+}
+
+/// Account data with no two neighbouring bytes alike, so a shifted field shows
+fn account_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7 % 251) as u8 + 1).collect()
+}
+
+#[test]
+fn codox_token_state_layout() {
+    let data = account_data(CodoxTokenState::LEN);
+    let state = CodoxTokenState::unpack_unchecked(&data).unwrap();
+    let mut fields = Fields(&data);
+
+    assert_eq!(state.is_initialized.get(), fields.flag());
+    assert_eq!(state.authority, fields.pubkey());
+    assert_eq!(state.token_mint, fields.pubkey());
+    assert_eq!(state.tax_vault, fields.pubkey());
+    assert_eq!(state.reflection_pool, fields.pubkey());
+    assert_eq!(state.staking_pool, fields.pubkey());
+    assert_eq!(state.lottery_pool, fields.pubkey());
+    assert_eq!(state.tax_rate.get(), fields.u16());
+    assert_eq!(state.reflection_rate.get(), fields.u16());
+    assert_eq!(state.staking_rate.get(), fields.u16());
+    assert_eq!(state.lottery_rate.get(), fields.u16());
+    assert_eq!(state.total_staked.get(), fields.u64());
+    assert_eq!(state.total_reflection_distributed.get(), fields.u64());
+    assert_eq!(state.last_lottery_draw.get(), fields.i64());
+    assert_eq!(state.lottery_interval.get(), fields.i64());
+    assert_eq!(state.total_tax_collected.get(), fields.u64());
+    assert_eq!(state.reflection_index.get(), fields.u128());
+    assert_eq!(state.max_transfer_bps.get(), fields.u16());
+    assert_eq!(state.max_wallet_bps.get(), fields.u16());
+    for exempt in state.limit_exempt {
+        assert_eq!(exempt, fields.pubkey());
+    }
+    assert_eq!(state.trading_enabled_at.get(), fields.i64());
+    assert_eq!(state.launch_window.get(), fields.i64());
+    assert_eq!(state.buy_cooldown.get(), fields.i64());
+    assert_eq!(state.sniper_tax_rate.get(), fields.u16());
+    assert_eq!(state.liquidity_rate.get(), fields.u16());
+    assert_eq!(state.liquidity_vault, fields.pubkey());
+    assert_eq!(state.amm_program, fields.pubkey());
+    assert_eq!(state.amm_pool, fields.pubkey());
+    assert_eq!(state.liquify_threshold.get(), fields.u64());
+    assert_eq!(state.total_liquified.get(), fields.u64());
+    assert_eq!(state.treasury_rate.get(), fields.u16());
+    assert_eq!(state.treasury_vault, fields.pubkey());
+    assert_eq!(state.treasury_quote_vault, fields.pubkey());
+    assert_eq!(state.keeper, fields.pubkey());
+    assert_eq!(state.buyback_max_per_call.get(), fields.u64());
+    assert_eq!(state.total_burned.get(), fields.u64());
+    assert_eq!(state.paused.get(), fields.flag());
+    let undistributed = state.undistributed.get();
+    assert_eq!(undistributed.reflection, fields.u64());
+    assert_eq!(undistributed.staking, fields.u64());
+    assert_eq!(undistributed.lottery, fields.u64());
+    assert_eq!(undistributed.liquidity, fields.u64());
+    assert_eq!(undistributed.treasury, fields.u64());
+    assert_eq!(state.reflection_bonus.get(), fields.u64());
+    assert_eq!(state.liquify_max_slippage_bps.get(), fields.u16());
+    assert!(fields.0.is_empty());
+
+    let mut packed = vec![0; CodoxTokenState::LEN];
+    state.pack_into_slice(&mut packed);
+    assert_eq!(packed, data);
+}
+
+#[test]
+fn holder_state_layout() {
+    let data = account_data(HolderState::LEN);
+    let holder_state = HolderState::unpack_unchecked(&data).unwrap();
+    let mut fields = Fields(&data);
+
+    assert_eq!(holder_state.holder, fields.pubkey());
+    assert_eq!(holder_state.last_reflection_claim.get(), fields.i64());
+    assert_eq!(holder_state.staked_amount.get(), fields.u64());
+    assert_eq!(holder_state.stake_time.get(), fields.i64());
+    assert_eq!(holder_state.total_claimed.get(), fields.u64());
+    assert_eq!(holder_state.holding_multiplier.get(), fields.u16());
+    assert_eq!(holder_state.reflection_index_checkpoint.get(), fields.u128());
+    assert_eq!(holder_state.last_buy_time.get(), fields.i64());
+    assert_eq!(holder_state.reflection_balance.get(), fields.u64());
+    assert_eq!(holder_state.unpaid_reflection.get(), fields.u128());
+    assert_eq!(holder_state.lottery_round_start.get(), fields.i64());
+    assert_eq!(holder_state.lottery_received.get(), fields.u64());
+    assert!(fields.0.is_empty());
+
+    let mut packed = vec![0; HolderState::LEN];
+    holder_state.pack_into_slice(&mut packed);
+    assert_eq!(packed, data);
+}
@@ -0,0 +1,424 @@
+//! Taxed transfers, staking and reflection claims end to end, checking balances
+//! and the decoded program state after each step.
+#![cfg(not(feature = "token-2022"))]
+
+mod common;
+
+use codox::{instruction, preview::TransferTaxPreview, TaxSplit};
+#[cfg(feature = "reflection")]
+use codox::{preview::ClaimPreview, reflection_reward, REFLECTION_INDEX_SCALE};
+#[cfg(all(feature = "staking", feature = "reflection"))]
+use codox::{error::CodoxError, time_multiplier};
+#[cfg(any(feature = "burn", all(feature = "staking", feature = "reflection")))]
+use codox::ConfigUpdate;
+#[cfg(all(feature = "reflection", feature = "staking", feature = "lottery"))]
+use codox::MAX_LIMIT_EXEMPT;
+use common::*;
+#[cfg(all(feature = "reflection", feature = "staking", feature = "lottery"))]
+use solana_sdk::pubkey::Pubkey;
+#[cfg(feature = "reflection")]
+use solana_sdk::program_pack::Pack;
+
+const SUPPLY: u64 = 1_000_000_000;
+
+#[cfg(all(feature = "staking", feature = "reflection"))]
+#[tokio::test]
+async fn initialize_transfer_stake_claim() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+
+    let state = env.state().await;
+    assert!(state.is_initialized.get());
+    assert_eq!(state.authority, payer);
+    assert_eq!(state.token_mint, env.codox.mint);
+    assert_eq!(state.tax_vault, env.codox.tax_vault);
+    assert_eq!(state.reflection_pool, env.codox.reflection_pool);
+    assert_eq!(state.staking_pool, env.codox.staking_pool);
+    assert_eq!(state.lottery_pool, env.codox.lottery_pool);
+    assert_eq!(state.liquidity_vault, env.codox.liquidity_vault);
+    assert_eq!(state.treasury_vault, env.codox.treasury_vault);
+    assert_eq!(
+        [
+            state.tax_rate.get(),
+            state.reflection_rate.get(),
+            state.staking_rate.get(),
+            state.lottery_rate.get(),
+            state.liquidity_rate.get(),
+            state.treasury_rate.get(),
+        ],
+        [TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE]
+    );
+    assert_eq!(state.trading_enabled_at.get(), 0);
+    assert_eq!(state.lottery_interval.get(), DAY);
+    assert_eq!(state.reflection_index.get(), 0);
+    assert!(!state.paused.get());
+
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.register_holder(&alice).await.unwrap();
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.holder, alice.pubkey());
+    assert_eq!(alice_state.staked_amount.get(), 0);
+    assert_eq!(alice_state.holding_multiplier.get(), 100);
+    assert_eq!(alice_state.reflection_index_checkpoint.get(), 0);
+    assert_eq!(alice_state.reflection_balance.get(), SUPPLY);
+
+    // A 5% tax into the tax vault, recorded as split 200/100/100/50/50
+    let amount = 100_000_000;
+    let tax = amount * TAX_RATE as u64 / 10000;
+    let split = TaxSplit {
+        reflection: 2_000_000,
+        staking: 1_000_000,
+        lottery: 1_000_000,
+        liquidity: 500_000,
+        treasury: 500_000,
+    };
+    env.transfer(&alice, &bob, amount).await.unwrap();
+    assert_eq!(env.balance(&alice.token).await, SUPPLY - amount);
+    assert_eq!(env.balance(&bob.token).await, amount - tax);
+    assert_eq!(env.balance(&env.codox.tax_vault.clone()).await, tax);
+    assert_eq!(env.pool_balances().await, [0; 5]);
+    let state = env.state().await;
+    assert_eq!(state.total_tax_collected.get(), tax);
+    assert_eq!(state.undistributed.get(), split);
+    assert_eq!(state.reflection_index.get(), 0);
+    assert_eq!(env.holder_state(&alice.pubkey()).await.reflection_balance.get(), SUPPLY - amount);
+
+    // Distributing moves each share to its pool and accrues the reflection, less the bonus allocation
+    env.distribute_tax_vault().await.unwrap();
+    assert_eq!(env.balance(&env.codox.tax_vault.clone()).await, 0);
+    assert_eq!(env.pool_balances().await, [2_000_000, 1_000_000, 1_000_000, 500_000, 500_000]);
+    let state = env.state().await;
+    assert_eq!(state.undistributed.get(), TaxSplit::default());
+    assert_eq!(state.total_reflection_distributed.get(), 2_000_000);
+    assert_eq!(state.reflection_index.get(), 1_600_000 * REFLECTION_INDEX_SCALE / SUPPLY as u128);
+    assert_eq!(state.reflection_bonus.get(), 400_000);
+
+    // Staking creates the staker's holder state
+    let stake = instruction::stake(&env.codox, &bob.pubkey(), &bob.token, 10_000_000, &[]);
+    env.process(&[stake], &[&bob.wallet]).await.unwrap();
+    assert_eq!(env.balance(&bob.token).await, amount - tax - 10_000_000);
+    assert_eq!(env.pool_balances().await[1], 11_000_000);
+    let bob_state = env.holder_state(&bob.pubkey()).await;
+    assert_eq!(bob_state.holder, bob.pubkey());
+    assert_eq!(bob_state.staked_amount.get(), 10_000_000);
+    assert_eq!(bob_state.reflection_index_checkpoint.get(), state.reflection_index.get());
+    assert_eq!(env.state().await.total_staked.get(), 10_000_000);
+
+    // Reflection accrues on Alice's balance since registering, boosted by the days held
+    env.advance_clock(5 * DAY).await;
+    let now = env.now().await;
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    let multiplier = time_multiplier(now - alice_state.last_reflection_claim.get());
+    assert_eq!(multiplier, 110);
+    let reward = reflection_reward(SUPPLY - amount, state.reflection_index.get(), multiplier);
+    assert_eq!(reward, 1_584_000);
+    let preview: ClaimPreview = env.simulate(instruction::preview_claim(&env.codox, &alice.pubkey(), &alice.token)).await;
+    assert_eq!(preview, ClaimPreview { reward, multiplier });
+
+    let claim = instruction::claim_reflection(&env.codox, &alice.pubkey(), &alice.token, &[]);
+    env.process(&[claim], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.balance(&alice.token).await, SUPPLY - amount + reward);
+    assert_eq!(env.pool_balances().await[0], 2_000_000 - reward);
+    // The multiplier's 10% came out of the bonus allocation
+    assert_eq!(env.state().await.reflection_bonus.get(), 400_000 - 144_000);
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.total_claimed.get(), reward);
+    assert_eq!(alice_state.holding_multiplier.get(), multiplier);
+    assert_eq!(alice_state.last_reflection_claim.get(), now);
+    assert_eq!(alice_state.reflection_index_checkpoint.get(), state.reflection_index.get());
+    assert_eq!(alice_state.reflection_balance.get(), SUPPLY - amount + reward);
+    assert_eq!(alice_state.unpaid_reflection.get(), 0);
+
+    // Nothing more until the index moves again
+    let preview: ClaimPreview = env.simulate(instruction::preview_claim(&env.codox, &alice.pubkey(), &alice.token)).await;
+    assert_eq!(preview.reward, 0);
+
+    let unstake = instruction::unstake(&env.codox, &bob.pubkey(), &bob.token, 4_000_000, &[]);
+    env.process(&[unstake], &[&bob.wallet]).await.unwrap();
+    assert_eq!(env.balance(&bob.token).await, amount - tax - 6_000_000);
+    assert_eq!(env.pool_balances().await[1], 7_000_000);
+    assert_eq!(env.holder_state(&bob.pubkey()).await.staked_amount.get(), 6_000_000);
+    assert_eq!(env.state().await.total_staked.get(), 6_000_000);
+}
+
+#[cfg(feature = "reflection")]
+#[tokio::test]
+async fn reflection_settles_before_balances_change() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    let carol = env.new_holder(0).await;
+    env.register_holder(&alice).await.unwrap();
+    env.register_holder(&bob).await.unwrap();
+    env.transfer(&alice, &carol, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    let index = env.state().await.reflection_index.get();
+    let earned = reflection_reward(SUPPLY - 100_000_000, index, 100);
+    assert!(earned > 0);
+
+    // Buying just before claiming earns nothing on the tokens bought
+    env.transfer(&alice, &bob, 500_000_000).await.unwrap();
+    let preview: ClaimPreview = env.simulate(instruction::preview_claim(&env.codox, &bob.pubkey(), &bob.token)).await;
+    assert_eq!(preview.reward, 0);
+    let claim = instruction::claim_reflection(&env.codox, &bob.pubkey(), &bob.token, &[]);
+    env.process(&[claim], &[&bob.wallet]).await.unwrap();
+    assert_eq!(env.holder_state(&bob.pubkey()).await.total_claimed.get(), 0);
+
+    // The seller keeps what the sold tokens earned while held
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.reflection_balance.get(), SUPPLY - 600_000_000);
+    assert_eq!(alice_state.unpaid_reflection.get() / REFLECTION_INDEX_SCALE, earned as u128);
+    let preview: ClaimPreview = env.simulate(instruction::preview_claim(&env.codox, &alice.pubkey(), &alice.token)).await;
+    assert_eq!(preview.reward, earned);
+}
+
+#[cfg(feature = "reflection")]
+#[tokio::test]
+async fn unpaid_reflection_stays_owed() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.register_holder(&alice).await.unwrap();
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    let state = env.state().await;
+    let owed = reflection_reward(SUPPLY - 100_000_000, state.reflection_index.get(), 100);
+
+    // A pool short of what Alice is owed, beyond the bonus allocation
+    let reflection_pool = env.codox.reflection_pool;
+    let mut account = env.account(&reflection_pool).await.unwrap();
+    let mut pool = spl_token::state::Account::unpack(&account.data).unwrap();
+    let short = owed / 4;
+    pool.amount = state.reflection_bonus.get() + short;
+    pool.pack_into_slice(&mut account.data);
+    env.set_account(&reflection_pool, account);
+
+    let claim = instruction::claim_reflection(&env.codox, &alice.pubkey(), &alice.token, &[]);
+    env.process(&[claim], &[&alice.wallet]).await.unwrap();
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.total_claimed.get(), short);
+    assert_eq!(alice_state.unpaid_reflection.get() / REFLECTION_INDEX_SCALE, (owed - short) as u128);
+
+    // The rest is paid once the pool has it
+    let mint = env.codox.mint;
+    env.mint_to(&mint, &reflection_pool, owed).await;
+    env.advance_clock(1).await;
+    let claim = instruction::claim_reflection(&env.codox, &alice.pubkey(), &alice.token, &[]);
+    env.process(&[claim], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.holder_state(&alice.pubkey()).await.total_claimed.get(), owed);
+}
+
+#[cfg(all(feature = "reflection", feature = "lottery"))]
+#[tokio::test]
+async fn lottery_deposits_earn_no_reflection() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.register_holder(&alice).await.unwrap();
+    env.initialize_lottery(true).await.unwrap();
+
+    // Deposited tokens leave the balance reflection accrues on while they're locked
+    let principal = 400_000_000;
+    let deposit = instruction::deposit_to_lottery(&env.codox, &alice.pubkey(), &alice.token, principal, &[]);
+    env.process(&[deposit], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.holder_state(&alice.pubkey()).await.reflection_balance.get(), SUPPLY - principal);
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    let index = env.state().await.reflection_index.get();
+    let held = SUPPLY - principal - 100_000_000;
+
+    // Once the round is drawn the principal comes back and earns again
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 0).await.unwrap();
+    let withdraw = instruction::withdraw_from_lottery(&env.codox, &alice.pubkey(), &alice.token, principal, &[]);
+    env.process(&[withdraw], &[&alice.wallet]).await.unwrap();
+    let alice_state = env.holder_state(&alice.pubkey()).await;
+    assert_eq!(alice_state.reflection_balance.get(), SUPPLY - 100_000_000);
+    assert_eq!(alice_state.unpaid_reflection.get() / REFLECTION_INDEX_SCALE, reflection_reward(held, index, 100) as u128);
+
+    // The claim pays only for the tokens held in the wallet meanwhile
+    let preview: ClaimPreview = env.simulate(instruction::preview_claim(&env.codox, &alice.pubkey(), &alice.token)).await;
+    assert!(preview.reward > 0);
+    assert_eq!(preview.reward, reflection_reward(held, index, preview.multiplier));
+    let balance = env.balance(&alice.token).await;
+    let claim = instruction::claim_reflection(&env.codox, &alice.pubkey(), &alice.token, &[]);
+    env.process(&[claim], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.balance(&alice.token).await, balance + preview.reward);
+}
+
+// The shares below are the rates of a build with every mechanism
+#[cfg(all(feature = "reflection", feature = "staking", feature = "lottery"))]
+#[tokio::test]
+async fn distribute_tax_vault() {
+    let mut env = TestEnv::start(Options {
+        open_trading: false,
+        ..Options::default()
+    })
+    .await;
+    // Alice stands in for the DEX pool, so transfers to Bob are launch window buys
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    let mut limit_exempt = [Pubkey::default(); MAX_LIMIT_EXEMPT];
+    limit_exempt[0] = alice.pubkey();
+    let now = env.now().await;
+    env.update_config(ConfigUpdate {
+        limit_exempt: Some(limit_exempt),
+        trading_enabled_at: Some(now),
+        launch_window: Some(DAY),
+        sniper_tax_rate: Some(3000),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    env.register_holder(&bob).await.unwrap();
+    let (mint, tax_vault) = (env.codox.mint, env.codox.tax_vault);
+    env.mint_to(&mint, &tax_vault, 1_000_000).await;
+    let supply = env.supply().await;
+
+    // Tokens sent to the vault outside a transfer are split by the rates
+    env.distribute_tax_vault().await.unwrap();
+    assert_eq!(env.balance(&tax_vault).await, 0);
+    assert_eq!(env.pool_balances().await, [400_000, 200_000, 200_000, 100_000, 100_000]);
+    let state = env.state().await;
+    assert_eq!(state.total_reflection_distributed.get(), 400_000);
+    assert_eq!(state.reflection_index.get(), 320_000 * REFLECTION_INDEX_SCALE / supply as u128);
+    assert_eq!(state.reflection_bonus.get(), 80_000);
+
+    // The sniper tax beyond the normal tax is all lottery, so the recorded shares
+    // don't follow the rates; tokens on top of them still do
+    let buy = instruction::transfer(&env.codox, &alice.pubkey(), &alice.token, &bob.token, &bob.pubkey(), 10_000_000);
+    env.process(&[buy], &[&alice.wallet]).await.unwrap();
+    let split = TaxSplit {
+        reflection: 200_000,
+        staking: 100_000,
+        lottery: 2_600_000,
+        liquidity: 50_000,
+        treasury: 50_000,
+    };
+    assert_eq!(env.state().await.undistributed.get(), split);
+    env.mint_to(&mint, &tax_vault, 10_000).await;
+    env.distribute_tax_vault().await.unwrap();
+    assert_eq!(env.balance(&tax_vault).await, 0);
+    assert_eq!(env.pool_balances().await, [604_000, 302_000, 2_802_000, 151_000, 151_000]);
+    assert_eq!(env.state().await.undistributed.get(), TaxSplit::default());
+}
+
+#[tokio::test]
+async fn preview_transfer_tax_matches_transfer() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+
+    let amount = 12_345_678;
+    let preview: TransferTaxPreview =
+        env.simulate(instruction::preview_transfer_tax(&env.codox, &alice.token, &bob.token, amount)).await;
+    let tax_amount = amount * TAX_RATE as u64 / 10000;
+    let share = |rate: u16| tax_amount * rate as u64 / TAX_RATE as u64;
+    let mut split = TaxSplit {
+        reflection: share(REFLECTION_RATE),
+        staking: share(STAKING_RATE),
+        lottery: share(LOTTERY_RATE),
+        liquidity: share(LIQUIDITY_RATE),
+        treasury: share(TREASURY_RATE),
+    };
+    // Rounding dust goes to the lottery, or the treasury without it, or the
+    // liquidity vault without burn either
+    let dust = tax_amount - split.total();
+    if cfg!(feature = "lottery") {
+        split.lottery += dust;
+    } else if cfg!(feature = "burn") {
+        split.treasury += dust;
+    } else {
+        split.liquidity += dust;
+    }
+    assert_eq!(
+        preview,
+        TransferTaxPreview {
+            net_amount: amount - tax_amount,
+            tax_amount,
+            split,
+            launch_buy: false,
+        }
+    );
+
+    env.transfer(&alice, &bob, amount).await.unwrap();
+    assert_eq!(env.balance(&bob.token).await, preview.net_amount);
+    assert_eq!(env.balance(&env.codox.tax_vault.clone()).await, preview.tax_amount);
+    assert_eq!(env.state().await.undistributed.get(), preview.split);
+}
+
+#[cfg(all(feature = "staking", feature = "reflection"))]
+#[tokio::test]
+async fn pause_stops_holder_transfers_but_not_payouts() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.register_holder(&bob).await.unwrap();
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+
+    env.update_config(ConfigUpdate {
+        paused: Some(true),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    assert!(env.state().await.paused.get());
+    assert_codox_error(env.transfer(&alice, &bob, 1_000).await, CodoxError::ProgramPaused);
+    let stake = instruction::stake(&env.codox, &alice.pubkey(), &alice.token, 1_000, &[]);
+    assert_codox_error(env.process(&[stake], &[&alice.wallet]).await, CodoxError::ProgramPaused);
+
+    // Claims pay out of a pool and keep working
+    let balance = env.balance(&bob.token).await;
+    let claim = instruction::claim_reflection(&env.codox, &bob.pubkey(), &bob.token, &[]);
+    env.process(&[claim], &[&bob.wallet]).await.unwrap();
+    assert!(env.balance(&bob.token).await > balance);
+
+    env.update_config(ConfigUpdate {
+        paused: Some(false),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    env.transfer(&alice, &bob, 1_000).await.unwrap();
+}
+
+#[cfg(feature = "burn")]
+#[tokio::test]
+async fn buyback_burns_treasury_share() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    let treasury_share = env.pool_balances().await[4];
+    assert!(treasury_share > 0);
+
+    let reserve = 100_000_000;
+    let amm = env.create_amm(reserve, reserve).await;
+    let pool_authority = env.codox.pool_authority();
+    let treasury_quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+    env.mint_to(&amm.quote_mint, &treasury_quote_vault, 1_000_000).await;
+    env.update_config(ConfigUpdate {
+        amm_program: Some(amm.program_id),
+        amm_pool: Some(amm.pool),
+        treasury_quote_vault: Some(treasury_quote_vault),
+        buyback_max_per_call: Some(1_000_000),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    let supply = env.supply().await;
+
+    // The treasury's tax share is burned along with what's bought instead of piling up
+    let bought = codox::amm::swap_amount_out(1_000_000, reserve, reserve);
+    let payer = env.payer();
+    let buyback =
+        instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1_000_000, bought);
+    env.process(&[buyback], &[]).await.unwrap();
+    assert_eq!(env.pool_balances().await[4], 0);
+    assert_eq!(env.supply().await, supply - treasury_share - bought);
+    assert_eq!(env.state().await.total_burned.get(), treasury_share + bought);
+}
@@ -0,0 +1,271 @@
+//! Lottery rounds end to end: ticket pages, snapshots, the draw, prize claims,
+//! expiry and the deposit mode.
+#![cfg(all(feature = "lottery", not(feature = "token-2022")))]
+
+mod common;
+
+use codox::{
+    error::CodoxError,
+    instruction,
+    lottery::{draw_winners, tier_prize, JACKPOT_SLOT},
+    preview::LotteryOddsPreview,
+};
+use common::*;
+use solana_sdk::{
+    hash::Hash,
+    keccak,
+    pubkey::Pubkey,
+    slot_hashes::{SlotHashes, MAX_ENTRIES},
+};
+
+const SUPPLY: u64 = 1_000_000_000;
+
+#[tokio::test]
+async fn balance_lottery_round() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+    // Close to even odds, so the first two tiers draw different players
+    let alice = env.new_holder(SUPPLY / 2).await;
+    let bob = env.new_holder(SUPPLY / 2).await;
+    // Holders registered before the round take part in it
+    env.register_holder(&alice).await.unwrap();
+    env.register_holder(&bob).await.unwrap();
+    // Fund the lottery pool through the tax
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    let prize_pool = env.pool_balances().await[2];
+    assert_eq!(prize_pool, 1_000_000);
+
+    env.initialize_lottery(false).await.unwrap();
+    let lottery = env.lottery().await;
+    assert!(lottery.is_initialized);
+    assert_eq!(lottery.round, 0);
+    assert_eq!(lottery.prize_tiers, PRIZE_TIERS);
+    assert_eq!(lottery.claim_period, CLAIM_PERIOD);
+    assert!(!lottery.deposit_mode);
+
+    env.open_ticket_page(0, 0).await.unwrap();
+    let page = env.ticket_page(0, 0).await;
+    assert_eq!((page.round, page.page, page.count, page.payer), (0, 0, 0, payer));
+    assert_eq!(env.lottery().await.ticket_pages, 1);
+
+    env.snapshot_balances(0, 0, &[&alice, &bob]).await.unwrap();
+    let alice_tickets = env.balance(&alice.token).await;
+    let bob_tickets = env.balance(&bob.token).await;
+    let total_tickets = alice_tickets + bob_tickets;
+    let lottery = env.lottery().await;
+    assert_eq!(lottery.total_tickets, total_tickets);
+    let page = env.ticket_page(0, 0).await;
+    assert_eq!((page.count, page.tickets), (2, total_tickets));
+
+    let tier_prizes = PRIZE_TIERS.map(|tier| tier_prize(prize_pool, tier));
+    let odds: LotteryOddsPreview =
+        env.simulate(instruction::preview_lottery_odds(&env.codox, &alice.pubkey(), 0, 1)).await;
+    assert_eq!(
+        odds,
+        LotteryOddsPreview {
+            round: 0,
+            tickets: alice_tickets,
+            total_tickets,
+            tier_prizes,
+            jackpot: 0,
+            jackpot_chance: 0,
+        }
+    );
+
+    // Closing the round once the interval has passed fixes the slot whose hash draws it
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    let now = env.now().await;
+    let round = env.lottery_round(0).await;
+    assert_eq!(round.round, 0);
+    assert_eq!(round.drawn_at, now);
+    assert_eq!(round.participants, 2);
+    assert_eq!(round.prize_pool, prize_pool);
+    assert_eq!(round.claim_deadline, now + CLAIM_PERIOD);
+    // The draw slot's fixed hash fixes the seed, and with it the tickets drawn
+    let seed = keccak::hashv(&[DRAW_HASH.as_ref(), env.codox.state.as_ref(), &0u64.to_le_bytes()]).to_bytes();
+    assert_eq!(round.seed, seed);
+    let expected = draw_winners(&seed, total_tickets, prize_pool, &PRIZE_TIERS, &mut 0, 0, |ticket| {
+        Ok(if ticket < alice_tickets { alice.pubkey() } else { bob.pubkey() })
+    })
+    .unwrap();
+    assert_eq!((round.winners, round.prizes), expected);
+    // Two players fill the first two tiers; the third can't draw a new winner and
+    // its prize joins the remainder in the jackpot
+    let mut winners = [round.winners[0], round.winners[1]];
+    winners.sort();
+    let mut players = [alice.pubkey(), bob.pubkey()];
+    players.sort();
+    assert_eq!(winners, players);
+    assert_eq!(round.prizes, [tier_prizes[0], tier_prizes[1], 0, 0]);
+    assert_eq!(round.winners[2], Pubkey::default());
+    assert_eq!(round.winners[JACKPOT_SLOT], Pubkey::default());
+    let jackpot = prize_pool - tier_prizes[0] - tier_prizes[1];
+    assert_eq!(round.jackpot, jackpot);
+
+    let lottery = env.lottery().await;
+    assert_eq!(lottery.round, 1);
+    assert_eq!(lottery.jackpot, jackpot);
+    assert_eq!(lottery.prizes_owed, tier_prizes[0] + tier_prizes[1]);
+    assert_eq!((lottery.ticket_pages, lottery.total_tickets), (0, 0));
+    assert_eq!(lottery.draw_slot, 0);
+    assert_eq!(env.state().await.last_lottery_draw.get(), now);
+
+    // The first tier winner claims
+    let winner = if round.winners[0] == alice.pubkey() { &alice } else { &bob };
+    let balance = env.balance(&winner.token).await;
+    let claim = instruction::claim_lottery_prize(&env.codox, &winner.pubkey(), &winner.token, 0, 0, &[]);
+    env.process(&[claim], &[&winner.wallet]).await.unwrap();
+    assert_eq!(env.balance(&winner.token).await, balance + tier_prizes[0]);
+    assert_eq!(env.pool_balances().await[2], prize_pool - tier_prizes[0]);
+    assert!(env.lottery_round(0).await.is_paid(0));
+    assert_eq!(env.lottery().await.prizes_owed, tier_prizes[1]);
+
+    // The drawn round's page can go, refunding its rent
+    let page_address = env.codox.ticket_page(0, 0);
+    env.process(&[instruction::close_ticket_page(&env.codox, &payer, 0, 0)], &[])
+        .await
+        .unwrap();
+    assert!(env.account(&page_address).await.is_none());
+
+    // The second prize goes unclaimed and returns to the jackpot
+    env.advance_clock(CLAIM_PERIOD + 1).await;
+    env.process(&[instruction::expire_lottery_prizes(&env.codox, 0)], &[])
+        .await
+        .unwrap();
+    let round = env.lottery_round(0).await;
+    assert!(round.expired);
+    assert!(!round.is_paid(1));
+    let lottery = env.lottery().await;
+    assert_eq!(lottery.prizes_owed, 0);
+    assert_eq!(lottery.jackpot, jackpot + tier_prizes[1]);
+
+    let loser = if winner.pubkey() == alice.pubkey() { &bob } else { &alice };
+    let claim = instruction::claim_lottery_prize(&env.codox, &loser.pubkey(), &loser.token, 0, 1, &[]);
+    assert_codox_error(env.process(&[claim], &[&loser.wallet]).await, CodoxError::PrizeUnavailable);
+}
+
+#[tokio::test]
+async fn moved_tokens_are_entered_once() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let holders = [env.new_holder(0).await, env.new_holder(0).await, env.new_holder(0).await];
+    let [first, second, late] = &holders;
+    let mint = env.codox.mint;
+    env.mint_to(&mint, &first.token, 1_000_000).await;
+    env.mint_to(&mint, &late.token, 1_000).await;
+    env.register_holder(first).await.unwrap();
+    env.register_holder(second).await.unwrap();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+
+    // Tokens moved on after their holder was recorded earn the recipient nothing
+    env.snapshot_balances(0, 0, &[first]).await.unwrap();
+    env.transfer(first, second, 600_000).await.unwrap();
+    assert!(env.balance(&second.token).await > 0);
+    env.snapshot_balances(0, 0, &[second]).await.unwrap();
+    let page = env.ticket_page(0, 0).await;
+    assert_eq!((page.count, page.tickets), (1, 1_000_000));
+    assert_eq!(env.lottery().await.total_tickets, 1_000_000);
+
+    // Nor do the tokens of a holder registering during the round
+    env.advance_clock(1).await;
+    env.register_holder(late).await.unwrap();
+    env.snapshot_balances(0, 0, &[late]).await.unwrap();
+    assert_eq!(env.ticket_page(0, 0).await.count, 1);
+
+    // Next round, every balance counts in full
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    env.open_ticket_page(1, 0).await.unwrap();
+    env.snapshot_balances(1, 0, &[first, second, late]).await.unwrap();
+    let balances = env.balance(&first.token).await + env.balance(&second.token).await + 1_000;
+    assert_eq!(env.ticket_page(1, 0).await.count, 3);
+    assert_eq!(env.lottery().await.total_tickets, balances);
+}
+
+#[tokio::test]
+async fn deposit_lottery_round() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(10_000_000).await;
+    env.initialize_lottery(true).await.unwrap();
+    assert!(env.lottery().await.deposit_mode);
+
+    let deposit = instruction::deposit_to_lottery(&env.codox, &alice.pubkey(), &alice.token, 4_000_000, &[]);
+    env.process(&[deposit], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.balance(&alice.token).await, 6_000_000);
+    assert_eq!(env.pool_balances().await[2], 4_000_000);
+    let deposit = env.lottery_deposit(&alice.pubkey()).await;
+    assert_eq!((deposit.owner, deposit.amount, deposit.locked_round), (alice.pubkey(), 4_000_000, 0));
+    assert_eq!(env.lottery().await.total_deposits, 4_000_000);
+
+    env.open_ticket_page(0, 0).await.unwrap();
+    let payer = env.payer();
+    env.process(&[instruction::snapshot_deposits(&env.codox, &payer, 0, 0, &[alice.pubkey()])], &[])
+        .await
+        .unwrap();
+    assert_eq!(env.lottery().await.total_tickets, 4_000_000);
+    // Each deposit is entered once a round
+    let deposit = env.lottery_deposit(&alice.pubkey()).await;
+    assert_eq!(deposit.entered_round_start, env.state().await.last_lottery_draw.get());
+    let snapshot = instruction::snapshot_deposits(&env.codox, &alice.pubkey(), 0, 0, &[alice.pubkey()]);
+    env.process(&[snapshot], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.ticket_page(0, 0).await.count, 1);
+    assert_eq!(env.lottery().await.total_tickets, 4_000_000);
+
+    // Principal stays put until the round is drawn
+    let withdraw = instruction::withdraw_from_lottery(&env.codox, &alice.pubkey(), &alice.token, 4_000_000, &[]);
+    assert_codox_error(env.process(std::slice::from_ref(&withdraw), &[&alice.wallet]).await, CodoxError::DepositLocked);
+
+    // Deposits aren't prizes, so with no other inflow nothing is won
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    let round = env.lottery_round(0).await;
+    assert_eq!(round.prize_pool, 0);
+    assert_eq!(round.prizes, [0; 4]);
+
+    env.process(&[withdraw], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.balance(&alice.token).await, 10_000_000);
+    assert_eq!(env.lottery_deposit(&alice.pubkey()).await.amount, 0);
+    assert_eq!(env.lottery().await.total_deposits, 0);
+}
+
+#[tokio::test]
+async fn draw_uses_the_draw_slot_hash() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+    env.advance_clock(DAY).await;
+    let close = instruction::close_lottery_round(&env.codox, &payer);
+    env.process(&[close], &[]).await.unwrap();
+
+    // The draw slot was skipped, so the first slot produced after it draws the round
+    let draw_slot = env.lottery().await.draw_slot;
+    let hash = Hash::new_unique();
+    env.context.set_sysvar(&SlotHashes::new(&[
+        (draw_slot + 3, Hash::new_unique()),
+        (draw_slot + 2, hash),
+        (draw_slot - 1, Hash::new_unique()),
+    ]));
+    env.draw_lottery(0, 1).await.unwrap();
+    let seed = keccak::hashv(&[hash.as_ref(), env.codox.state.as_ref(), &0u64.to_le_bytes()]).to_bytes();
+    assert_eq!(env.lottery_round(0).await.seed, seed);
+
+    // Once the hashes no longer reach back to the draw slot, the round is closed again
+    env.advance_clock(DAY).await;
+    let close = instruction::close_lottery_round(&env.codox, &payer);
+    env.process(std::slice::from_ref(&close), &[]).await.unwrap();
+    let draw_slot = env.lottery().await.draw_slot;
+    let hashes: Vec<_> = (1..=MAX_ENTRIES as u64).map(|slot| (draw_slot + slot, Hash::new_unique())).collect();
+    env.context.set_sysvar(&SlotHashes::new(&hashes));
+    assert_codox_error(env.draw_lottery(1, 0).await, CodoxError::DrawSlotExpired);
+    assert_codox_error(env.process(std::slice::from_ref(&close), &[]).await, CodoxError::LotteryRoundClosed);
+
+    env.advance_past_slot(draw_slot + MAX_ENTRIES as u64).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(1, 0).await.unwrap();
+}
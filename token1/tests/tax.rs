@@ -0,0 +1,160 @@
+//! Property tests for the transfer tax math: no rate configuration or amount
+//! loses or creates tokens at the rounding edges.
+
+use codox::tax::{bps_of, TaxRates, MAX_TAX_RATE};
+use proptest::prelude::*;
+
+/// `rates` with the shares of mechanisms this build leaves out moved to the liquidity vault
+fn built_only(rates: TaxRates) -> TaxRates {
+    let mut rates = rates;
+    for (built, rate) in [
+        (cfg!(feature = "reflection"), &mut rates.reflection_rate),
+        (cfg!(feature = "staking"), &mut rates.staking_rate),
+        (cfg!(feature = "lottery"), &mut rates.lottery_rate),
+        (cfg!(feature = "burn"), &mut rates.treasury_rate),
+    ] {
+        if !built {
+            rates.liquidity_rate += std::mem::take(rate);
+        }
+    }
+    rates
+}
+
+/// Valid rates: a tax of at most `MAX_TAX_RATE` cut into five shares at random
+/// points, and any sniper tax
+fn tax_rates() -> impl Strategy<Value = TaxRates> {
+    (0..=MAX_TAX_RATE, 0..=10000u16)
+        .prop_flat_map(|(tax_rate, sniper_tax_rate)| {
+            (Just(tax_rate), Just(sniper_tax_rate), proptest::collection::vec(0..=tax_rate, 4))
+        })
+        .prop_map(|(tax_rate, sniper_tax_rate, mut cuts)| {
+            cuts.sort_unstable();
+            built_only(TaxRates {
+                tax_rate,
+                reflection_rate: cuts[0],
+                staking_rate: cuts[1] - cuts[0],
+                lottery_rate: cuts[2] - cuts[1],
+                liquidity_rate: cuts[3] - cuts[2],
+                treasury_rate: tax_rate - cuts[3],
+                sniper_tax_rate,
+            })
+        })
+}
+
+/// Amounts across the whole range, with the edges drawn often
+fn amount() -> impl Strategy<Value = u64> {
+    prop_oneof![Just(0), Just(1), Just(u64::MAX), Just(u64::MAX - 1), 0..10_000u64, any::<u64>()]
+}
+
+proptest! {
+    #[test]
+    fn transfer_is_conserved(rates in tax_rates(), amount in amount(), launch_buy in any::<bool>()) {
+        prop_assert!(rates.is_valid());
+        let (tax_amount, split) = rates.transfer_tax(amount, launch_buy);
+        prop_assert!(tax_amount <= amount);
+        prop_assert_eq!(split.total(), tax_amount);
+        prop_assert_eq!((amount - tax_amount) as u128 + split.total() as u128, amount as u128);
+    }
+
+    #[test]
+    fn tax_is_the_configured_rate(rates in tax_rates(), amount in amount(), launch_buy in any::<bool>()) {
+        let (tax_amount, _) = rates.transfer_tax(amount, launch_buy);
+        let expected = if launch_buy {
+            bps_of(amount, rates.tax_rate).max(bps_of(amount, rates.sniper_tax_rate))
+        } else {
+            bps_of(amount, rates.tax_rate)
+        };
+        prop_assert_eq!(tax_amount, expected);
+    }
+
+    #[test]
+    fn split_is_conserved(rates in tax_rates(), tax_amount in amount()) {
+        prop_assert_eq!(rates.split(tax_amount).total(), tax_amount);
+    }
+
+    #[test]
+    fn shares_round_down_and_dust_goes_to_the_lottery(rates in tax_rates(), tax_amount in amount()) {
+        prop_assume!(rates.tax_rate > 0);
+        let split = rates.split(tax_amount);
+        let exact = |rate: u16| tax_amount as u128 * rate as u128;
+        let tax_rate = rates.tax_rate as u128;
+        let shares = [
+            (split.reflection, rates.reflection_rate),
+            (split.staking, rates.staking_rate),
+            (split.lottery, rates.lottery_rate),
+            (split.liquidity, rates.liquidity_rate),
+            (split.treasury, rates.treasury_rate),
+        ];
+        // Builds without the lottery send the dust to the treasury, or without burn
+        // either to the liquidity vault
+        let dust_share = if cfg!(feature = "lottery") {
+            2
+        } else if cfg!(feature = "burn") {
+            4
+        } else {
+            3
+        };
+        for (index, (share, rate)) in shares.into_iter().enumerate() {
+            if index == dust_share {
+                continue;
+            }
+            // floor(exact / tax_rate)
+            prop_assert!(share as u128 * tax_rate <= exact(rate));
+            prop_assert!(exact(rate) < (share as u128 + 1) * tax_rate);
+        }
+        // Its exact share plus under one token of dust from each of the other four
+        let (share, rate) = shares[dust_share];
+        prop_assert!(share as u128 * tax_rate >= exact(rate));
+        prop_assert!((share as u128 * tax_rate) < exact(rate) + 4 * tax_rate);
+    }
+
+    #[test]
+    fn zero_rate_takes_nothing(amount in amount(), launch_buy in any::<bool>()) {
+        let rates = TaxRates::default();
+        prop_assert_eq!(rates.transfer_tax(amount, launch_buy), (0, Default::default()));
+    }
+}
+
+#[test]
+fn invalid_rates_are_rejected() {
+    let rates = built_only(TaxRates {
+        tax_rate: 500,
+        reflection_rate: 200,
+        staking_rate: 100,
+        lottery_rate: 100,
+        liquidity_rate: 50,
+        treasury_rate: 50,
+        sniper_tax_rate: 500,
+    });
+    assert!(rates.is_valid());
+    assert!(!TaxRates {
+        liquidity_rate: rates.liquidity_rate - 1,
+        ..rates
+    }
+    .is_valid());
+    assert!(!TaxRates { sniper_tax_rate: 10001, ..rates }.is_valid());
+    // Shares adding up to a tax above the maximum
+    assert!(!TaxRates {
+        tax_rate: MAX_TAX_RATE + 100,
+        liquidity_rate: rates.liquidity_rate + 600,
+        ..rates
+    }
+    .is_valid());
+}
+
+#[test]
+fn shares_need_their_mechanism_built() {
+    let rates = TaxRates {
+        tax_rate: 500,
+        liquidity_rate: 500,
+        ..TaxRates::default()
+    };
+    assert!(rates.is_valid());
+    // A share of the liquidity vault's moved to each mechanism, and to the treasury
+    // only `BuybackAndBurn` empties
+    let rates = TaxRates { liquidity_rate: 400, ..rates };
+    assert_eq!(TaxRates { reflection_rate: 100, ..rates }.is_valid(), cfg!(feature = "reflection"));
+    assert_eq!(TaxRates { staking_rate: 100, ..rates }.is_valid(), cfg!(feature = "staking"));
+    assert_eq!(TaxRates { lottery_rate: 100, ..rates }.is_valid(), cfg!(feature = "lottery"));
+    assert_eq!(TaxRates { treasury_rate: 100, ..rates }.is_valid(), cfg!(feature = "burn"));
+}
@@ -0,0 +1,498 @@
+//! Each validation the program performs, triggered on purpose.
+#![cfg(not(feature = "token-2022"))]
+
+mod common;
+
+#[cfg(feature = "lottery")]
+use codox::{lottery::LotteryState, HolderState};
+use codox::{error::CodoxError, instruction, CodoxTokenInstruction, ConfigUpdate, MAX_LIMIT_EXEMPT};
+use common::*;
+#[cfg(feature = "staking")]
+use solana_sdk::account::Account;
+#[cfg(any(feature = "staking", feature = "lottery"))]
+use solana_sdk::program_pack::Pack;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const SUPPLY: u64 = 1_000_000_000;
+
+#[cfg(feature = "lottery")]
+/// A deployment whose lottery round 0 was drawn between Alice and Bob, each
+/// winning one of the first two tiers
+async fn drawn_lottery() -> (TestEnv, Holder, Holder) {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY / 2).await;
+    let bob = env.new_holder(SUPPLY / 2).await;
+    env.register_holder(&alice).await.unwrap();
+    env.register_holder(&bob).await.unwrap();
+    env.transfer(&alice, &bob, 100_000_000).await.unwrap();
+    env.distribute_tax_vault().await.unwrap();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+    env.snapshot_balances(0, 0, &[&alice, &bob]).await.unwrap();
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    (env, alice, bob)
+}
+
+#[tokio::test]
+async fn initialize_requires_program_owned_state() {
+    let mut env = TestEnv::start_uninitialized().await;
+    let state = env.codox.state;
+    let mut account = env.account(&state).await.unwrap();
+    account.owner = Pubkey::new_unique();
+    env.set_account(&state, account);
+
+    let payer = env.payer();
+    let state = env.state_keypair.insecure_clone();
+    let initialize =
+        instruction::initialize_codox_token(&env.codox, &payer, TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE, 0, 0);
+    assert_codox_error(env.process(&[initialize], &[&state]).await, CodoxError::InvalidStateOwner);
+}
+
+#[tokio::test]
+async fn initialize_requires_state_signature() {
+    let mut env = TestEnv::start_uninitialized().await;
+    let payer = env.payer();
+    let mut initialize =
+        instruction::initialize_codox_token(&env.codox, &payer, TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE, 0, 0);
+    initialize.accounts[6].is_signer = false;
+    assert_instruction_error(env.process(&[initialize], &[]).await, InstructionError::MissingRequiredSignature);
+}
+
+#[tokio::test]
+async fn initialize_only_once() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+    let state = env.state_keypair.insecure_clone();
+    let initialize =
+        instruction::initialize_codox_token(&env.codox, &payer, TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE, 100, 100);
+    assert_codox_error(env.process(&[initialize], &[&state]).await, CodoxError::AlreadyInitialized);
+
+    #[cfg(feature = "lottery")]
+    env.initialize_lottery(false).await.unwrap();
+    #[cfg(feature = "lottery")]
+    assert_codox_error(env.initialize_lottery(true).await, CodoxError::AlreadyInitialized);
+}
+
+#[tokio::test]
+async fn initialize_checks_rates_and_pools() {
+    let mut env = TestEnv::start_uninitialized().await;
+    let payer = env.payer();
+    let state = env.state_keypair.insecure_clone();
+
+    // The shares must add up to the tax rate
+    let initialize =
+        instruction::initialize_codox_token(&env.codox, &payer, TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, 0, TREASURY_RATE, 0, 0);
+    assert_instruction_error(env.process(&[initialize], &[&state]).await, InstructionError::InvalidArgument);
+
+    // Pools must be owned by the pool authority
+    let mut codox = env.codox.clone();
+    codox.staking_pool = env.create_token_account(&codox.mint, &payer).await;
+    let initialize =
+        instruction::initialize_codox_token(&codox, &payer, TAX_RATE, REFLECTION_RATE, STAKING_RATE, LOTTERY_RATE, LIQUIDITY_RATE, TREASURY_RATE, 0, 0);
+    assert_codox_error(env.process(&[initialize], &[&state]).await, CodoxError::InvalidPoolAccount);
+}
+
+#[tokio::test]
+async fn transfer_checks_accounts() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+
+    let mut codox = env.codox.clone();
+    codox.tax_vault = codox.staking_pool;
+    let transfer = instruction::transfer(&codox, &alice.pubkey(), &alice.token, &bob.token, &bob.pubkey(), 1_000);
+    assert_codox_error(env.process(&[transfer], &[&alice.wallet]).await, CodoxError::InvalidPoolAccount);
+
+    let payer = env.payer();
+    let mut codox = env.codox.clone();
+    codox.mint = env.create_mint(&payer).await;
+    let transfer = instruction::transfer(&codox, &alice.pubkey(), &alice.token, &bob.token, &bob.pubkey(), 1_000);
+    assert_codox_error(env.process(&[transfer], &[&alice.wallet]).await, CodoxError::InvalidMint);
+
+    // Both token accounts must hold CODOX
+    let alice_other = env.create_token_account(&codox.mint, &alice.pubkey()).await;
+    let bob_other = env.create_token_account(&codox.mint, &bob.pubkey()).await;
+    env.mint_to(&codox.mint, &alice_other, 1_000).await;
+    for (source, destination) in [(alice_other, bob.token), (alice.token, bob_other)] {
+        let transfer = instruction::transfer(&env.codox, &alice.pubkey(), &source, &destination, &bob.pubkey(), 1_000);
+        assert_codox_error(env.process(&[transfer], &[&alice.wallet]).await, CodoxError::InvalidMint);
+    }
+}
+
+#[cfg(feature = "anti-whale")]
+#[tokio::test]
+async fn transfer_limits() {
+    let mut env = TestEnv::start(Options {
+        max_transfer_bps: 100,
+        max_wallet_bps: 150,
+        open_trading: true,
+    })
+    .await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+
+    assert_codox_error(env.transfer(&alice, &bob, SUPPLY / 50).await, CodoxError::TransferLimitExceeded);
+    env.transfer(&alice, &bob, SUPPLY / 100).await.unwrap();
+    // 1.9% of the supply after two transfers, above the 1.5% wallet limit
+    assert_codox_error(env.transfer(&alice, &bob, SUPPLY / 100).await, CodoxError::WalletLimitExceeded);
+}
+
+#[cfg(feature = "anti-whale")]
+#[tokio::test]
+async fn limits_only_loosen() {
+    let mut env = TestEnv::start(Options {
+        max_transfer_bps: 100,
+        ..Options::default()
+    })
+    .await;
+
+    let tighten = ConfigUpdate {
+        max_transfer_bps: Some(50),
+        ..ConfigUpdate::default()
+    };
+    assert_codox_error(env.update_config(tighten).await, CodoxError::LimitTightened);
+    let add_limit = ConfigUpdate {
+        max_wallet_bps: Some(200),
+        ..ConfigUpdate::default()
+    };
+    assert_codox_error(env.update_config(add_limit).await, CodoxError::LimitTightened);
+
+    env.update_config(ConfigUpdate {
+        max_transfer_bps: Some(200),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    assert_eq!(env.state().await.max_transfer_bps.get(), 200);
+}
+
+#[tokio::test]
+async fn trading_opens_once() {
+    let mut env = TestEnv::start(Options {
+        open_trading: false,
+        ..Options::default()
+    })
+    .await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    assert_codox_error(env.transfer(&alice, &bob, 1_000).await, CodoxError::TradingNotEnabled);
+
+    env.update_config(ConfigUpdate {
+        trading_enabled_at: Some(0),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    env.transfer(&alice, &bob, 1_000).await.unwrap();
+
+    let reopen = ConfigUpdate {
+        trading_enabled_at: Some(1),
+        ..ConfigUpdate::default()
+    };
+    assert_codox_error(env.update_config(reopen).await, CodoxError::TradingAlreadyEnabled);
+}
+
+#[tokio::test]
+async fn launch_buys_cool_down() {
+    let mut env = TestEnv::start(Options {
+        open_trading: false,
+        ..Options::default()
+    })
+    .await;
+    // Alice stands in for the DEX pool, an exempt seller
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    let mut limit_exempt = [Pubkey::default(); MAX_LIMIT_EXEMPT];
+    limit_exempt[0] = alice.pubkey();
+    let now = env.now().await;
+    env.update_config(ConfigUpdate {
+        limit_exempt: Some(limit_exempt),
+        trading_enabled_at: Some(now),
+        launch_window: Some(DAY),
+        buy_cooldown: Some(3600),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    env.register_holder(&bob).await.unwrap();
+
+    let buy = |amount| instruction::transfer(&env.codox, &alice.pubkey(), &alice.token, &bob.token, &bob.pubkey(), amount);
+    let (first, second) = (buy(1_000), buy(2_000));
+    env.process(&[first], &[&alice.wallet]).await.unwrap();
+    assert_eq!(env.holder_state(&bob.pubkey()).await.last_buy_time.get(), env.now().await);
+    assert_codox_error(env.process(&[second], &[&alice.wallet]).await, CodoxError::BuyCooldown);
+}
+
+#[tokio::test]
+async fn config_needs_the_authority() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let intruder = Keypair::new();
+    let update = instruction::update_config(
+        &env.codox,
+        &intruder.pubkey(),
+        ConfigUpdate {
+            paused: Some(true),
+            ..ConfigUpdate::default()
+        },
+    );
+    assert_instruction_error(
+        env.process(&[update], &[&intruder]).await,
+        InstructionError::MissingRequiredSignature,
+    );
+}
+
+#[tokio::test]
+async fn paused_transfers() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(SUPPLY).await;
+    let bob = env.new_holder(0).await;
+    env.update_config(ConfigUpdate {
+        paused: Some(true),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    assert_codox_error(env.transfer(&alice, &bob, 1_000).await, CodoxError::ProgramPaused);
+}
+
+#[cfg(feature = "burn")]
+#[tokio::test]
+async fn buyback_needs_configuration() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let amm = env.create_amm(1_000_000, 1_000_000).await;
+    let payer = env.payer();
+    let pool_authority = env.codox.pool_authority();
+    let treasury_quote_vault = env.create_token_account(&amm.quote_mint, &pool_authority).await;
+
+    let buyback = instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1, 1);
+    assert_codox_error(env.process(&[buyback], &[]).await, CodoxError::InvalidPoolAccount);
+
+    env.update_config(ConfigUpdate {
+        treasury_quote_vault: Some(treasury_quote_vault),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    let buyback = instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1, 2);
+    assert_codox_error(env.process(&[buyback], &[]).await, CodoxError::InvalidAmm);
+
+    env.update_config(ConfigUpdate {
+        amm_program: Some(amm.program_id),
+        amm_pool: Some(amm.pool),
+        ..ConfigUpdate::default()
+    })
+    .await
+    .unwrap();
+    // Buybacks stay off until a per-call maximum is set
+    let buyback = instruction::buyback_and_burn(&env.codox, &payer, &amm.accounts(), &treasury_quote_vault, 1, 3);
+    assert_codox_error(env.process(&[buyback], &[]).await, CodoxError::BuybackLimitExceeded);
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn snapshot_entries_and_page_capacity() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(1_000).await;
+    let bob = env.new_holder(1_000).await;
+    env.register_holder(&alice).await.unwrap();
+    env.register_holder(&bob).await.unwrap();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+
+    // Only a holder's associated token account counts
+    let mint = env.codox.mint;
+    let other_token = env.create_token_account(&mint, &alice.pubkey()).await;
+    env.mint_to(&mint, &other_token, 500).await;
+    let snapshot = instruction::snapshot_balances(&env.codox, &bob.pubkey(), 0, 0, &[(other_token, alice.pubkey())]);
+    assert_codox_error(env.process(&[snapshot], &[&bob.wallet]).await, CodoxError::InvalidSnapshotAccount);
+
+    // Anyone records tickets, and a holder passed twice is entered once
+    let alice_token = (alice.token, alice.pubkey());
+    let snapshot = instruction::snapshot_balances(&env.codox, &bob.pubkey(), 0, 0, &[alice_token, alice_token]);
+    env.process(&[snapshot], &[&bob.wallet]).await.unwrap();
+    let page = env.ticket_page(0, 0).await;
+    assert_eq!((page.count, page.tickets), (1, 1_000));
+    assert_eq!(env.holder_state(&alice.pubkey()).await.lottery_received.get(), u64::MAX);
+    // A holder recorded in an earlier call isn't entered again
+    env.snapshot_balances(0, 0, &[&alice]).await.unwrap();
+    assert_eq!(env.ticket_page(0, 0).await.count, 1);
+
+    // Page 1 waits for page 0 to fill up, and a full page takes no more entries
+    assert_codox_error(env.open_ticket_page(0, 1).await, CodoxError::TicketPageNotFull);
+    env.fill_ticket_page(0, 0, &alice.pubkey()).await;
+    assert_codox_error(env.snapshot_balances(0, 0, &[&bob]).await, CodoxError::TicketPageFull);
+    env.open_ticket_page(0, 1).await.unwrap();
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn draw_needs_every_page_and_the_interval() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(1_000).await;
+    env.register_holder(&alice).await.unwrap();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+    assert_codox_error(env.draw_lottery(0, 1).await, CodoxError::LotteryRoundOpen);
+
+    // Round 0 runs for the lottery interval from initialization
+    assert_codox_error(env.close_lottery_round().await, CodoxError::LotteryDrawTooEarly);
+    env.advance_clock(DAY).await;
+
+    // Only the authority or keeper closes a round, which then takes no more tickets
+    let close = instruction::close_lottery_round(&env.codox, &alice.pubkey());
+    assert_instruction_error(env.process(&[close], &[&alice.wallet]).await, InstructionError::MissingRequiredSignature);
+    let payer = env.payer();
+    let close = instruction::close_lottery_round(&env.codox, &payer);
+    env.process(std::slice::from_ref(&close), &[]).await.unwrap();
+    assert_codox_error(env.snapshot_balances(0, 0, &[&alice]).await, CodoxError::LotteryRoundClosed);
+    assert_codox_error(env.open_ticket_page(0, 1).await, CodoxError::LotteryRoundClosed);
+    assert_codox_error(env.process(&[close], &[]).await, CodoxError::LotteryRoundClosed);
+
+    // The draw waits for the draw slot
+    assert_codox_error(env.draw_lottery(0, 1).await, CodoxError::LotteryDrawTooEarly);
+    let draw_slot = env.lottery().await.draw_slot;
+    env.advance_past_slot(draw_slot).await;
+    assert_codox_error(env.draw_lottery(0, 0).await, CodoxError::TicketPagesMissing);
+    env.draw_lottery(0, 1).await.unwrap();
+
+    assert_codox_error(env.close_lottery_round().await, CodoxError::LotteryDrawTooEarly);
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(1, 0).await.unwrap();
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn pdas_funded_in_advance() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(1_000).await;
+    env.fund(&alice.pubkey(), 1_000_000_000).await;
+
+    // Anyone can send lamports to an address before the program creates its account there,
+    // short of its rent or beyond it
+    let short = env.rent().await.minimum_balance(0);
+    for address in [env.codox.lottery(), env.codox.ticket_page(0, 0), env.codox.holder_state(&alice.pubkey())] {
+        env.fund(&address, short).await;
+    }
+    env.fund(&env.codox.lottery_round(0), 1_000_000_000).await;
+
+    env.register_holder(&alice).await.unwrap();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    assert_eq!(env.lottery().await.round, 1);
+    let rent = env.rent().await;
+    for (address, len) in [
+        (env.codox.lottery(), LotteryState::LEN),
+        (env.codox.holder_state(&alice.pubkey()), HolderState::LEN),
+    ] {
+        let account = env.account(&address).await.unwrap();
+        assert_eq!(account.owner, env.codox.program_id);
+        assert_eq!(account.data.len(), len);
+        assert!(rent.is_exempt(account.lamports, len));
+    }
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn prizes_are_claimed_once_within_the_period() {
+    let (mut env, alice, bob) = drawn_lottery().await;
+    let round = env.lottery_round(0).await;
+    let (first, second) = if round.winners[0] == alice.pubkey() { (&alice, &bob) } else { (&bob, &alice) };
+
+    // Only the winner, only once
+    let claim = instruction::claim_lottery_prize(&env.codox, &second.pubkey(), &second.token, 0, 0, &[]);
+    assert_instruction_error(
+        env.process(&[claim], &[&second.wallet]).await,
+        InstructionError::MissingRequiredSignature,
+    );
+    let claim = instruction::claim_lottery_prize(&env.codox, &first.pubkey(), &first.token, 0, 0, &[]);
+    env.process(std::slice::from_ref(&claim), &[&first.wallet]).await.unwrap();
+    assert_codox_error(env.process(&[claim], &[&first.wallet]).await, CodoxError::PrizeUnavailable);
+
+    // The third tier went unpaid
+    let claim = instruction::claim_lottery_prize(&env.codox, &first.pubkey(), &first.token, 0, 2, &[]);
+    assert_codox_error(env.process(&[claim], &[&first.wallet]).await, CodoxError::PrizeUnavailable);
+
+    let expire = instruction::expire_lottery_prizes(&env.codox, 0);
+    assert_codox_error(env.process(std::slice::from_ref(&expire), &[]).await, CodoxError::ClaimPeriodActive);
+
+    env.advance_clock(CLAIM_PERIOD + 1).await;
+    let claim = instruction::claim_lottery_prize(&env.codox, &second.pubkey(), &second.token, 0, 1, &[]);
+    assert_codox_error(env.process(&[claim], &[&second.wallet]).await, CodoxError::ClaimDeadlinePassed);
+
+    env.process(std::slice::from_ref(&expire), &[]).await.unwrap();
+    assert_codox_error(env.process(&[expire], &[]).await, CodoxError::PrizeUnavailable);
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn pages_close_after_their_draw() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let payer = env.payer();
+    env.initialize_lottery(false).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+
+    let close = instruction::close_ticket_page(&env.codox, &payer, 0, 0);
+    assert_codox_error(env.process(std::slice::from_ref(&close), &[]).await, CodoxError::TicketPageInUse);
+    env.advance_clock(DAY).await;
+    env.close_lottery_round().await.unwrap();
+    env.draw_lottery(0, 1).await.unwrap();
+    env.process(&[close], &[]).await.unwrap();
+}
+
+#[cfg(feature = "lottery")]
+#[tokio::test]
+async fn deposits_match_the_lottery_mode() {
+    let (mut env, alice, _) = drawn_lottery().await;
+    let deposit = instruction::deposit_to_lottery(&env.codox, &alice.pubkey(), &alice.token, 1_000, &[]);
+    assert_codox_error(env.process(&[deposit], &[&alice.wallet]).await, CodoxError::LotteryModeMismatch);
+
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(1_000).await;
+    env.initialize_lottery(true).await.unwrap();
+    env.open_ticket_page(0, 0).await.unwrap();
+    assert_codox_error(env.snapshot_balances(0, 0, &[&alice]).await, CodoxError::LotteryModeMismatch);
+
+    let deposit = instruction::deposit_to_lottery(&env.codox, &alice.pubkey(), &alice.token, 1_000, &[]);
+    env.process(&[deposit], &[&alice.wallet]).await.unwrap();
+    let withdraw = instruction::withdraw_from_lottery(&env.codox, &alice.pubkey(), &alice.token, 1_000, &[]);
+    assert_codox_error(env.process(&[withdraw], &[&alice.wallet]).await, CodoxError::DepositLocked);
+}
+
+#[cfg(feature = "staking")]
+#[tokio::test]
+async fn unknown_state_account() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let alice = env.new_holder(1_000).await;
+    let mut codox = env.codox.clone();
+    codox.state = Pubkey::new_unique();
+    env.set_account(
+        &codox.state,
+        Account {
+            lamports: 1_000_000_000,
+            data: vec![0; codox::CodoxTokenState::LEN],
+            owner: Pubkey::new_unique(),
+            ..Account::default()
+        },
+    );
+    let stake = instruction::stake(&codox, &alice.pubkey(), &alice.token, 1_000, &[]);
+    assert_codox_error(env.process(&[stake], &[&alice.wallet]).await, CodoxError::InvalidStateOwner);
+}
+
+#[tokio::test]
+async fn retired_instructions_keep_their_tags() {
+    let mut env = TestEnv::start(Options::default()).await;
+    let data = borsh::to_vec(&CodoxTokenInstruction::ParticipateInLottery).unwrap();
+    assert_eq!(data, [4]);
+    let participate = Instruction::new_with_bytes(env.codox.program_id, &data, vec![]);
+    assert_codox_error(env.process(&[participate], &[]).await, CodoxError::InstructionRetired);
+}
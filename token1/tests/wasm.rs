@@ -0,0 +1,120 @@
+//! The WebAssembly bindings return what the program computes. Only the success
+//! paths run natively, since building a `JsError` needs a JavaScript host.
+#![cfg(feature = "wasm")]
+
+use borsh::BorshDeserialize;
+use codox::{
+    lottery::{LotteryState, TicketPage, TICKET_ENTRY_LEN},
+    preview::{ClaimPreview, LotteryOddsPreview, TransferTaxPreview},
+    wasm, CodoxTokenState, HolderState, TaxRates, TaxSplit, REFLECTION_INDEX_SCALE,
+};
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+
+const DAY: i64 = 86400;
+
+fn rates() -> TaxRates {
+    TaxRates::new(500, 200, 100, 100, 50, 50, 3000)
+}
+
+#[test]
+fn tax_math_matches_the_program() {
+    let rates = rates();
+    for (amount, launch_buy) in [(0, false), (1_000_003, false), (1_000_003, true), (u64::MAX, true)] {
+        let (tax_amount, split) = rates.transfer_tax(amount, launch_buy);
+        assert_eq!(
+            wasm::transfer_tax(&rates, amount, launch_buy),
+            TransferTaxPreview {
+                net_amount: amount - tax_amount,
+                tax_amount,
+                split,
+                launch_buy,
+            }
+        );
+        assert_eq!(wasm::staking_tax_share(&rates, amount, launch_buy), split.staking);
+        assert_eq!(wasm::tax_split(&rates, tax_amount), rates.split(tax_amount));
+    }
+}
+
+#[test]
+fn state_decoding_and_reflection_match_the_program() {
+    let mut state = CodoxTokenState::unpack_unchecked(&[0; CodoxTokenState::LEN]).unwrap();
+    state.is_initialized = true.into();
+    state.authority = Pubkey::new_unique();
+    state.tax_rate = 500.into();
+    state.reflection_rate = 200.into();
+    state.staking_rate = 100.into();
+    state.lottery_rate = 100.into();
+    state.liquidity_rate = 50.into();
+    state.treasury_rate = 50.into();
+    state.sniper_tax_rate = 3000.into();
+    state.limit_exempt[1] = Pubkey::new_unique();
+    state.accrue_reflection(5_000_000, 1_000_000_000);
+    state.undistributed = TaxSplit { reflection: 7, ..TaxSplit::default() }.into();
+    let mut data = vec![0; CodoxTokenState::LEN];
+    state.pack_into_slice(&mut data);
+
+    let decoded = wasm::JsCodoxTokenState::decode(&data).unwrap();
+    assert_eq!(decoded.authority(), state.authority.to_string());
+    assert_eq!(decoded.reflection_index(), 4_000_000 * REFLECTION_INDEX_SCALE / 1_000_000_000);
+    assert_eq!(decoded.reflection_bonus(), 1_000_000);
+    assert_eq!(decoded.limit_exempt()[1], state.limit_exempt[1].to_string());
+    assert_eq!(decoded.undistributed(), state.undistributed.get());
+    assert_eq!(decoded.tax_rates(), rates());
+
+    let holder_state = HolderState {
+        holder: Pubkey::new_unique(),
+        last_reflection_claim: DAY.into(),
+        reflection_index_checkpoint: (REFLECTION_INDEX_SCALE / 1000).into(),
+        reflection_balance: 10_000_000.into(),
+        ..HolderState::unpack_unchecked(&[0; HolderState::LEN]).unwrap()
+    };
+    let mut data = vec![0; HolderState::LEN];
+    holder_state.pack_into_slice(&mut data);
+    let decoded_holder = wasm::JsHolderState::decode(&data).unwrap();
+    assert_eq!(decoded_holder.holder(), holder_state.holder.to_string());
+    assert_eq!(decoded_holder.last_reflection_claim(), DAY);
+    assert_eq!(decoded_holder.reflection_balance(), 10_000_000);
+
+    for (balance, pool_balance, now) in [(10_000_000, u64::MAX, 31 * DAY), (10_000_000, 100, 31 * DAY), (0, 0, 0)] {
+        let (reward, multiplier) = holder_state.pending_reflection(&state, balance, pool_balance, now);
+        assert_eq!(
+            wasm::pending_reflection(&decoded, &decoded_holder, balance, pool_balance, now),
+            ClaimPreview { reward, multiplier }
+        );
+    }
+    assert_eq!(wasm::reflection_accrual(5_000_000, 1_000_000_000), codox::reflection_accrual(5_000_000, 1_000_000_000));
+    assert_eq!(wasm::reflection_bonus_share(5_000_000), 1_000_000);
+    assert_eq!(wasm::time_multiplier(30 * DAY), 160);
+    assert_eq!(wasm::reflection_reward(10_000_000, REFLECTION_INDEX_SCALE / 1000, 160), 16_000);
+}
+
+#[test]
+fn lottery_odds_match_the_program() {
+    let mut lottery_data = vec![0; LotteryState::LEN];
+    lottery_data[0] = 1;
+    let mut lottery = LotteryState::try_from_slice(&lottery_data).unwrap();
+    lottery.round = 3;
+    lottery.jackpot = 1_000;
+    lottery.prize_tiers = [5000, 3000, 1000];
+    lottery.jackpot_chance = 100;
+    lottery.total_tickets = 400;
+    let lottery_data = borsh::to_vec(&lottery).unwrap();
+
+    let odds = wasm::lottery_odds(&lottery_data, 100, 11_000).unwrap();
+    assert_eq!(odds, LotteryOddsPreview::new(&lottery, 100, 11_000));
+    assert_eq!(odds.tier_prizes(), [5_000, 3_000, 1_000]);
+
+    let holder = Pubkey::new_unique();
+    let page = TicketPage {
+        round: 3,
+        count: 2,
+        tickets: 150,
+        is_initialized: true,
+        ..TicketPage::default()
+    };
+    let mut page_data = vec![0; TicketPage::LEN + 2 * TICKET_ENTRY_LEN];
+    page.pack_into_slice(&mut page_data[..TicketPage::LEN]);
+    TicketPage::set_entry(&mut page_data, 0, &Pubkey::new_unique(), 0, 50);
+    TicketPage::set_entry(&mut page_data, 1, &holder, 50, 100);
+    assert_eq!(wasm::holder_tickets(&page_data, &holder.to_string()).unwrap(), 100);
+}